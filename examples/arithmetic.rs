@@ -0,0 +1,65 @@
+//! Totals an order held in memory: adds up the item prices, takes off a discount and counts the order.
+//!
+//! Run with `cargo run --example arithmetic`.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use cpu::{BuildError, CpuBuilder, Data, Instruction, MemOp, Operand, Register, Verbosity, CPU, IS};
+
+/// Instructions the program may run before it is considered stuck
+pub const INSTRUCTION_LIMIT: u64 = 100;
+
+fn mem(label: &str) -> Operand {
+    Operand::Memory(MemOp::Address(label.to_string()))
+}
+
+/// Builds the program, writing to `output`
+pub fn cpu(output: impl Write + 'static) -> Result<CPU, BuildError> {
+    let data = HashMap::from([
+        ("item0".to_string(), Data::Word(1250)),
+        ("item1".to_string(), Data::Word(830)),
+        ("item2".to_string(), Data::Word(415)),
+        ("discount".to_string(), Data::Word(300)),
+        ("subtotal".to_string(), Data::Word(0)),
+        ("total".to_string(), Data::Word(0)),
+        ("orders".to_string(), Data::Word(41)),
+    ]);
+    let code = vec![
+        Instruction::new(IS::Mov, vec![Operand::Register(Register::AX), mem("item0")]),
+        Instruction::new(IS::Add, vec![Operand::Register(Register::AX), mem("item1")]),
+        Instruction::new(IS::Add, vec![Operand::Register(Register::AX), mem("item2")]),
+        Instruction::new(IS::Mov, vec![mem("subtotal"), Operand::Register(Register::AX)]),
+        Instruction::new(IS::Sub, vec![Operand::Register(Register::AX), mem("discount")]),
+        Instruction::new(IS::Mov, vec![mem("total"), Operand::Register(Register::AX)]),
+        // Memory is updated in place as well
+        Instruction::new(IS::Add, vec![mem("orders"), Operand::Immediate(Data::Word(1))]),
+    ];
+    CpuBuilder::new(data, code)
+        .verbosity(Verbosity::Quiet)
+        .instruction_limit(INSTRUCTION_LIMIT)
+        .guest_output(output)
+        .build()
+}
+
+fn main() {
+    let mut cpu = match cpu(std::io::stdout()) {
+        Ok(cpu) => cpu,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    match cpu.run() {
+        Ok(outcome) => {
+            for label in ["subtotal", "total", "orders"] {
+                println!("{} = {:?}", label, cpu.read_label(label));
+            }
+            println!("{}", outcome);
+        },
+        Err(fault) => {
+            eprintln!("{}", fault);
+            std::process::exit(101);
+        }
+    }
+}
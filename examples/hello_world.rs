@@ -0,0 +1,52 @@
+//! Prints "Hello, world!" with the write syscall.
+//!
+//! Run with `cargo run --example hello_world`.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use cpu::{syscall_abi, BuildError, CpuBuilder, Data, Instruction, Operand, Verbosity, CPU, IS};
+
+/// Instructions the program may run before it is considered stuck
+pub const INSTRUCTION_LIMIT: u64 = 100;
+
+pub const MESSAGE: &[u8] = b"Hello, world!\n";
+
+/// Builds the program, writing to `output`
+pub fn cpu(output: impl Write + 'static) -> Result<CPU, BuildError> {
+    // The message is packed into dwords `msg0`, `msg1`, ... which are laid out one after the other
+    let data: HashMap<String, Data> = MESSAGE.chunks(4).enumerate().map(|(index, chunk)| {
+        let mut bytes = [0; 4];
+        bytes[..chunk.len()].copy_from_slice(chunk);
+        (format!("msg{}", index), Data::Dword(u32::from_le_bytes(bytes)))
+    }).collect();
+    let code = vec![
+        Instruction::new(IS::Mov, vec![Operand::Register(syscall_abi::NUMBER), Operand::Immediate(Data::Word(syscall_abi::SYS_WRITE as u16))]),
+        Instruction::new(IS::Mov, vec![Operand::Register(syscall_abi::ARG1), Operand::Immediate(Data::Word(1))]),
+        Instruction::new(IS::Mov, vec![Operand::Register(syscall_abi::ARG2), Operand::LabelOffset("msg0".to_string())]),
+        Instruction::new(IS::Mov, vec![Operand::Register(syscall_abi::ARG3), Operand::Immediate(Data::Word(MESSAGE.len() as u16))]),
+        Instruction::new(IS::Syscall, vec![]),
+    ];
+    CpuBuilder::new(data, code)
+        .verbosity(Verbosity::Quiet)
+        .instruction_limit(INSTRUCTION_LIMIT)
+        .guest_output(output)
+        .build()
+}
+
+fn main() {
+    let mut cpu = match cpu(std::io::stdout()) {
+        Ok(cpu) => cpu,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    match cpu.run() {
+        Ok(outcome) => println!("{}", outcome),
+        Err(fault) => {
+            eprintln!("{}", fault);
+            std::process::exit(101);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! The program of the crate documentation: adds two numbers, converts the sum to ASCII and prints it.
+//!
+//! It is written with this CPU's instruction set, so where the documented program uses an instruction
+//! or register the CPU doesn't have, the closest equivalent stands in:
+//! - EBX is the buffer pointer instead of EDI
+//! - `mov edx, 0`, `sub ebx, 1` and `cmp eax, 0` replace `xor edx, edx`, `dec edi` and `test eax, eax`
+//! - the syscalls take their numbers and arguments from `syscall_abi`
+//! - the `output` buffer is three zeroed labels laid out one after the other, as there is no `.bss` section
+//!
+//! Run with `cargo run --example int_to_str`.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use cpu::{syscall_abi, BuildError, CpuBuilder, Data, DataWidth, Instruction, MemOp, Operand, Register, Verbosity, CPU, IS};
+
+/// Instructions the program may run before it is considered stuck
+pub const INSTRUCTION_LIMIT: u64 = 200;
+
+/// Bytes in the output buffer, enough for any dword in decimal
+const OUTPUT_LENGTH: u16 = 10;
+
+/// Index of the first instruction of `int_to_str`
+const INT_TO_STR: u16 = 21;
+
+fn reg(register: Register) -> Operand {
+    Operand::Register(register)
+}
+
+fn mem(label: &str) -> Operand {
+    Operand::Memory(MemOp::Address(label.to_string()))
+}
+
+fn offset(label: &str) -> Operand {
+    Operand::LabelOffset(label.to_string())
+}
+
+fn word(value: u16) -> Operand {
+    Operand::Immediate(Data::Word(value))
+}
+
+fn dword(value: u32) -> Operand {
+    Operand::Immediate(Data::Dword(value))
+}
+
+/// Builds the program adding `num1` and `num2`, writing to `output`
+pub fn cpu(num1: u16, num2: u16, output: impl Write + 'static) -> Result<CPU, BuildError> {
+    program(num1, num2, output).build()
+}
+
+/// The program adding `num1` and `num2`, configured but not built yet
+pub fn program(num1: u16, num2: u16, output: impl Write + 'static) -> CpuBuilder {
+    let data = HashMap::from([
+        ("num1".to_string(), Data::Word(num1)),
+        ("num2".to_string(), Data::Word(num2)),
+        ("result".to_string(), Data::Dword(0)),
+        ("newline".to_string(), Data::Byte(b'\n')),
+        ("output0".to_string(), Data::Dword(0)),
+        ("output1".to_string(), Data::Dword(0)),
+        ("output2".to_string(), Data::Word(0)),
+    ]);
+    let code = vec![
+        // Load values from memory
+        Instruction::new(IS::Mov, vec![reg(Register::EAX), mem("num1")]),
+        Instruction::new(IS::Add, vec![reg(Register::EAX), mem("num2")]),
+        // Store the result in memory
+        Instruction::new(IS::Mov, vec![mem("result"), reg(Register::EAX)]),
+        // Convert result to string, the digits are stored backwards from the end of the buffer
+        Instruction::new(IS::Mov, vec![reg(Register::EBX), offset("output0")]),
+        Instruction::new(IS::Add, vec![reg(Register::EBX), dword(OUTPUT_LENGTH as u32)]),
+        Instruction::new(IS::Call, vec![word(INT_TO_STR)]),
+        // Print the result, from the first digit to the end of the buffer
+        Instruction::new(IS::Mov, vec![reg(syscall_abi::NUMBER), word(syscall_abi::SYS_WRITE as u16)]),
+        Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG1), word(1)]),
+        Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG2), reg(Register::EBX)]),
+        Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG3), offset("output0")]),
+        Instruction::new(IS::Add, vec![reg(syscall_abi::ARG3), word(OUTPUT_LENGTH)]),
+        Instruction::new(IS::Sub, vec![reg(syscall_abi::ARG3), reg(syscall_abi::ARG2)]),
+        Instruction::new(IS::Syscall, vec![]),
+        // Print newline
+        Instruction::new(IS::Mov, vec![reg(syscall_abi::NUMBER), word(syscall_abi::SYS_WRITE as u16)]),
+        Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG1), word(1)]),
+        Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG2), offset("newline")]),
+        Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG3), word(1)]),
+        Instruction::new(IS::Syscall, vec![]),
+        // Exit program
+        Instruction::new(IS::Mov, vec![reg(syscall_abi::NUMBER), word(syscall_abi::SYS_EXIT as u16)]),
+        Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG1), word(0)]),
+        Instruction::new(IS::Syscall, vec![]),
+        // int_to_str: converts EAX to ASCII, storing the digits before EBX and leaving EBX on the first one
+        Instruction::new(IS::Mov, vec![reg(Register::ECX), dword(10)]),
+        // .loop:
+        Instruction::new(IS::Mov, vec![reg(Register::EDX), dword(0)]),
+        Instruction::new(IS::Div, vec![reg(Register::ECX)]),
+        Instruction::new(IS::Add, vec![reg(Register::EDX), dword(b'0' as u32)]),
+        Instruction::new(IS::Sub, vec![reg(Register::EBX), dword(1)]),
+        Instruction::new(IS::Mov, vec![Operand::Memory(MemOp::Indirect(DataWidth::Byte, Register::EBX, 0)), reg(Register::EDX)]),
+        Instruction::new(IS::Cmp, vec![reg(Register::EAX), dword(0)]),
+        Instruction::new(IS::Jnz, vec![word(INT_TO_STR + 1)]),
+        Instruction::new(IS::Ret, vec![]),
+    ];
+    CpuBuilder::new(data, code)
+        .verbosity(Verbosity::Quiet)
+        .instruction_limit(INSTRUCTION_LIMIT)
+        .guest_output(output)
+        .code_label("int_to_str", INT_TO_STR as usize)
+}
+
+fn main() {
+    let mut cpu = match cpu(10, 20, std::io::stdout()) {
+        Ok(cpu) => cpu,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    if let Err(fault) = cpu.run() {
+        eprintln!("{}", fault);
+        std::process::exit(101);
+    }
+}
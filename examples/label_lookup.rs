@@ -0,0 +1,63 @@
+//! Times a loop reading a data label on every iteration, once through an interned `[label]` operand and once
+//! through a `word ptr [label]` operand, which is still looked up by name on every execution.
+//!
+//! Run with `cargo run --release --example label_lookup`.
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use cpu::{BuildError, CpuBuilder, Data, DataWidth, Instruction, MemOp, Operand, Register, Verbosity, CPU, IS};
+
+/// Iterations of the timed loop
+const ITERATIONS: u16 = 60000;
+
+fn reg(register: Register) -> Operand {
+    Operand::Register(register)
+}
+
+fn word(value: u16) -> Operand {
+    Operand::Immediate(Data::Word(value))
+}
+
+/// The `[step]` operand, interned when the program is loaded
+pub fn interned() -> Operand {
+    Operand::Memory(MemOp::Address("step".to_string()))
+}
+
+/// The `word ptr [step]` operand, looked up by name when it is executed
+pub fn by_name() -> Operand {
+    Operand::Memory(MemOp::Ptr(DataWidth::Word, "step".to_string(), 0))
+}
+
+/// Builds the loop adding `step` to BX `iterations` times, reading it through `operand`
+pub fn cpu(iterations: u16, operand: Operand) -> Result<CPU, BuildError> {
+    let data = HashMap::from([("step".to_string(), Data::Word(3))]);
+    let code = vec![
+        Instruction::new(IS::Mov, vec![reg(Register::CX), word(0)]),
+        // .loop:
+        Instruction::new(IS::Add, vec![reg(Register::BX), operand]),
+        Instruction::new(IS::Add, vec![reg(Register::CX), word(1)]),
+        Instruction::new(IS::Cmp, vec![reg(Register::CX), word(iterations)]),
+        Instruction::new(IS::Jnz, vec![word(1)]),
+    ];
+    CpuBuilder::new(data, code)
+        .verbosity(Verbosity::Quiet)
+        .instruction_limit(iterations as u64 * 4 + 2)
+        .build()
+}
+
+fn main() {
+    for (name, operand) in [("interned [step]", interned()), ("word ptr [step]", by_name())] {
+        let mut cpu = cpu(ITERATIONS, operand).unwrap();
+        let start = Instant::now();
+        let outcome = cpu.run().unwrap();
+        let elapsed = start.elapsed();
+        println!(
+            "{:<16} {} instructions in {:?} ({:.1} ns each)",
+            name,
+            outcome.instructions,
+            elapsed,
+            elapsed.as_nanos() as f64 / outcome.instructions as f64
+        );
+    }
+}
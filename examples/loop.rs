@@ -0,0 +1,53 @@
+//! Sums the numbers from 1 to 100 with a loop closed by a conditional jump.
+//!
+//! Run with `cargo run --example loop`.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use cpu::{BuildError, CpuBuilder, Data, Instruction, MemOp, Operand, Register, Verbosity, CPU, IS};
+
+/// Instructions the program may run before it is considered stuck, a few hundred more than the loop needs
+pub const INSTRUCTION_LIMIT: u64 = 1000;
+
+pub const LAST: u16 = 100;
+
+/// Builds the program, writing to `output`
+pub fn cpu(output: impl Write + 'static) -> Result<CPU, BuildError> {
+    let data = HashMap::from([("sum".to_string(), Data::Word(0))]);
+    let code = vec![
+        Instruction::new(IS::Mov, vec![Operand::Register(Register::AX), Operand::Immediate(Data::Word(0))]),
+        Instruction::new(IS::Mov, vec![Operand::Register(Register::CX), Operand::Immediate(Data::Word(1))]),
+        // loop:
+        Instruction::new(IS::Add, vec![Operand::Register(Register::AX), Operand::Register(Register::CX)]),
+        Instruction::new(IS::Add, vec![Operand::Register(Register::CX), Operand::Immediate(Data::Word(1))]),
+        Instruction::new(IS::Cmp, vec![Operand::Register(Register::CX), Operand::Immediate(Data::Word(LAST + 1))]),
+        Instruction::new(IS::Jnz, vec![Operand::Immediate(Data::Word(2))]),
+        Instruction::new(IS::Mov, vec![Operand::Memory(MemOp::Address("sum".to_string())), Operand::Register(Register::AX)]),
+    ];
+    CpuBuilder::new(data, code)
+        .verbosity(Verbosity::Quiet)
+        .instruction_limit(INSTRUCTION_LIMIT)
+        .guest_output(output)
+        .build()
+}
+
+fn main() {
+    let mut cpu = match cpu(std::io::stdout()) {
+        Ok(cpu) => cpu,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    match cpu.run() {
+        Ok(outcome) => {
+            println!("sum = {:?}", cpu.read_label("sum"));
+            println!("{}", outcome);
+        },
+        Err(fault) => {
+            eprintln!("{}", fault);
+            std::process::exit(101);
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! Finds the largest of three numbers by calling a `max` subroutine twice.
+//!
+//! Run with `cargo run --example subroutine`.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use cpu::{syscall_abi, BuildError, CpuBuilder, Data, Instruction, MemOp, Operand, Register, Verbosity, CPU, IS};
+
+/// Instructions the program may run before it is considered stuck
+pub const INSTRUCTION_LIMIT: u64 = 100;
+
+/// Index of the first instruction of the `max` subroutine
+const MAX: u16 = 9;
+
+fn mem(label: &str) -> Operand {
+    Operand::Memory(MemOp::Address(label.to_string()))
+}
+
+fn reg(register: Register) -> Operand {
+    Operand::Register(register)
+}
+
+fn word(value: u16) -> Operand {
+    Operand::Immediate(Data::Word(value))
+}
+
+/// Builds the program, writing to `output`
+pub fn cpu(output: impl Write + 'static) -> Result<CPU, BuildError> {
+    let data = HashMap::from([
+        ("a".to_string(), Data::Word(17)),
+        ("b".to_string(), Data::Word(42)),
+        ("c".to_string(), Data::Word(30)),
+        ("result".to_string(), Data::Word(0)),
+    ]);
+    let code = vec![
+        Instruction::new(IS::Mov, vec![reg(Register::AX), mem("a")]),
+        Instruction::new(IS::Mov, vec![reg(Register::BX), mem("b")]),
+        Instruction::new(IS::Call, vec![word(MAX)]),
+        Instruction::new(IS::Mov, vec![reg(Register::BX), mem("c")]),
+        Instruction::new(IS::Call, vec![word(MAX)]),
+        Instruction::new(IS::Mov, vec![mem("result"), reg(Register::AX)]),
+        Instruction::new(IS::Mov, vec![reg(syscall_abi::NUMBER), word(syscall_abi::SYS_EXIT as u16)]),
+        Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG1), word(0)]),
+        Instruction::new(IS::Syscall, vec![]),
+        // max: leaves the larger of ax and bx in ax
+        Instruction::new(IS::Cmp, vec![reg(Register::AX), reg(Register::BX)]),
+        Instruction::new(IS::Jnc, vec![word(MAX + 3)]),
+        Instruction::new(IS::Mov, vec![reg(Register::AX), reg(Register::BX)]),
+        Instruction::new(IS::Ret, vec![]),
+    ];
+    CpuBuilder::new(data, code)
+        .verbosity(Verbosity::Quiet)
+        .instruction_limit(INSTRUCTION_LIMIT)
+        .guest_output(output)
+        .build()
+}
+
+fn main() {
+    let mut cpu = match cpu(std::io::stdout()) {
+        Ok(cpu) => cpu,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    match cpu.run() {
+        Ok(outcome) => {
+            println!("result = {:?}", cpu.read_label("result"));
+            println!("{}", outcome);
+        },
+        Err(fault) => {
+            eprintln!("{}", fault);
+            std::process::exit(101);
+        }
+    }
+}
@@ -0,0 +1,9125 @@
+//! This is a simple implementation of a CPU in Rust
+//! It is meant to assume simple assembly instructions, say, mov, add, sub, etc.
+//! Example intended usage;
+//! ```text
+//! section .data
+//! num1 dw 10       ; First number (16-bit integer)
+//! num2 dw 20       ; Second number (16-bit integer)
+//! result dd 0      ; Variable to store the result
+//! newline db 10    ; Newline character for formatting
+//!
+//! section .bss
+//! output resb 10   ; Buffer to store the result as a string
+//!
+//! section .text
+//! global _start
+//!
+//! _start:
+//! ; Load values from memory
+//! mov eax, [num1]  ; Load first number into EAX
+//! add eax, [num2]  ; Add second number
+//!
+//! ; Store the result in memory
+//! mov [result], eax
+//!
+//! ; Convert result to string (integer to ASCII)
+//! mov edi, output  ; Destination buffer
+//! call int_to_str  ; Convert EAX to ASCII string
+//!
+//! ; Print the result
+//! mov eax, 1       ; syscall: sys_write
+//! mov edi, 1       ; file descriptor: stdout
+//! mov rsi, output  ; buffer
+//! mov rdx, 10      ; max 10 bytes
+//! syscall
+//!
+//! ; Print newline
+//! mov eax, 1
+//! mov edi, 1
+//! mov rsi, newline
+//! mov rdx, 1
+//! syscall
+//!
+//! ; Exit program
+//! mov eax, 60      ; syscall: sys_exit
+//! xor edi, edi     ; status 0
+//! syscall
+//!
+//! ; -----------------------------------
+//! ; Convert integer in EAX to ASCII
+//! ; -----------------------------------
+//! int_to_str:
+//! mov ecx, 10      ; Base 10 divisor
+//! mov ebx, edi     ; Save buffer pointer
+//!
+//! .loop:
+//!     xor edx, edx
+//!     div ecx      ; EAX = EAX / 10, remainder in EDX
+//!     add dl, '0'  ; Convert to ASCII
+//!     dec edi
+//!     mov [edi], dl
+//!     test eax, eax
+//!     jnz .loop
+//!
+//! mov rsi, edi     ; Update buffer pointer
+//! ret
+//!```
+//! The above code is a simple assembly code that adds two numbers and prints the result.
+//! `examples/int_to_str.rs` runs it on this CPU, see `cargo run --example int_to_str`.
+
+use std::{cell::RefCell, collections::{BTreeMap, BTreeSet, HashMap, VecDeque}, fmt::Debug, io::{stdin, IsTerminal, Read, stdout, Write}, sync::atomic::{AtomicBool, Ordering}, time::{Duration, Instant}};
+
+
+trait GetValue<T> {
+    fn get_value(&self) -> T;
+}
+
+trait SetValue<T, U> {
+    fn set_value(&mut self, value: T) -> U;
+}
+
+trait DisplayRegister: std::fmt::Debug {
+    fn display(&self){
+        println!("{:?}", self);   
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// General Purpose Registers for user interfacing(usage) when writing Instructions
+pub enum Register{
+    AX, BX, CX, DX,
+    EAX, EBX, ECX, EDX,
+}
+
+impl Register {
+    /// Every general purpose register, in register file order
+    fn all() -> [Register; 8] {
+        [
+            Register::AX, Register::BX, Register::CX, Register::DX,
+            Register::EAX, Register::EBX, Register::ECX, Register::EDX,
+        ]
+    }
+
+    /// Stable numeric code of the register, to be used by every serialized form of a register.
+    ///
+    /// | Code | Register | Code | Register |
+    /// |------|----------|------|----------|
+    /// | 0    | AX       | 4    | EAX      |
+    /// | 1    | BX       | 5    | EBX      |
+    /// | 2    | CX       | 6    | ECX      |
+    /// | 3    | DX       | 7    | EDX      |
+    ///
+    /// Codes are never reused or renumbered, new registers get the next free code.
+    pub fn code(&self) -> u8 {
+        match self {
+            Register::AX => 0, Register::BX => 1, Register::CX => 2, Register::DX => 3,
+            Register::EAX => 4, Register::EBX => 5, Register::ECX => 6, Register::EDX => 7,
+        }
+    }
+
+    /// The register with a code returned by `Register::code`
+    pub fn from_code(code: u8) -> Option<Register> {
+        Register::all().into_iter().find(|register| register.code() == code)
+    }
+
+    /// Index of the register in the general purpose register file, which is laid out in code order
+    fn index(&self) -> usize {
+        self.code() as usize
+    }
+
+    /// Width of the data held by the register
+    fn width(&self) -> DataWidth {
+        match self {
+            Register::AX | Register::BX | Register::CX | Register::DX => DataWidth::Word,
+            Register::EAX | Register::EBX | Register::ECX | Register::EDX => DataWidth::Dword,
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug)]
+/// Registers type used to store different register types of the CPU
+struct Registers{
+    GP: [GPRegister; 8],
+    SP: [SPRegister; 3],
+}
+
+impl DisplayRegister for Registers {
+    fn display(&self) {
+        println!("General Purpose Registers:");
+        self.GP.iter().for_each(|reg| {
+            println!("{:?}", reg);
+        });
+
+        println!("Special Purpose Registers:");
+        self.SP.iter().for_each(|reg| {
+            println!("{:?}", reg);
+        });
+    }
+}
+
+impl Registers {
+    fn get_register(&mut self, register: Register) -> &mut GPRegister {
+        &mut self.GP[register.index()]
+    }
+}
+
+#[derive(Clone)]
+///General Purpose Registers
+enum GPRegister {
+    AX(u8, u8), BX(u8, u8), CX(u8, u8),
+    DX(u8, u8), EAX(u8, u8, u8, u8),
+    EBX(u8, u8, u8, u8), ECX(u8, u8, u8, u8),
+    EDX(u8, u8, u8, u8),
+}
+
+impl Debug for GPRegister {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GPRegister::AX(a, b) => write!(f, "AX:\n   AL  AH\n   {:02X}  {:02X}\n", a, b),
+            GPRegister::BX(a, b) => write!(f, "BX:\n   BL  BH\n   {:02X}  {:02X}\n", a, b),
+            GPRegister::CX(a, b) => write!(f, "CX:\n   CL  CH\n   {:02X}  {:02X}\n", a, b),
+            GPRegister::DX(a, b) => write!(f, "DX:\n   DL  DH\n   {:02X}  {:02X}\n", a, b),
+            GPRegister::EAX(a, b, c, d) => write!(f, "EAX:\n    AL  AH  EAL  EAH\n     {:02X}  {:02X}  {:02X}   {:02X}\n", a, b, c, d),
+            GPRegister::EBX(a, b, c, d) => write!(f, "EBX:\n    BL  BH  EBL  EBH\n     {:02X}  {:02X}  {:02X}   {:02X}\n", a, b, c, d),
+            GPRegister::ECX(a, b, c, d) => write!(f, "ECX:\n    CL  CH  ECL  ECH\n     {:02X}  {:02X}  {:02X}   {:02X}\n", a, b, c, d),
+            GPRegister::EDX(a, b, c, d) => write!(f, "EDX:\n    DL  DH  EDL  EDH\n     {:02X}  {:02X}  {:02X}   {:02X}\n", a, b, c, d),
+        }
+    }
+}
+
+impl GetValue<u32> for GPRegister {
+    fn get_value(&self) -> u32 {
+        match self {
+            GPRegister::AX(a, b) | GPRegister::BX(a, b) | GPRegister::CX(a, b) |
+            GPRegister::DX(a, b) => u16::from_le_bytes([*a, *b]) as u32,
+            GPRegister::EAX(a, b, c, d) | GPRegister::EBX(a, b, c, d) | GPRegister::ECX(a, b, c, d) |
+            GPRegister::EDX(a, b, c, d) => u32::from_le_bytes([*a, *b, *c, *d]),
+        }
+    }
+}
+
+impl SetValue<Data, ()> for GPRegister {
+    fn set_value(&mut self, value: Data) {
+        match self {
+            GPRegister::AX(_, ah) => {
+                match value {
+                    Data::Byte(value) => *self = GPRegister::AX(value, *ah),
+                    Data::Word(value) => {
+                        let data = value.to_le_bytes();
+                        *self = GPRegister::AX(data[0], data[1]);
+                    }
+                    _ => {
+                        panic!("Data type mismatch. Expected Word or Byte, found Dword");
+                    }
+                }
+            },
+
+            GPRegister::BX(_, bh) => {
+                match value {
+                    Data::Byte(value) => *self = GPRegister::BX(value, *bh),
+                    Data::Word(value) => {
+                        let data = value.to_le_bytes();
+                        *self = GPRegister::BX(data[0], data[1]);
+                    }
+                    _ => {
+                        panic!("Data type mismatch. Expected Word or Byte, found Dword");
+                    }
+                }
+            },
+
+            GPRegister::CX(_, ch) => {
+                match value {
+                    Data::Byte(value) => *self = GPRegister::CX(value, *ch),
+                    Data::Word(value) => {
+                        let data = value.to_le_bytes();
+                        *self = GPRegister::CX(data[0], data[1]);
+                    }
+                    _ => {
+                        panic!("Data type mismatch. Expected Word or Byte, found Dword");
+                    }
+                }
+            },
+
+            GPRegister::DX(_, dh) => {
+                match value {
+                    Data::Byte(value) => *self = GPRegister::DX(value, *dh),
+                    Data::Word(value) => {
+                        let data = value.to_le_bytes();
+                        *self = GPRegister::DX(data[0], data[1]);
+                    }
+                    _ => {
+                        panic!("Data type mismatch. Expected Word or Byte, found Dword");
+                    }
+                }
+            },
+
+            GPRegister::EAX(_, ah, eal, eah) => {
+                match value {
+                    Data::Byte(a) => {
+                        *self = GPRegister::EAX(a, *ah, *eal, *eah);
+                    }
+                    Data::Word(a) => {
+                        let ah = (a >> 8) as u8;
+                        let al = (a & 0x00FF) as u8;
+                        *self = GPRegister::EAX(al, ah, *eal, *eah);
+                    }
+                    Data::Dword(a) => {
+                        let eah = (a >> 24) as u8;
+                        let eal = (a >> 16) as u8;
+                        let ah = (a >> 8) as u8;
+                        let al = (a & 0x00FF) as u8;
+                        *self = GPRegister::EAX(al, ah, eal, eah);
+                    }
+                }
+            },
+
+            GPRegister::EBX(_, bh, ebl, ebh) => {
+                match value {
+                    Data::Byte(a) => {
+                        *self = GPRegister::EBX(a, *bh, *ebl, *ebh);
+                    }
+                    Data::Word(a) => {
+                        let bh = (a >> 8) as u8;
+                        let bl = (a & 0x00FF) as u8;
+                        *self = GPRegister::EBX(bl, bh, *ebl, *ebh);
+                    }
+                    Data::Dword(a) => {
+                        let ebh = (a >> 24) as u8;
+                        let ebl = (a >> 16) as u8;
+                        let bh = (a >> 8) as u8;
+                        let bl = (a & 0x00FF) as u8;
+                        *self = GPRegister::EBX(bl, bh, ebl, ebh);
+                    }
+                }
+            },
+
+            GPRegister::ECX(_, ch, ecl, ech) => {
+                match value {
+                    Data::Byte(a) => {
+                        *self = GPRegister::ECX(a, *ch, *ecl, *ech);
+                    }
+                    Data::Word(a) => {
+                        let ch = (a >> 8) as u8;
+                        let cl = (a & 0x00FF) as u8;
+                        *self = GPRegister::ECX(cl, ch, *ecl, *ech);
+                    }
+                    Data::Dword(a) => {
+                        let ech = (a >> 24) as u8;
+                        let ecl = (a >> 16) as u8;
+                        let ch = (a >> 8) as u8;
+                        let cl = (a & 0x00FF) as u8;
+                        *self = GPRegister::ECX(cl, ch, ecl, ech);
+                    }
+                }
+            },
+
+            GPRegister::EDX(_, dh, edl, edh) => {
+                match value {
+                    Data::Byte(a) => {
+                        *self = GPRegister::EDX(a, *dh, *edl, *edh);
+                    }
+                    Data::Word(a) => {
+                        let dh = (a >> 8) as u8;
+                        let dl = (a & 0x00FF) as u8;
+                        *self = GPRegister::EDX(dl, dh, *edl, *edh);
+                    }
+                    Data::Dword(a) => {
+                        let edh = (a >> 24) as u8;
+                        let edl = (a >> 16) as u8;
+                        let dh = (a >> 8) as u8;
+                        let dl = (a & 0x00FF) as u8;
+                        *self = GPRegister::EDX(dl, dh, edl, edh);
+                    }
+                }
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+///Special Purpose Registers
+enum SPRegister {
+    SP(u8, u8),
+    BP(u8, u8),
+    IP(u8, u8),
+}
+
+impl GetValue<u32>for SPRegister {
+    fn get_value(&self) -> u32 {
+        match self {
+            SPRegister::SP(a, b) | SPRegister::BP(a, b) |
+            SPRegister::IP(a, b) => u16::from_le_bytes([*a, *b]) as u32,
+        }
+    }
+}
+
+impl SetValue<Data, ()> for SPRegister {
+    fn set_value(&mut self, value: Data) {
+        match self {
+            SPRegister::SP(_, b) => {
+                match value {
+                    Data::Byte(a) => {
+                        *self = SPRegister::SP(a, *b);
+                    }
+                    Data::Word(a) => {
+                        let b = (a >> 8) as u8;
+                        let a = (a & 0x00FF) as u8;
+                        *self = SPRegister::SP(a, b);
+                    }
+                    Data::Dword(a) => {
+                        let b = (a >> 8) as u8;
+                        let a = (a & 0x00FF) as u8;
+                        *self = SPRegister::SP(a, b);
+                    }
+                }
+            },
+
+            SPRegister::BP(_, b) => {
+                match value {
+                    Data::Byte(a) => {
+                        *self = SPRegister::BP(a, *b);
+                    }
+                    Data::Word(a) => {
+                        let b = (a >> 8) as u8;
+                        let a = (a & 0x00FF) as u8;
+                        *self = SPRegister::BP(a, b);
+                    }
+                    Data::Dword(a) => {
+                        let b = (a >> 8) as u8;
+                        let a = (a & 0x00FF) as u8;
+                        *self = SPRegister::BP(a, b);
+                    }
+                }
+            },
+
+            SPRegister::IP(_, b) => {
+                match value {
+                    Data::Byte(a) => {
+                        *self = SPRegister::IP(a, *b);
+                    }
+                    Data::Word(a) => {
+                        let b = (a >> 8) as u8;
+                        let a = (a & 0x00FF) as u8;
+                        *self = SPRegister::IP(a, b);
+                    }
+                    Data::Dword(a) => {
+                        let b = (a >> 8) as u8;
+                        let a = (a & 0x00FF) as u8;
+                        *self = SPRegister::IP(a, b);
+                    }
+                }
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A flag of the flags register with its value, 0 or 1
+pub enum FLAGS {
+    PF(u8), AF(u8), ZF(u8),
+    SF(u8), TF(u8), IF(u8),
+    DF(u8), OF(u8), CF(u8),
+}
+
+impl FLAGS {
+    /// Stable numeric code of the flag, its bit in the flags word and its index in the CPU's flags array.
+    ///
+    /// | Code | Flag | Code | Flag | Code | Flag |
+    /// |------|------|------|------|------|------|
+    /// | 0    | PF   | 3    | SF   | 6    | DF   |
+    /// | 1    | AF   | 4    | TF   | 7    | OF   |
+    /// | 2    | ZF   | 5    | IF   | 8    | CF   |
+    ///
+    /// Codes are never reused or renumbered, so they can be used by every serialized form of a flag.
+    pub fn code(&self) -> u8 {
+        match self {
+            FLAGS::PF(_) => 0, FLAGS::AF(_) => 1, FLAGS::ZF(_) => 2,
+            FLAGS::SF(_) => 3, FLAGS::TF(_) => 4, FLAGS::IF(_) => 5,
+            FLAGS::DF(_) => 6, FLAGS::OF(_) => 7, FLAGS::CF(_) => 8,
+        }
+    }
+
+    /// The cleared flag with a code returned by `FLAGS::code`
+    pub fn from_code(code: u8) -> Option<FLAGS> {
+        let flags = [FLAGS::PF(0), FLAGS::AF(0), FLAGS::ZF(0), FLAGS::SF(0), FLAGS::TF(0), FLAGS::IF(0), FLAGS::DF(0), FLAGS::OF(0), FLAGS::CF(0)];
+        flags.into_iter().find(|flag| flag.code() == code)
+    }
+}
+
+impl GetValue<u8> for FLAGS {
+    fn get_value(&self) -> u8 {
+        match self {
+            FLAGS::AF(a) | FLAGS::ZF(a) | FLAGS::SF(a) | FLAGS::TF(a) |
+            FLAGS::IF(a) | FLAGS::DF(a) | FLAGS::OF(a) | FLAGS::CF(a) |
+            FLAGS::PF(a) => *a,
+        }
+    }
+}
+
+impl SetValue<u8, ()> for FLAGS {
+    fn set_value(&mut self, value: u8) {
+        match self {
+            FLAGS::ZF(a) | FLAGS::SF(a) | FLAGS::TF(a) | FLAGS::IF(a) |
+            FLAGS::DF(a) | FLAGS::OF(a) | FLAGS::CF(a) | FLAGS::PF(a) |
+            FLAGS::AF(a) => *a = value,
+        }
+    }
+}
+
+/// Masks selecting flags in the CPU's flags array, one bit per index.
+/// Used by instructions to declare which flags they update.
+pub const FLAG_PF: u16 = 1 << 0;
+pub const FLAG_AF: u16 = 1 << 1;
+pub const FLAG_ZF: u16 = 1 << 2;
+pub const FLAG_SF: u16 = 1 << 3;
+pub const FLAG_OF: u16 = 1 << 7;
+pub const FLAG_CF: u16 = 1 << 8;
+/// Flags updated by arithmetic and logical instructions
+pub const STATUS_FLAGS: u16 = FLAG_PF | FLAG_AF | FLAG_ZF | FLAG_SF | FLAG_OF | FLAG_CF;
+/// Names of the flags, in the order of the CPU's flags array
+pub const FLAG_NAMES: [&str; 9] = ["PF", "AF", "ZF", "SF", "TF", "IF", "DF", "OF", "CF"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Status flags computed by the ALU for a single operation
+pub struct FlagsDelta {
+    pub carry: bool,
+    pub overflow: bool,
+    pub zero: bool,
+    pub sign: bool,
+    pub parity: bool,
+    pub aux: bool,
+}
+
+impl FlagsDelta {
+    /// Computes the zero, sign and parity flags from a result of the given width
+    fn from_result(result: u32, width: DataWidth, carry: bool, overflow: bool, aux: bool) -> FlagsDelta {
+        FlagsDelta {
+            carry,
+            overflow,
+            zero: result == 0,
+            sign: result & width.sign_bit() != 0,
+            // Parity only considers the low byte of the result
+            parity: (result as u8).count_ones().is_multiple_of(2),
+            aux,
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+///! Instruction Set. This is the set of instructions that the CPU can execute.
+/// NB: Not all instructions are implemented.
+pub enum IS {
+    Mov, Add, Sub,
+    Mul, Div, And,
+    Or, Xor, Not,
+    Jmp, Daa, Das,
+    Aam, Aad, Syscall,
+    Cmp, Jz, Jnz,
+    Jc, Jnc, Call,
+    Ret,
+    /// Pseudo-instruction stopping execution if a register doesn't hold the expected value
+    AssertReg,
+    /// Pseudo-instruction stopping execution if a flag isn't in the expected state
+    AssertFlag,
+}
+
+impl IS {
+    /// Every instruction of the instruction set, in declaration order
+    pub fn all() -> &'static [IS] {
+        &[
+            IS::Mov, IS::Add, IS::Sub,
+            IS::Mul, IS::Div, IS::And,
+            IS::Or, IS::Xor, IS::Not,
+            IS::Jmp, IS::Daa, IS::Das,
+            IS::Aam, IS::Aad, IS::Syscall,
+            IS::Cmp, IS::Jz, IS::Jnz,
+            IS::Jc, IS::Jnc, IS::Call,
+            IS::Ret,
+            IS::AssertReg, IS::AssertFlag,
+        ]
+    }
+
+    /// Whether the instruction transfers control to its operand, always or depending on the flags
+    fn is_jump(&self) -> bool {
+        matches!(self, IS::Jmp | IS::Jz | IS::Jnz | IS::Jc | IS::Jnc | IS::Call)
+    }
+
+    /// Description of the instruction.
+    ///
+    /// This is the single table describing the instruction set: operand verification, flag updates
+    /// and the cost model are all derived from it, so adding an instruction only needs a new entry here.
+    pub fn info(&self) -> InstructionInfo {
+        use OperandKind::*;
+        const DEST: &[OperandKind] = &[Register, Memory];
+        const SRC: &[OperandKind] = &[Register, Memory, Immediate];
+        let unsupported = InstructionInfo { supported: false, operand_count: 0..=0, operand_kinds: &[], usage: "", flags: 0, cost: 1 };
+
+        match self {
+            IS::Mov => InstructionInfo {
+                supported: true, operand_count: 2..=2, operand_kinds: &[DEST, SRC],
+                usage: "mov reg/mem, reg/mem/imm", flags: 0, cost: 1,
+            },
+            IS::Add => InstructionInfo {
+                supported: true, operand_count: 2..=2, operand_kinds: &[DEST, SRC],
+                usage: "add reg/mem, reg/mem/imm", flags: STATUS_FLAGS, cost: 1,
+            },
+            IS::Sub => InstructionInfo {
+                supported: true, operand_count: 2..=2, operand_kinds: &[DEST, SRC],
+                usage: "sub reg/mem, reg/mem/imm", flags: STATUS_FLAGS, cost: 1,
+            },
+            IS::Mul => InstructionInfo { flags: STATUS_FLAGS, cost: 3, ..unsupported },
+            // Unsigned, the flags are undefined afterwards and left untouched
+            IS::Div => InstructionInfo {
+                supported: true, operand_count: 1..=1, operand_kinds: &[&[Register, Memory]],
+                usage: "div reg/mem", flags: 0, cost: 20,
+            },
+            // AF is undefined after logical operations and left untouched
+            IS::And => InstructionInfo {
+                supported: true, operand_count: 2..=2, operand_kinds: &[DEST, SRC],
+                usage: "and reg/mem, reg/mem/imm", flags: STATUS_FLAGS & !FLAG_AF, cost: 1,
+            },
+            IS::Or => InstructionInfo {
+                supported: true, operand_count: 2..=2, operand_kinds: &[DEST, SRC],
+                usage: "or reg/mem, reg/mem/imm", flags: STATUS_FLAGS & !FLAG_AF, cost: 1,
+            },
+            IS::Xor => InstructionInfo { flags: STATUS_FLAGS, ..unsupported },
+            // NOT leaves every flag untouched
+            IS::Not => InstructionInfo {
+                supported: true, operand_count: 1..=1, operand_kinds: &[DEST],
+                usage: "not reg/mem", flags: 0, cost: 1,
+            },
+            IS::Jmp => InstructionInfo {
+                supported: true, operand_count: 1..=1, operand_kinds: &[&[Immediate, Relative]],
+                usage: "jmp index/rel", flags: 0, cost: 2,
+            },
+            // OF is undefined after decimal adjustment and left untouched
+            IS::Daa => InstructionInfo {
+                supported: true, operand_count: 0..=0, operand_kinds: &[],
+                usage: "daa", flags: STATUS_FLAGS & !FLAG_OF, cost: 2,
+            },
+            IS::Das => InstructionInfo {
+                supported: true, operand_count: 0..=0, operand_kinds: &[],
+                usage: "das", flags: STATUS_FLAGS & !FLAG_OF, cost: 2,
+            },
+            // The base is optional and defaults to 10
+            IS::Aam => InstructionInfo {
+                supported: true, operand_count: 0..=1, operand_kinds: &[&[Immediate]],
+                usage: "aam [imm]", flags: FLAG_ZF | FLAG_SF | FLAG_PF, cost: 17,
+            },
+            IS::Aad => InstructionInfo {
+                supported: true, operand_count: 0..=1, operand_kinds: &[&[Immediate]],
+                usage: "aad [imm]", flags: FLAG_ZF | FLAG_SF | FLAG_PF, cost: 10,
+            },
+            IS::Syscall => InstructionInfo {
+                supported: true, operand_count: 0..=0, operand_kinds: &[],
+                usage: "syscall", flags: 0, cost: 10,
+            },
+            // CMP sets the flags of a SUB without writing the result back
+            IS::Cmp => InstructionInfo {
+                supported: true, operand_count: 2..=2, operand_kinds: &[DEST, SRC],
+                usage: "cmp reg/mem, reg/mem/imm", flags: STATUS_FLAGS, cost: 1,
+            },
+            // Conditional jumps on ZF (equal) and CF (unsigned below)
+            IS::Jz => InstructionInfo {
+                supported: true, operand_count: 1..=1, operand_kinds: &[&[Immediate, Relative]],
+                usage: "jz index/rel", flags: 0, cost: 2,
+            },
+            IS::Jnz => InstructionInfo {
+                supported: true, operand_count: 1..=1, operand_kinds: &[&[Immediate, Relative]],
+                usage: "jnz index/rel", flags: 0, cost: 2,
+            },
+            IS::Jc => InstructionInfo {
+                supported: true, operand_count: 1..=1, operand_kinds: &[&[Immediate, Relative]],
+                usage: "jc index/rel", flags: 0, cost: 2,
+            },
+            IS::Jnc => InstructionInfo {
+                supported: true, operand_count: 1..=1, operand_kinds: &[&[Immediate, Relative]],
+                usage: "jnc index/rel", flags: 0, cost: 2,
+            },
+            // Return addresses are kept by the CPU, and on the stack in main memory if one is reserved
+            IS::Call => InstructionInfo {
+                supported: true, operand_count: 1..=1, operand_kinds: &[&[Immediate, Relative]],
+                usage: "call index/rel", flags: 0, cost: 3,
+            },
+            IS::Ret => InstructionInfo {
+                supported: true, operand_count: 0..=0, operand_kinds: &[],
+                usage: "ret", flags: 0, cost: 3,
+            },
+            // Debugging aids rather than machine instructions, so they take no cycles
+            IS::AssertReg => InstructionInfo {
+                supported: true, operand_count: 2..=2, operand_kinds: &[&[Register], &[Immediate]],
+                usage: "assertreg reg, imm", flags: 0, cost: 0,
+            },
+            IS::AssertFlag => InstructionInfo {
+                supported: true, operand_count: 2..=2, operand_kinds: &[&[Immediate], &[Immediate]],
+                usage: "assertflag flag, 0/1", flags: 0, cost: 0,
+            },
+        }
+    }
+
+    /// Flags the instruction updates, as a mask over the CPU's flags array.
+    ///
+    /// Every other flag is guaranteed to keep its value, e.g. MOV and JMP never touch the flags.
+    pub fn flag_mask(&self) -> u16 {
+        self.info().flags
+    }
+
+    /// Cycle cost of the instruction in the built-in cost table, excluding memory operand costs.
+    pub fn default_cost(&self) -> u64 {
+        self.info().cost
+    }
+
+    /// Assembly name of the instruction, e.g. `"mov"`
+    pub fn mnemonic(&self) -> String {
+        format!("{:?}", self).to_lowercase()
+    }
+}
+
+impl std::str::FromStr for IS {
+    type Err = String;
+
+    /// Parses a mnemonic in any case, suggesting the closest instruction for a misspelled one
+    fn from_str(mnemonic: &str) -> Result<IS, String> {
+        let mnemonic = mnemonic.to_lowercase();
+        if let Some(opcode) = IS::all().iter().find(|opcode| opcode.mnemonic() == mnemonic) {
+            return Ok(opcode.clone());
+        }
+        let mnemonics: Vec<String> = IS::all().iter().map(IS::mnemonic).collect();
+        match closest_match(&mnemonic, mnemonics.iter().map(String::as_str)) {
+            Some(suggestion) => Err(format!("Unknown instruction: {}, did you mean {}?", mnemonic, suggestion)),
+            None => Err(format!("Unknown instruction: {}", mnemonic)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Kind of an operand, used to describe the operands an instruction accepts
+pub enum OperandKind {
+    Register,
+    Memory,
+    /// Immediate values, including label offsets
+    Immediate,
+    /// Relative jump offsets
+    Relative,
+}
+
+#[derive(Debug, Clone)]
+/// Description of an instruction of the instruction set
+pub struct InstructionInfo {
+    /// Whether the CPU can execute the instruction
+    pub supported: bool,
+    pub operand_count: std::ops::RangeInclusive<u8>,
+    /// Operand kinds accepted at each operand position
+    pub operand_kinds: &'static [&'static [OperandKind]],
+    /// Assembly syntax of the instruction, used in error messages
+    pub usage: &'static str,
+    /// Flags updated by the instruction, a mask of the `FLAG_*` constants
+    pub flags: u16,
+    /// Cycle cost in the built-in cost table
+    pub cost: u64,
+}
+
+/// Extra cycles charged for every memory operand of an instruction
+const MEMORY_OPERAND_COST: u64 = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Data type used to store data in memory
+/// NB: Only Byte, Word and Dword are supported
+pub enum Data {
+    Byte(u8),
+    Word(u16),
+    Dword(u32),
+}
+
+impl Data {
+    /// Creates data of the given width from a value, truncating it to the width
+    fn from_u32(width: DataWidth, value: u32) -> Data {
+        match width {
+            DataWidth::Byte => Data::Byte(value as u8),
+            DataWidth::Word => Data::Word(value as u16),
+            DataWidth::Dword => Data::Dword(value),
+        }
+    }
+
+    /// Width of the data
+    fn width(&self) -> DataWidth {
+        match self {
+            Data::Byte(_) => DataWidth::Byte,
+            Data::Word(_) => DataWidth::Word,
+            Data::Dword(_) => DataWidth::Dword,
+        }
+    }
+
+    /// Little endian bytes of the data, as many as its width
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.get_value().to_le_bytes()[..self.width().bytes()].to_vec()
+    }
+
+    /// Creates data of the given width from its little endian bytes.
+    ///
+    /// Fails unless there are exactly as many bytes as the width covers.
+    pub fn from_bytes(width: DataWidth, bytes: &[u8]) -> Result<Data, String> {
+        if bytes.len() != width.bytes() {
+            return Err(format!("Expected {} bytes for {:?} data, got {}", width.bytes(), width, bytes.len()));
+        }
+        let mut value = [0u8; 4];
+        value[..bytes.len()].copy_from_slice(bytes);
+        Ok(Data::from_u32(width, u32::from_le_bytes(value)))
+    }
+
+    /// Adds a value to the data, keeping its width.
+    ///
+    /// Fails instead of wrapping around if the sum doesn't fit the width.
+    pub fn checked_add(&self, value: u32) -> Result<Data, String> {
+        match self.get_value().checked_add(value) {
+            Some(sum) if sum & !self.width().mask() == 0 => Ok(Data::from_u32(self.width(), sum)),
+            _ => Err(format!("{:?} + {} overflows {:?} data", self, value, self.width())),
+        }
+    }
+
+    /// Splits a dword into its low and high words
+    pub fn split_dword(value: u32) -> (Data, Data) {
+        (Data::Word(value as u16), Data::Word((value >> 16) as u16))
+    }
+}
+
+impl GetValue<u32> for Data {
+    fn get_value(&self) -> u32 {
+        match self {
+            Data::Byte(a) => *a as u32,
+            Data::Word(a) => *a as u32,
+            Data::Dword(a) => *a,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Width of a memory access, used by width-qualified memory operands (`byte ptr`, `word ptr`, `dword ptr`)
+pub enum DataWidth {
+    Byte,
+    Word,
+    Dword,
+}
+
+impl DataWidth {
+    /// Number of bytes covered by the width
+    fn bytes(&self) -> usize {
+        match self {
+            DataWidth::Byte => 1,
+            DataWidth::Word => 2,
+            DataWidth::Dword => 4,
+        }
+    }
+
+    /// Mask covering all bits of the width
+    fn mask(&self) -> u32 {
+        match self {
+            DataWidth::Byte => 0xFF,
+            DataWidth::Word => 0xFFFF,
+            DataWidth::Dword => 0xFFFF_FFFF,
+        }
+    }
+
+    /// Mask of the most significant bit of the width
+    fn sign_bit(&self) -> u32 {
+        1 << (self.bytes() * 8 - 1)
+    }
+
+    /// Registers holding the dividend of a DIV by a divisor of this width, low half first,
+    /// which receive its quotient and remainder: AX for a byte, DX:AX for a word and EDX:EAX for a dword
+    fn dividend_registers(&self) -> (Register, Option<Register>) {
+        match self {
+            DataWidth::Byte => (Register::AX, None),
+            DataWidth::Word => (Register::AX, Some(Register::DX)),
+            DataWidth::Dword => (Register::EAX, Some(Register::EDX)),
+        }
+    }
+
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Index of an interned label in the symbol table
+pub struct SymbolId(usize);
+
+#[derive(Debug)]
+/// Symbol table mapping data section labels to their location in main memory.
+///
+/// Labels are interned to a `SymbolId` once, so operands resolved at load time look their symbol up
+/// by index instead of hashing the label on every execution. Ids stay valid when symbols are redefined.
+pub struct SymbolTable {
+    ids: HashMap<String, SymbolId>,
+    names: Vec<String>,
+    symbols: Vec<Option<Symbol>>,
+}
+
+impl Default for SymbolTable {
+    fn default() -> SymbolTable {
+        SymbolTable::new()
+    }
+}
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        SymbolTable {
+            ids: HashMap::new(),
+            names: Vec::new(),
+            symbols: Vec::new(),
+        }
+    }
+
+    /// Returns the id of a label, allocating one if the label hasn't been seen yet.
+    ///
+    /// The label doesn't need to be defined, looking up the id of an undefined label returns `None`.
+    pub fn intern(&mut self, label: &str) -> SymbolId {
+        if let Some(id) = self.ids.get(label) {
+            return *id;
+        }
+        let id = SymbolId(self.names.len());
+        self.ids.insert(label.to_string(), id);
+        self.names.push(label.to_string());
+        self.symbols.push(None);
+        id
+    }
+
+    /// Defines the location of a label
+    fn define(&mut self, label: &str, symbol: Symbol) -> SymbolId {
+        let id = self.intern(label);
+        self.symbols[id.0] = Some(symbol);
+        id
+    }
+
+    /// Undefines every label while keeping their ids
+    fn clear(&mut self) {
+        self.symbols.iter_mut().for_each(|symbol| *symbol = None);
+    }
+
+    /// The location of the label with the id, if it is defined
+    pub fn get(&self, id: SymbolId) -> Option<&Symbol> {
+        self.symbols.get(id.0)?.as_ref()
+    }
+
+    /// The location of the label, if it is defined
+    pub fn lookup(&self, label: &str) -> Option<&Symbol> {
+        self.get(*self.ids.get(label)?)
+    }
+
+    /// The label interned with the id
+    pub fn name(&self, id: SymbolId) -> Option<&str> {
+        self.names.get(id.0).map(String::as_str)
+    }
+
+    /// Defined labels and their symbols, sorted by label
+    fn defined(&self) -> Vec<(&String, &Symbol)> {
+        let mut defined: Vec<(&String, &Symbol)> = self.names.iter()
+            .zip(self.symbols.iter())
+            .filter_map(|(label, symbol)| Some((label, symbol.as_ref()?)))
+            .collect();
+        defined.sort_by(|a, b| a.0.cmp(b.0));
+        defined
+    }
+
+    /// The defined label closest to a misspelled one, if any is close enough to be a likely typo
+    fn suggest(&self, label: &str) -> Option<String> {
+        closest_match(label, self.defined().into_iter().map(|(name, _)| name.as_str())).map(str::to_string)
+    }
+}
+
+/// Finds the candidate closest to `name` by edit distance.
+///
+/// Candidates more than a third of the name's length away (at least 1, at most 3 edits) aren't
+/// considered a match, so unrelated names don't get suggested.
+fn closest_match<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).clamp(1, 3);
+    candidates
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance between two strings, counted in characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + (a_char != *b_char) as usize;
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Location of a data section label in main memory
+pub struct Symbol {
+    /// Byte offset of the label's data in the RAM
+    pub offset: u32,
+    /// Width of the label's data
+    pub width: DataWidth,
+}
+
+// TODO: Implement SetValue for Data to cater for the u32 without data loss
+impl SetValue<u32, Data> for Data {
+    fn set_value(&mut self, value: u32)-> Self {
+        match self {
+            Data::Byte(data) => {
+                *data = value as u8;
+                Data::Byte(*data)
+            },
+            Data::Word(data) => {
+                *data = value as u16;
+                Data::Word(*data)
+            },
+            Data::Dword(data) => {
+                *data = value;
+                Data::Dword(*data)
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MemOp {
+    ///Memory address. This is interpreted as ```[label]``` 
+    /// # Example:
+    /// 
+    /// this
+    /// ```ignore
+    /// Instruction::new(
+    ///     IS::Mov, vec![Operand::Register(Register::AX), Operand::Immediate((Data::Word(0x00FF))]
+    /// );
+    /// Instruction
+    ///     IS::Mov, vec![Operand::Memory(MemOp::Address("label".to_owned())), Operand::Register(Register::AX)]
+    /// );
+    /// ```
+    /// is interpreted as
+    /// ```text
+    /// mov ax, 0x00FF
+    /// mov [label], ax
+    /// ```
+    Address(String),
+
+    ///Value. This is interpreted as `data/raw value`
+    /// # Example:
+    /// 
+    /// this 
+    /// ```ignore
+    /// Instruction::new(
+    ///     IS::Mov, vec![Operand::Register(Register::AX), Operand::Immediate((Data::Word(0x00FF))]
+    /// );
+    /// Instruction::new(
+    ///     IS::Mov, vec![Operand::Memory(MemOp::Value("address".to_owned())), Operand::Register(Register::AX)]
+    /// );
+    /// ```
+    /// is interpreted as
+    /// ```text
+    /// mov ax, 0x00FF
+    /// mov address, ax
+    /// ```
+    /// This would lead to an error as the first operand is expected to be an address/memory location
+    Label(String),
+
+    ///Width-qualified memory address with a byte displacement. This is interpreted as ```byte/word/dword ptr [label+n]```
+    /// # Example:
+    ///
+    /// this
+    /// ```ignore
+    /// Instruction::new(
+    ///     IS::Mov, vec![Operand::Memory(MemOp::Ptr(DataWidth::Byte, "output".to_owned(), 3)), Operand::Register(Register::DX)]
+    /// );
+    /// ```
+    /// is interpreted as
+    /// ```text
+    /// mov byte ptr [output+3], dl
+    /// ```
+    /// Only the bytes covered by the width are accessed, the rest of the variable is left untouched.
+    Ptr(DataWidth, String, i32),
+
+    ///Width-qualified memory address held by a register, with a byte displacement. This is interpreted as ```byte/word/dword ptr [reg+n]```
+    /// # Example:
+    ///
+    /// this
+    /// ```ignore
+    /// Instruction::new(
+    ///     IS::Mov, vec![Operand::Register(Register::AX), Operand::Memory(MemOp::Indirect(DataWidth::Word, Register::BX, 2))]
+    /// );
+    /// ```
+    /// is interpreted as
+    /// ```text
+    /// mov ax, word ptr [bx+2]
+    /// ```
+    /// The register holds an offset in main memory, e.g. loaded with `offset label`, which lets programs walk arrays.
+    Indirect(DataWidth, Register, i32),
+
+    ///Memory address ```[label]``` whose label has been interned in the symbol table.
+    ///
+    ///The loader replaces `Address` operands with this form so executing them looks the symbol up by index.
+    ///The label is only kept for display.
+    Symbol(SymbolId, String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Operand type used to store operands for instructions
+/// 
+/// Usage example:
+/// ```
+/// # use cpu::{Data, Instruction, IS, Operand, Register};
+/// Instruction::new(
+///     IS::Mov, vec![Operand::Register(Register::AX), Operand::Immediate(Data::Word(0x00FF))]
+/// );
+/// ```
+/// This example moves the value 0x00FF to the AX register
+/// 
+/// It simulates the instruction `MOV AX, 0x00FF` in x86 assembly
+pub enum Operand {
+    Register(Register),
+    Memory(MemOp),
+    Immediate(Data),
+    /// Signed instruction offset used by relative jumps.
+    ///
+    /// The offset is added to IP after it has been incremented past the jump,
+    /// so `JmpRel(0)` falls through to the next instruction and `JmpRel(-1)` jumps to itself.
+    JmpRel(i32),
+    /// Offset of a data section label in main memory, used as an immediate value.
+    ///
+    /// This is interpreted as `offset label`, unlike `[label]` it doesn't read the label's data.
+    LabelOffset(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Instruction {
+    opcode: IS,
+    operands: Vec<Operand>,
+    operand_count: u8,
+}
+
+impl Operand {
+    fn kind(&self) -> OperandKind {
+        match self {
+            Operand::Register(_) => OperandKind::Register,
+            // A label operand evaluates to the label's offset
+            Operand::Memory(MemOp::Label(_)) => OperandKind::Immediate,
+            Operand::Memory(_) => OperandKind::Memory,
+            Operand::Immediate(_) | Operand::LabelOffset(_) => OperandKind::Immediate,
+            Operand::JmpRel(_) => OperandKind::Relative,
+        }
+    }
+}
+
+impl std::fmt::Display for Operand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operand::Register(register) => write!(f, "{}", format!("{:?}", register).to_lowercase()),
+            Operand::Immediate(data) => write!(f, "{}", data.get_value()),
+            Operand::Memory(MemOp::Address(label)) | Operand::Memory(MemOp::Symbol(_, label)) => write!(f, "[{}]", label),
+            Operand::Memory(MemOp::Label(label)) => write!(f, "{}", label),
+            Operand::Memory(MemOp::Ptr(width, label, displacement)) => {
+                let width = format!("{:?}", width).to_lowercase();
+                match displacement {
+                    0 => write!(f, "{} ptr [{}]", width, label),
+                    _ => write!(f, "{} ptr [{}{:+}]", width, label, displacement),
+                }
+            },
+            Operand::Memory(MemOp::Indirect(width, register, displacement)) => {
+                let width = format!("{:?}", width).to_lowercase();
+                let register = format!("{:?}", register).to_lowercase();
+                match displacement {
+                    0 => write!(f, "{} ptr [{}]", width, register),
+                    _ => write!(f, "{} ptr [{}{:+}]", width, register, displacement),
+                }
+            },
+            Operand::JmpRel(offset) => write!(f, "{:+}", offset),
+            Operand::LabelOffset(label) => write!(f, "offset {}", label),
+        }
+    }
+}
+
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let operands: Vec<String> = self.operands.iter().map(|operand| operand.to_string()).collect();
+        let mnemonic = self.opcode.mnemonic();
+        match operands.is_empty() {
+            true => write!(f, "{}", mnemonic),
+            false => write!(f, "{} {}", mnemonic, operands.join(", ")),
+        }
+    }
+}
+
+impl Instruction {
+    pub fn new(opcode: IS, operands: Vec<Operand>) -> Instruction {
+        Instruction {
+            operand_count: operands.len() as u8,
+            opcode,
+            operands,
+        }
+    }
+
+    /// Registers whose value the instruction reads, including the registers holding memory operand addresses
+    fn register_reads(&self) -> Vec<Register> {
+        let mut reads = match self.opcode {
+            IS::Mov => match self.operands.get(1) {
+                Some(Operand::Register(register)) => vec![register.clone()],
+                _ => vec![],
+            },
+            IS::Add | IS::Sub | IS::And | IS::Or | IS::Not | IS::Cmp => self.operands.iter()
+                .filter_map(|operand| match operand {
+                    Operand::Register(register) => Some(register.clone()),
+                    _ => None,
+                })
+                .collect(),
+            IS::Daa | IS::Das | IS::Aam | IS::Aad => vec![Register::AX],
+            IS::Div => {
+                let mut reads = self.dividend_registers();
+                if let Some(Operand::Register(register)) = self.operands.first() {
+                    reads.push(register.clone());
+                }
+                reads
+            },
+            // Every argument register, as which ones are used depends on the syscall number
+            IS::Syscall => vec![syscall_abi::NUMBER, syscall_abi::ARG1, syscall_abi::ARG2, syscall_abi::ARG3],
+            _ => vec![],
+        };
+        for operand in &self.operands {
+            if let Operand::Memory(MemOp::Indirect(_, register, _)) = operand && !reads.contains(register) {
+                reads.push(register.clone());
+            }
+        }
+        reads
+    }
+
+    /// Registers the instruction writes to
+    fn register_writes(&self) -> Vec<Register> {
+        match self.opcode {
+            IS::Mov | IS::Add | IS::Sub | IS::And | IS::Or | IS::Not => match self.operands.first() {
+                Some(Operand::Register(register)) => vec![register.clone()],
+                _ => vec![],
+            },
+            IS::Daa | IS::Das | IS::Aam | IS::Aad => vec![Register::AX],
+            IS::Div => self.dividend_registers(),
+            _ => vec![],
+        }
+    }
+
+    /// Registers a DIV divides and leaves its results in.
+    ///
+    /// The width of a label is only known once the data section is laid out, so a label divisor is taken to be a word.
+    fn dividend_registers(&self) -> Vec<Register> {
+        let width = match self.operands.first() {
+            Some(Operand::Register(register)) => register.width(),
+            Some(Operand::Memory(MemOp::Ptr(width, _, _) | MemOp::Indirect(width, _, _))) => *width,
+            _ => DataWidth::Word,
+        };
+        let (low, high) = width.dividend_registers();
+        [Some(low), high].into_iter().flatten().collect()
+    }
+
+    /// Labels referenced by the instruction's memory operands
+    fn labels(&self) -> Vec<&String> {
+        self.operands.iter()
+            .filter_map(|operand| match operand {
+                Operand::Memory(MemOp::Address(label)) | Operand::Memory(MemOp::Label(label)) |
+                Operand::Memory(MemOp::Ptr(_, label, _)) | Operand::Memory(MemOp::Symbol(_, label)) |
+                Operand::LabelOffset(label) => Some(label),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The instruction's opcode, e.g. to find instructions with `CPU::find_instructions`
+    pub fn opcode(&self) -> &IS {
+        &self.opcode
+    }
+
+    /// First operand, the destination of two-operand instructions
+    pub fn dest(&self) -> Result<&Operand, CpuError> {
+        self.operand(0)
+    }
+
+    /// Second operand, the source of two-operand instructions
+    pub fn src(&self) -> Result<&Operand, CpuError> {
+        self.operand(1)
+    }
+
+    /// Operand of a single-operand instruction, failing if there isn't exactly one
+    pub fn only(&self) -> Result<&Operand, CpuError> {
+        match self.operands.as_slice() {
+            [operand] => Ok(operand),
+            _ => Err(self.invalid_operands()),
+        }
+    }
+
+    fn operand(&self, index: usize) -> Result<&Operand, CpuError> {
+        self.operands.get(index).ok_or_else(|| self.invalid_operands())
+    }
+
+    fn invalid_operands(&self) -> CpuError {
+        CpuError::InvalidOperands(self.opcode.clone(), self.opcode.info().usage)
+    }
+
+    /// Checks the operand count and operand kinds against the instruction set table
+    fn verify_operands(&self) -> bool {
+        let info = self.opcode.info();
+        info.operand_count.contains(&self.operand_count) &&
+            self.operands.iter().zip(info.operand_kinds).all(|(operand, kinds)| kinds.contains(&operand.kind()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Operation the ALU performs
+pub enum ALUMode {
+    Add, Sub, Mul,
+    And, Or, Xor,
+    Not, Off
+}
+
+#[derive(Debug)]
+/// Arithmetic Logic Unit.
+/// 
+/// This is the unit that performs arithmetic and logical operations.
+/// 
+/// Operations are performed at the width of the destination operand.
+struct ALU{
+    buffer: (u32, u32),
+    mode: ALUMode,
+    /// The last operation executed
+    trace: Option<AluTrace>,
+}
+
+impl ALU {
+    fn new() -> ALU {
+        ALU {
+            buffer: (0, 0),
+            mode: ALUMode::Off,
+            trace: None,
+        }
+    }
+
+    /// Sets the mode of the ALU's operation state
+    fn set_mode(&mut self, mode: ALUMode) {
+        self.mode = mode;
+    }
+
+    fn operand_fetch(&mut self, destination: u32, source: u32) {
+        self.buffer = (destination, source);
+    }
+
+    /// Loads the operand of a single-operand operation, clearing the source half of the buffer
+    fn operand_fetch_single(&mut self, destination: u32) {
+        self.buffer = (destination, 0);
+    }
+
+    /// Executes the operation based on the mode of the ALU at the given width.
+    /// Returns the result truncated to the width and the status flags of the operation.
+    fn execute(&mut self, width: DataWidth) -> (u32, FlagsDelta) {
+        let (result, flags) = match self.mode {
+            ALUMode::Add => self.add(width),
+            ALUMode::Sub => self.sub(width),
+            ALUMode::And => self.and(width),
+            ALUMode::Or => self.or(width),
+            ALUMode::Not => self.not(width),
+            ALUMode::Off => panic!("ALU is off"),
+            _ => panic!("Unsupported mode not implemented"),
+        };
+        self.trace = Some(AluTrace {
+            mode: self.mode,
+            width,
+            operands: (self.buffer.0 & width.mask(), self.buffer.1 & width.mask()),
+            result,
+            flags,
+        });
+        (result, flags)
+    }
+
+    /// Adds the values in the buffer of the ALU.
+    /// CF is set on unsigned overflow of the width and OF on signed overflow.
+    fn add(&mut self, width: DataWidth) -> (u32, FlagsDelta) {
+        let (a, b) = (self.buffer.0 & width.mask(), self.buffer.1 & width.mask());
+        let sum = a as u64 + b as u64;
+        let result = sum as u32 & width.mask();
+
+        let carry = sum > width.mask() as u64;
+        let overflow = (a ^ result) & (b ^ result) & width.sign_bit() != 0;
+        let aux = (a ^ b ^ result) & 0x10 != 0;
+        (result, FlagsDelta::from_result(result, width, carry, overflow, aux))
+    } 
+
+    /// Subtracts the source from the destination value in the buffer of the ALU.
+    /// CF is set when a borrow is needed and OF on signed overflow.
+    fn sub(&mut self, width: DataWidth) -> (u32, FlagsDelta) {
+        let (a, b) = (self.buffer.0 & width.mask(), self.buffer.1 & width.mask());
+        let result = a.wrapping_sub(b) & width.mask();
+
+        let carry = b > a;
+        let overflow = (a ^ b) & (a ^ result) & width.sign_bit() != 0;
+        let aux = (a ^ b ^ result) & 0x10 != 0;
+        (result, FlagsDelta::from_result(result, width, carry, overflow, aux))
+    }
+
+    /// Bitwise ANDs the values in the buffer of the ALU.
+    /// CF and OF are always cleared.
+    fn and(&mut self, width: DataWidth) -> (u32, FlagsDelta) {
+        let result = self.buffer.0 & self.buffer.1 & width.mask();
+        (result, FlagsDelta::from_result(result, width, false, false, false))
+    }
+
+    /// Bitwise ORs the values in the buffer of the ALU.
+    /// CF and OF are always cleared.
+    fn or(&mut self, width: DataWidth) -> (u32, FlagsDelta) {
+        let result = (self.buffer.0 | self.buffer.1) & width.mask();
+        (result, FlagsDelta::from_result(result, width, false, false, false))
+    }
+
+    /// Complements the destination value in the buffer of the ALU.
+    /// The returned flags describe the result, but NOT doesn't apply any of them.
+    fn not(&mut self, width: DataWidth) -> (u32, FlagsDelta) {
+        let result = !self.buffer.0 & width.mask();
+        (result, FlagsDelta::from_result(result, width, false, false, false))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// An operation executed by the ALU, kept to show how its result and flags came out
+pub struct AluTrace {
+    pub mode: ALUMode,
+    pub width: DataWidth,
+    /// Operands masked to the width, the source is 0 for single-operand operations
+    pub operands: (u32, u32),
+    pub result: u32,
+    pub flags: FlagsDelta,
+}
+
+impl std::fmt::Display for AluTrace {
+    /// Renders the operation as a worked example in binary, followed by the flags it produced
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let binary = |value| format_value(value, self.width, Base::Binary);
+        writeln!(f, "{:?} ({:?})", self.mode, self.width)?;
+        match self.mode {
+            ALUMode::Not => writeln!(f, "  ~ {}", binary(self.operands.0))?,
+            mode => {
+                let operator = match mode {
+                    ALUMode::Add => "+",
+                    ALUMode::Sub => "-",
+                    ALUMode::And => "&",
+                    ALUMode::Or => "|",
+                    _ => "?",
+                };
+                writeln!(f, "    {}", binary(self.operands.0))?;
+                writeln!(f, "  {} {}", operator, binary(self.operands.1))?;
+            },
+        }
+        writeln!(f, "  = {}", binary(self.result))?;
+        write!(f, "{}", self.flags)
+    }
+}
+
+impl std::fmt::Display for FlagsDelta {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CF={} OF={} ZF={} SF={} PF={} AF={}",
+            self.carry as u8, self.overflow as u8, self.zero as u8, self.sign as u8, self.parity as u8, self.aux as u8)
+    }
+}
+
+/// Random Access Memory.
+/// 
+/// This is the unit that stores data of the running program.
+struct RAM{
+    data: Vec<u8>,
+    capacity: usize,
+}
+
+impl Debug for RAM {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RAM")
+            .field("data", &format_args!("{}", format_bytes(&self.data, BYTES_SHOWN)))
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+impl RAM {
+    fn new() -> RAM {
+        RAM {
+            data: Vec::with_capacity(1024),
+            capacity: 1024,
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Memory Unit.
+/// 
+/// This is the unit that stores data and code sections.
+/// 
+/// It is used to simulate the memory of the CPU.
+struct MemoryUnit {
+    ///Data section of the memory unit. 
+    /// 
+    ///It stores the program's variable declarations in the form of key(label)-value(initial value) pairs.
+    ///These are never modified after construction.
+    /// 
+    data_section: HashMap<String, Data>,
+    ///Symbol table of the memory unit.
+    ///
+    ///It maps each label of the data section to the location of its data in main memory.
+    symbols: SymbolTable,
+    ///Offset in main memory at which the data section is loaded.
+    ///
+    ///Memory below it is reserved, e.g. for an interrupt vector table or memory mapped devices.
+    data_base: u32,
+    ///Code section of the memory unit.
+    /// 
+    ///It stores the program instructions.
+    code_section: Vec<Instruction>,
+    ///Memory Access bus.
+    data_bus: RAM
+}
+
+/// Implementation of the Memory Unit that manages data used by the CPU and running program.
+/// 
+/// It contains the data and code sections of the program and does the read and write operations to main memory.
+// TODO: Implement the MemoryUnit's read and write methods to cater for different data sizes
+impl MemoryUnit {
+    fn new(data_section: HashMap<String, Data>, code_section: Vec<Instruction>) -> MemoryUnit {
+        MemoryUnit {
+            data_section,
+            symbols: SymbolTable::new(),
+            data_base: 0,
+            code_section,
+            data_bus: RAM::new(),
+        }
+    }
+
+    fn get_mem_capacity(&self) -> usize {
+        self.data_bus.capacity
+    }
+
+    fn get_data_len(&self) -> usize {
+        self.data_bus.data.len()
+    }
+
+    /// Returns the declared initial value of a label in the data section.
+    fn declaration(&self, label: &str) -> Option<&Data> {
+        self.data_section.get(label)
+    }
+
+    /// Returns the location of a label's data in main memory.
+    fn symbol(&self, label: &str) -> Option<&Symbol> {
+        self.symbols.lookup(label)
+    }
+
+    /// Range of main memory covered by `length` bytes at `offset`, or an error if it reaches past the end
+    fn checked_range(&self, offset: usize, length: usize) -> Result<std::ops::Range<usize>, CpuError> {
+        match offset.checked_add(length) {
+            Some(end) if end <= self.get_data_len() => Ok(offset..end),
+            _ => Err(CpuError::MemoryOutOfBounds(offset, length)),
+        }
+    }
+
+    /// Reads `length` bytes starting at `offset` in main memory.
+    fn read_bytes(&self, offset: usize, length: usize) -> Result<Vec<u8>, CpuError> {
+        let range = self.checked_range(offset, length)?;
+        Ok(self.data_bus.data[range].to_vec())
+    }
+
+    /// Writes the bytes in place starting at `offset` in main memory.
+    ///
+    /// Unlike `write_data`, the bytes around the written range are left untouched.
+    fn write_bytes(&mut self, offset: usize, data: &[u8]) -> Result<(), CpuError> {
+        let range = self.checked_range(offset, data.len())?;
+        self.data_bus.data[range].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Reads the data of a symbol from the main memory.
+    fn read_data(&self, symbol: &Symbol) -> Result<Vec<u8>, CpuError> {
+        self.read_bytes(symbol.offset as usize, symbol.width.bytes())
+    }
+
+    /// Reads the data of a symbol from the main memory as a zero extended value.
+    fn read_value(&self, symbol: &Symbol) -> Result<u32, CpuError> {
+        let bytes = self.read_data(symbol)?;
+        match Data::from_bytes(symbol.width, &bytes) {
+            Ok(data) => Ok(data.get_value()),
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Writes data to the main memory at the location of a symbol.
+    /// 
+    /// Data is the bytes to be written to memory. If it is shorter than the symbol's width, the remaining bytes are filled with 0.
+    /// 
+    /// This operation assumes constant data size and doesn't reallocate memory for data exceeding the symbol's width.
+    fn write_data(&mut self, symbol: &Symbol, data: Vec<u8>) -> Result<(), CpuError> {
+        let length = symbol.width.bytes();
+        if data.len() > length {
+            panic!("Data of {} bytes doesn't fit in a {:?} symbol", data.len(), symbol.width);
+        }
+        let mut bytes = vec![0; length];
+        bytes[..data.len()].copy_from_slice(&data);
+        self.write_bytes(symbol.offset as usize, &bytes)
+    }
+
+    /// Writes a value to the main memory at the location of a symbol, truncated to the symbol's width.
+    fn write_value(&mut self, symbol: &Symbol, value: u32) -> Result<(), CpuError> {
+        self.write_data(symbol, Data::from_u32(symbol.width, value).to_bytes())
+    }
+
+    /// Exports a range of main memory in the Intel HEX format.
+    ///
+    /// The bytes are written as data records of up to 16 bytes each, followed by the end of file record.
+    /// Each record holds the low 16 bits of its address, preceded by an extended linear address record
+    /// whenever the upper 16 bits change.
+    fn export_ihex(&self, range: std::ops::Range<usize>) -> Result<String, IhexError> {
+        let bytes = self.read_bytes(range.start, range.len()).map_err(|error| IhexError::Memory(None, error))?;
+        let mut output = String::new();
+        let mut upper = 0;
+
+        let mut address = range.start;
+        for chunk in bytes.chunks(16) {
+            // A record can't cross into the next 64 KiB, as its address only has 16 bits
+            let (chunk, rest) = chunk.split_at(chunk.len().min(0x10000 - (address & 0xFFFF)));
+            for chunk in [chunk, rest] {
+                if chunk.is_empty() {
+                    continue;
+                }
+                if address >> 16 != upper {
+                    upper = address >> 16;
+                    let mut record = vec![0x02, 0x00, 0x00, 0x04];
+                    record.extend((upper as u16).to_be_bytes());
+                    output.push_str(&ihex_record(&record));
+                }
+                let mut record = vec![chunk.len() as u8];
+                record.extend((address as u16).to_be_bytes());
+                record.push(0x00);
+                record.extend(chunk);
+                output.push_str(&ihex_record(&record));
+                address += chunk.len();
+            }
+        }
+        output.push_str(&ihex_record(&[0x00, 0x00, 0x00, 0x01]));
+        Ok(output)
+    }
+
+    /// Imports an Intel HEX image into main memory.
+    ///
+    /// Data, end of file, extended segment address and extended linear address records are supported.
+    /// Memory is extended up to its capacity if a record lies beyond the data already in memory.
+    /// Errors report the line number of the offending record.
+    fn import_ihex(&mut self, image: &str) -> Result<(), IhexError> {
+        // Added to the address of data records by the extended address records
+        let mut base = 0;
+        for (i, line) in image.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let hex = match line.strip_prefix(':') {
+                Some(hex) => hex,
+                None => return Err(IhexError::Malformed(line_number, "record doesn't start with ':'".to_string())),
+            };
+            if !hex.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+                return Err(IhexError::Malformed(line_number, format!("invalid hex digits in {:?}", line)));
+            }
+            if hex.len() % 2 != 0 || hex.len() < 10 {
+                return Err(IhexError::Malformed(line_number, format!("record {:?} is too short or has an odd number of digits", line)));
+            }
+            // Every digit is ASCII, so the record can be sliced two bytes at a time
+            let record: Vec<u8> = (0..hex.len()).step_by(2)
+                .map(|j| u8::from_str_radix(&hex[j..j + 2], 16).unwrap())
+                .collect();
+
+            let length = record[0] as usize;
+            if record.len() != length + 5 {
+                return Err(IhexError::Malformed(line_number, format!("record length {} doesn't match its {} data bytes", length, record.len() - 5)));
+            }
+            let checksum = record[record.len() - 1];
+            let expected = ihex_checksum(&record[..record.len() - 1]);
+            if checksum != expected {
+                return Err(IhexError::Checksum(line_number, expected, checksum));
+            }
+
+            let address = base + u16::from_be_bytes([record[1], record[2]]) as usize;
+            let data = &record[4..4 + length];
+            match record[3] {
+                0x00 => {
+                    let end = address + data.len();
+                    if end > self.get_data_len() {
+                        let extension = end - self.get_data_len();
+                        if extension > self.get_mem_capacity() {
+                            return Err(IhexError::Memory(Some(line_number), CpuError::MemoryOutOfBounds(address, data.len())));
+                        }
+                        self.data_bus.data.resize(end, 0);
+                        self.data_bus.capacity -= extension;
+                    }
+                    self.write_bytes(address, data).map_err(|error| IhexError::Memory(Some(line_number), error))?;
+                }
+                0x01 => return Ok(()),
+                0x02 | 0x04 if length != 2 => {
+                    return Err(IhexError::Malformed(line_number, format!("extended address record with {} data bytes instead of 2", length)));
+                },
+                0x02 => base = (u16::from_be_bytes([data[0], data[1]]) as usize) << 4,
+                0x04 => base = (u16::from_be_bytes([data[0], data[1]]) as usize) << 16,
+                record_type => return Err(IhexError::UnsupportedRecord(line_number, record_type)),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+/// Errors exporting main memory to an Intel HEX image or importing one, with the 1-based line of the offending record
+pub enum IhexError {
+    /// The record on the line isn't a well-formed record, with what is wrong with it
+    Malformed(usize, String),
+    /// The checksum of the record on the line doesn't match its bytes, with the expected and the found checksums
+    Checksum(usize, u8, u8),
+    /// The record on the line has a type other than data, end of file or extended address
+    UnsupportedRecord(usize, u8),
+    /// The exported range, or the data of the record on the line, lies outside main memory
+    Memory(Option<usize>, CpuError),
+}
+
+impl std::fmt::Display for IhexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IhexError::Malformed(line, reason) => write!(f, "Line {}: {}", line, reason),
+            IhexError::Checksum(line, expected, found) => write!(f, "Line {}: checksum mismatch, expected {:02X} found {:02X}", line, expected, found),
+            IhexError::UnsupportedRecord(line, record_type) => write!(f, "Line {}: unsupported record type {:02X}", line, record_type),
+            IhexError::Memory(Some(line), error) => write!(f, "Line {}: {:?}", line, error),
+            IhexError::Memory(None, error) => write!(f, "{:?}", error),
+        }
+    }
+}
+
+/// Magic bytes at the start of a raw memory image written by `CPU::save_memory`.
+const MEMORY_IMAGE_MAGIC: &[u8] = b"CPUMEM";
+
+/// Two's complement checksum of an Intel HEX record's bytes.
+fn ihex_checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)).wrapping_neg()
+}
+
+/// Formats the bytes of an Intel HEX record (length, address, type and data) as a line with its checksum.
+fn ihex_record(bytes: &[u8]) -> String {
+    let mut line = String::from(":");
+    for byte in bytes {
+        line.push_str(&format!("{:02X}", byte));
+    }
+    line.push_str(&format!("{:02X}\n", ihex_checksum(bytes)));
+    line
+}
+
+/// Builder for a CPU.
+///
+/// Collects the program and the machine options before the data section is laid out in memory.
+///
+/// Usage example:
+/// ```
+/// # use std::collections::HashMap;
+/// # use cpu::{CpuBuilder, Data, Instruction, IS, MemOp, Operand, Register};
+/// let data_section = HashMap::from([("num".to_string(), Data::Word(10))]);
+/// let code_section = vec![
+///     Instruction::new(IS::Mov, vec![Operand::Register(Register::AX), Operand::Memory(MemOp::Address("num".to_string()))]),
+/// ];
+/// let cpu = CpuBuilder::new(data_section, code_section)
+///     .data_base(0x100)
+///     .build()?;
+/// # Ok::<(), cpu::BuildError>(())
+/// ```
+pub struct CpuBuilder {
+    data_section: HashMap<String, Data>,
+    code_section: Vec<Instruction>,
+    data_base: u32,
+    guest_assertions: bool,
+    verbosity: Verbosity,
+    entry: usize,
+    timeout_check_interval: u64,
+    environment: Vec<(String, String)>,
+    color: bool,
+    output: TraceOutput,
+    dump_limit: usize,
+    clock: Box<dyn Clock>,
+    guest_output: GuestOutput,
+    guest_input: GuestInput,
+    instruction_limit: Option<u64>,
+    observers: Observers,
+    pipeline_view: bool,
+    stack: usize,
+    stack_canaries: bool,
+    shadow_stack_policy: ShadowStackPolicy,
+    code_labels: HashMap<String, usize>,
+    semantics: Semantics,
+}
+
+impl CpuBuilder {
+    pub fn new(data_section: HashMap<String, Data>, code_section: Vec<Instruction>) -> CpuBuilder {
+        CpuBuilder {
+            data_section,
+            code_section,
+            data_base: 0,
+            guest_assertions: cfg!(debug_assertions),
+            verbosity: Verbosity::Full,
+            entry: 0,
+            timeout_check_interval: 1024,
+            environment: Vec::new(),
+            color: false,
+            output: TraceOutput(RefCell::new(Box::new(stdout()))),
+            dump_limit: BYTES_SHOWN,
+            clock: Box::new(SystemClock(Instant::now())),
+            guest_output: GuestOutput(Box::new(stdout())),
+            guest_input: GuestInput(Box::new(stdin())),
+            observers: Observers(Vec::new()),
+            instruction_limit: None,
+            stack: 0,
+            stack_canaries: false,
+            shadow_stack_policy: ShadowStackPolicy::Warn,
+            code_labels: HashMap::new(),
+            pipeline_view: false,
+            semantics: Semantics::Strict,
+        }
+    }
+
+    /// Sets the offset in main memory at which the data section is loaded.
+    ///
+    /// All symbol offsets are relative to the start of memory, so labels resolve to `base + offset in data section`.
+    pub fn data_base(mut self, base: u32) -> CpuBuilder {
+        self.data_base = base;
+        self
+    }
+
+    /// Sets whether AssertReg/AssertFlag pseudo-instructions are checked.
+    ///
+    /// They are checked in debug builds by default and execute as no-ops when disabled.
+    pub fn guest_assertions(mut self, enabled: bool) -> CpuBuilder {
+        self.guest_assertions = enabled;
+        self
+    }
+
+    /// Sets how much the CPU reports about the run, `Full` by default
+    pub fn verbosity(mut self, verbosity: Verbosity) -> CpuBuilder {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Sets the index of the instruction the program starts at, 0 by default.
+    ///
+    /// This is the `_start` entry point of the program, letting helper routines be placed before it.
+    pub fn entry(mut self, index: usize) -> CpuBuilder {
+        self.entry = index;
+        self
+    }
+
+    /// Sets every how many instructions `CPU::run_with_timeout` checks the elapsed time, 1024 by default
+    pub fn timeout_check_interval(mut self, instructions: u64) -> CpuBuilder {
+        self.timeout_check_interval = instructions.max(1);
+        self
+    }
+
+    /// Sets whether the CPU's trace output is colored, off by default
+    pub fn color(mut self, enabled: bool) -> CpuBuilder {
+        self.color = enabled;
+        self
+    }
+
+    /// Sets where the CPU writes its trace output, stdout by default
+    pub fn output(mut self, output: impl Write + 'static) -> CpuBuilder {
+        self.output = TraceOutput(RefCell::new(Box::new(output)));
+        self
+    }
+
+    /// Sets the clock timing runs, the system clock by default
+    pub fn clock(mut self, clock: impl Clock + 'static) -> CpuBuilder {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Sets where the write syscalls of the program write to, stdout by default
+    pub fn guest_output(mut self, output: impl Write + 'static) -> CpuBuilder {
+        self.guest_output = GuestOutput(Box::new(output));
+        self
+    }
+
+    /// Installs an observer receiving the events of every executed instruction, see `Observer`
+    pub fn observer(mut self, observer: impl Observer + 'static) -> CpuBuilder {
+        self.observers.0.push(Box::new(observer));
+        self
+    }
+
+    /// Sets whether observers also receive the `Fetched`, `Decoded` and `Executed` stage events of each
+    /// instruction, to show it going through the fetch, decode and execute stages. Off by default.
+    pub fn pipeline_view(mut self, enabled: bool) -> CpuBuilder {
+        self.pipeline_view = enabled;
+        self
+    }
+
+    /// Sets where the read syscall of the program reads from, stdin by default
+    pub fn guest_input(mut self, input: impl Read + 'static) -> CpuBuilder {
+        self.guest_input = GuestInput(Box::new(input));
+        self
+    }
+
+    /// Sets the number of instructions after which a run is stopped with an `InstructionLimit` error, unlimited by default.
+    ///
+    /// This keeps a program that never exits from hanging its host.
+    pub fn instruction_limit(mut self, instructions: u64) -> CpuBuilder {
+        self.instruction_limit = Some(instructions);
+        self
+    }
+
+    /// Sets how many bytes of memory `CPU::hexdump` and fault reports show before eliding the rest, 64 by default
+    pub fn dump_limit(mut self, bytes: usize) -> CpuBuilder {
+        self.dump_limit = bytes;
+        self
+    }
+
+    /// Sets configuration passed to the program as `KEY=VALUE` environment variables.
+    ///
+    /// The variables are stored NUL-terminated one after the other in a block following the data section,
+    /// which the program locates with the environment syscall (7).
+    pub fn env(mut self, variables: &[(&str, &str)]) -> CpuBuilder {
+        self.environment = variables.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect();
+        self
+    }
+
+    /// Reserves a stack of `bytes` in main memory after the environment block, none by default.
+    ///
+    /// SP starts at the end of the stack, which grows down. With a stack, CALL pushes its return address as
+    /// a word and RET pops the address it returns to, so a program can inspect or overwrite it. Without one
+    /// the return addresses are only kept by the CPU.
+    pub fn stack(mut self, bytes: u16) -> CpuBuilder {
+        self.stack = bytes as usize;
+        self
+    }
+
+    /// Makes CALL push `STACK_CANARY` below the return address and RET check it is intact, off by default.
+    ///
+    /// This catches a callee overwriting its caller's frame through a bad offset, which RET reports with
+    /// `CpuError::CorruptedReturnAddress`. Canaries live on the stack, so one must be reserved with `stack`.
+    pub fn stack_canaries(mut self, enabled: bool) -> CpuBuilder {
+        self.stack_canaries = enabled;
+        self
+    }
+
+    /// Sets how a RET returning elsewhere than its call pushed is reported, `Warn` by default.
+    ///
+    /// The CPU records the return address of every call on a shadow stack, which RET checks the address
+    /// it pops from the stack against. This only applies with a stack reserved.
+    pub fn shadow_stack_policy(mut self, policy: ShadowStackPolicy) -> CpuBuilder {
+        self.shadow_stack_policy = policy;
+        self
+    }
+
+    /// Sets whether DIV and AAM follow x86 or the gentler teaching semantics, `Strict` by default
+    pub fn semantics(mut self, semantics: Semantics) -> CpuBuilder {
+        self.semantics = semantics;
+        self
+    }
+
+    /// Names the instruction at the index, e.g. the first instruction of a subroutine.
+    ///
+    /// Code labels let the host refer to places in the program by name, see `CPU::run_until_label`.
+    pub fn code_label(mut self, name: &str, index: usize) -> CpuBuilder {
+        self.code_labels.insert(name.to_string(), index);
+        self
+    }
+
+    /// Lays out the data section and returns the CPU, ready to run the program.
+    ///
+    /// Fails if the data section doesn't fit in main memory, in which case no CPU is returned.
+    pub fn build(self) -> Result<CPU, BuildError> {
+        if self.stack_canaries && self.stack == 0 {
+            return Err(BuildError::CanariesWithoutStack);
+        }
+        if let Some((name, index)) = self.code_labels.iter().find(|(_, index)| **index >= self.code_section.len()) {
+            return Err(BuildError::CodeLabelOutOfRange(name.clone(), *index));
+        }
+        let mut cpu = CPU {
+            alu: ALU::new(),
+            registers: Registers {
+                GP: [GPRegister::AX(0, 0), GPRegister::BX(0, 0), GPRegister::CX(0, 0), GPRegister::DX(0, 0), GPRegister::EAX(0, 0, 0, 0), GPRegister::EBX(0, 0, 0, 0), GPRegister::ECX(0, 0, 0, 0), GPRegister::EDX(0, 0, 0, 0)],
+                SP: [SPRegister::SP(0, 0), SPRegister::BP(0, 0), SPRegister::IP(0, 0)],
+            },
+            flags: [FLAGS::PF(0), FLAGS::AF(0), FLAGS::ZF(0), FLAGS::SF(0), FLAGS::TF(0), FLAGS::IF(0), FLAGS::DF(0), FLAGS::OF(0), FLAGS::CF(0)],
+            memory_unit: MemoryUnit {
+                data_section: self.data_section,
+                symbols: SymbolTable::new(),
+                data_base: self.data_base,
+                code_section: self.code_section,
+                data_bus: RAM::new(),
+            },
+            costs: HashMap::new(),
+            instruction_count: 0,
+            cycle_count: 0,
+            memory_reads: 0,
+            memory_writes: 0,
+            exit_code: None,
+            uninitialized: None,
+            warnings: Vec::new(),
+            writers: None,
+            last_memory_access: None,
+            access_log: None,
+            profile: None,
+            guest_assertions: self.guest_assertions,
+            verbosity: self.verbosity,
+            entry: self.entry,
+            last_fetched: None,
+            trace_tail: VecDeque::with_capacity(TRACE_TAIL),
+            timeout_check_interval: self.timeout_check_interval,
+            environment: self.environment.iter()
+                .flat_map(|(key, value)| format!("{}={}\0", key, value).into_bytes())
+                .collect(),
+            environment_offset: 0,
+            color: self.color,
+            output: self.output,
+            dump_limit: self.dump_limit,
+            clock: self.clock,
+            guest_output: self.guest_output,
+            guest_input: self.guest_input,
+            observers: self.observers,
+            executing: None,
+            pipeline_view: self.pipeline_view,
+            semantics: self.semantics,
+            stage_reads: Vec::new(),
+            stage_writes: Vec::new(),
+            instruction_limit: self.instruction_limit,
+            call_stack: Vec::new(),
+            guest_logs: Vec::new(),
+            yielded: false,
+            aborted: None,
+            returned_to_host: false,
+            hooks: HashMap::new(),
+            breakpoints: BTreeSet::new(),
+            register_conditions: Vec::new(),
+            expression_conditions: Vec::new(),
+            watches: Vec::new(),
+            code_labels: self.code_labels,
+            stack: match self.stack {
+                0 => None,
+                size => Some(Stack { base: 0, size, canaries: self.stack_canaries, shadow_stack_policy: self.shadow_stack_policy }),
+            },
+        };
+        cpu.registers.SP[2].set_value(Data::Word(self.entry as u16));
+        cpu.store_label_data()?;
+        cpu.check_labels()?;
+        cpu.intern_operands();
+        Ok(cpu)
+    }
+}
+
+#[derive(Debug)]
+/// Errors raised while building a CPU
+pub enum BuildError {
+    /// The data section and the memory reserved below the data base need more bytes than main memory has
+    OverCapacity(MemoryReport),
+    /// The instruction at the index references a label that isn't declared in the data section,
+    /// with the closest declared label if one looks like a typo of it
+    UndeclaredLabel(usize, String, Option<String>),
+    /// The core at the index of a `Machine` doesn't lay out its data section like the first core
+    CoreLayoutMismatch(usize),
+    /// Stack canaries were enabled without reserving a stack to hold them
+    CanariesWithoutStack,
+    /// The code label names an instruction index past the end of the code section
+    CodeLabelOutOfRange(String, usize),
+}
+
+impl std::fmt::Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::OverCapacity(report) => write!(f, "Not enough capacity in data bus!\n{}", report),
+            BuildError::UndeclaredLabel(index, label, suggestion) => {
+                write!(f, "Instruction {} references undeclared label {:?}", index, label)?;
+                match suggestion {
+                    Some(suggestion) => write!(f, ", did you mean {:?}?", suggestion),
+                    None => Ok(()),
+                }
+            },
+            BuildError::CoreLayoutMismatch(index) => write!(f, "Core {} doesn't share the data section layout of the first core", index),
+            BuildError::CanariesWithoutStack => write!(f, "Stack canaries need a stack to be reserved"),
+            BuildError::CodeLabelOutOfRange(name, index) => write!(f, "Code label {:?} names instruction {}, past the end of the code section", name, index),
+        }
+    }
+}
+
+/// Sets up a CPU to run one guest routine in isolation, for unit-testing the routines of a program.
+///
+/// The program is built as is and none of it runs before the routine: the registers and memory the
+/// routine expects are seeded directly, and the host's return address is pushed like `CPU::call_guest`
+/// does, so the routine's RET stops the CPU with `StopReason::ReturnedToHost`.
+///
+/// ```
+/// # use std::collections::HashMap;
+/// use cpu::{CpuBuilder, CpuTestBed, Data, Instruction, Operand, Register, StopReason, Verbosity, IS};
+///
+/// let code = vec![
+///     Instruction::new(IS::Mov, vec![Operand::Register(Register::AX), Operand::Immediate(Data::Word(1))]),
+///     // double: AX += AX
+///     Instruction::new(IS::Add, vec![Operand::Register(Register::AX), Operand::Register(Register::AX)]),
+///     Instruction::new(IS::Ret, vec![]),
+/// ];
+/// let program = CpuBuilder::new(HashMap::new(), code).verbosity(Verbosity::Quiet).code_label("double", 1);
+/// let mut cpu = CpuTestBed::new(program).with_register(Register::AX, 21).start_at("double")?;
+/// assert_eq!(cpu.run_debug().unwrap(), StopReason::ReturnedToHost);
+/// assert_eq!(cpu.register(Register::AX), 42);
+/// # Ok::<(), String>(())
+/// ```
+pub struct CpuTestBed {
+    program: CpuBuilder,
+    registers: Vec<(Register, u32)>,
+    memory: Vec<(String, Vec<u8>)>,
+}
+
+impl CpuTestBed {
+    /// Creates a bed for the program, seeding nothing yet
+    pub fn new(program: CpuBuilder) -> CpuTestBed {
+        CpuTestBed { program, registers: Vec::new(), memory: Vec::new() }
+    }
+
+    /// Sets a register before the routine starts, truncating the value to the register's width
+    pub fn with_register(mut self, register: Register, value: u32) -> CpuTestBed {
+        self.registers.push((register, value));
+        self
+    }
+
+    /// Writes the bytes to main memory starting at the data section label, before the routine starts.
+    ///
+    /// The bytes may run past the label into the labels laid out after it, e.g. to fill a buffer
+    /// declared as several labels.
+    pub fn with_memory(mut self, label: &str, bytes: &[u8]) -> CpuTestBed {
+        self.memory.push((label.to_string(), bytes.to_vec()));
+        self
+    }
+
+    /// Builds the program, seeds it and positions it at the first instruction of the routine at the code label.
+    ///
+    /// Fails if the program doesn't build, or a label is missing or its bytes run past the end of memory.
+    pub fn start_at(self, label: &str) -> Result<CPU, String> {
+        let mut cpu = self.program.build().map_err(|err| err.to_string())?;
+        for (register, value) in self.registers {
+            cpu.registers.get_register(register.clone()).set_value(Data::from_u32(register.width(), value));
+            cpu.mark_register_written(&register);
+        }
+        for (name, bytes) in self.memory {
+            let offset = match cpu.memory_unit.symbol(&name) {
+                Some(symbol) => symbol.offset as usize,
+                None => return Err(format!("{:?}", cpu.undeclared_label(&name))),
+            };
+            cpu.memory_unit.write_bytes(offset, &bytes).map_err(|err| format!("{:?}", err))?;
+            cpu.mark_memory_written(offset, bytes.len());
+        }
+        let Some(target) = cpu.code_label(label) else {
+            return Err(format!("Undeclared code label {:?}", label));
+        };
+        cpu.enter_from_host(target).map_err(|err| format!("{:?}", err))?;
+        Ok(cpu)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Non-fatal issues found by `CPU::validate` in a program that is likely to be wrong
+pub enum Warning {
+    /// A data section label that no instruction references
+    UnusedLabel(String),
+    /// A register read at the instruction index before any instruction writes it, relying on it starting as 0
+    ReadBeforeWrite(usize, Register),
+    /// The result of the ADD/SUB at the instruction index is overwritten before being read
+    UnusedResult(usize, Register),
+    /// Instructions starting at the index can never execute
+    UnreachableCode(usize),
+    /// The instruction at the index leaves its destination unchanged, e.g. `add ax, 0` or `mov ax, ax`
+    NoEffect(usize),
+    /// The instruction at the index read a register that was never written
+    UninitializedRegister(usize, Register),
+    /// The instruction at the index read a memory byte at the offset that was never written
+    UninitializedMemory(usize, usize),
+    /// The RET at the index returned to the first address, popped from the stack, instead of the second,
+    /// where the CPU recorded its call should return
+    ShadowStackMismatch(usize, usize, usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// How reads of uninitialized registers and memory are reported when tracking is enabled
+pub enum UninitializedPolicy {
+    /// Record a warning and carry on, each location is reported once
+    Warn,
+    /// Fail the instruction with `CpuError::UninitializedRead`
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// How a RET popping another return address than its call pushed is reported, when a stack is reserved.
+///
+/// Programs may replace a return address on purpose, e.g. to switch between coroutines, so a mismatch
+/// is a warning unless the program is known not to.
+pub enum ShadowStackPolicy {
+    /// Record a `Warning::ShadowStackMismatch` and return to the popped address
+    Warn,
+    /// Fail the RET with `CpuError::ShadowStackMismatch`
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Semantics of the instructions whose x86 behaviour is harsh on beginners, set with `CpuBuilder::semantics`.
+///
+/// Every such difference is decided here, so the instructions consult the policy rather than the mode.
+pub enum Semantics {
+    /// Behave like x86: a division by zero or a quotient too wide for its register faults with `CpuError::DivideError`
+    Strict,
+    /// Never fault on a division: dividing by zero gives a quotient of all ones and the dividend, truncated to
+    /// the remainder register, as remainder,
+    /// and a quotient too wide for its register is truncated to it
+    Simple,
+}
+
+impl Semantics {
+    /// Divides `dividend` by `divisor`, returning the quotient and the remainder to store in registers of `width`
+    fn divide(&self, dividend: u64, divisor: u32, width: DataWidth) -> Result<(u32, u32), CpuError> {
+        let mask = width.mask() as u64;
+        match (self, divisor) {
+            (Semantics::Strict, 0) => Err(CpuError::DivideError),
+            (Semantics::Simple, 0) => Ok((width.mask(), (dividend & mask) as u32)),
+            (_, divisor) => {
+                let (quotient, remainder) = (dividend / divisor as u64, dividend % divisor as u64);
+                // As on x86 a quotient too wide for its register faults like a division by zero
+                if *self == Semantics::Strict && quotient > mask {
+                    return Err(CpuError::DivideError);
+                }
+                Ok(((quotient & mask) as u32, remainder as u32))
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Shadow state marking which registers and memory bytes have been written
+struct UninitializedTracker {
+    policy: UninitializedPolicy,
+    registers: [bool; 8],
+    /// One entry per RAM byte; bytes past its end count as initialized
+    memory: Vec<bool>,
+}
+
+#[derive(Debug)]
+/// Index of the instruction that last wrote each register and memory byte.
+///
+/// `None` means the location hasn't been written since tracking started, or was last written by the host.
+struct WriterJournal {
+    registers: [Option<usize>; 8],
+    /// Grows on demand to cover the highest written offset
+    memory: Vec<Option<usize>>,
+}
+
+impl WriterJournal {
+    fn record_memory(&mut self, offset: usize, length: usize, writer: Option<usize>) {
+        if self.memory.len() < offset + length {
+            self.memory.resize(offset + length, None);
+        }
+        self.memory[offset..offset + length].iter_mut().for_each(|byte| *byte = writer);
+    }
+}
+
+#[derive(Debug, Default)]
+/// Instructions executed under each chain of active calls, recorded once `CPU::enable_profiling` is called
+struct Profile {
+    /// Entry points of the calls active, outermost first
+    frames: Vec<usize>,
+    counts: BTreeMap<Vec<usize>, u64>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug)]
+/// Errors raised while executing an instruction
+pub enum CpuError {
+    /// A memory operand refers to a label that isn't declared in the data section,
+    /// with the closest declared label if one looks like a typo of it
+    UndeclaredLabel(String, Option<String>),
+    /// A label's offset was used where a memory location was expected
+    NotAMemoryLocation(String),
+    /// A width-qualified memory operand (`[label+displacement]`) reaches outside main memory
+    AddressOutOfBounds(String, i32),
+    /// An access of the given length at the offset reaches past the end of main memory
+    MemoryOutOfBounds(usize, usize),
+    /// The operand can't be written to, e.g. an immediate value
+    InvalidDestination(Operand),
+    /// The operand can't be read as a value
+    InvalidOperand(Operand),
+    /// Both operands refer to main memory
+    MemoryToMemory,
+    /// Division by zero
+    DivideError,
+    /// The instruction's operands don't match its usage
+    InvalidOperands(IS, &'static str),
+    /// The instruction isn't implemented
+    UnsupportedInstruction(IS),
+    /// A jump target outside the addressable instruction range
+    JumpOutOfRange(i64),
+    /// The run took longer than its timeout, with the number of instructions it executed
+    Timeout(u64),
+    /// IP points past the end of the code section, with the index of the instruction that moved it there
+    /// (`None` when the program's entry point is out of range)
+    InvalidInstructionPointer(u32, Option<usize>),
+    /// A call would nest deeper than `CALL_STACK_LIMIT` calls
+    CallStackOverflow,
+    /// A RET was executed outside of any call
+    ReturnWithoutCall,
+    /// A RET found the stack canary of its frame overwritten, with the canary expected, the value found
+    /// in its place and the return address the CPU recorded for the call
+    CorruptedReturnAddress(u16, u16, usize),
+    /// A RET popped the first return address instead of the second, with the shadow stack policy set to `Error`
+    ShadowStackMismatch(usize, usize),
+    /// The run executed as many instructions as the limit set on the builder without finishing
+    InstructionLimit(u64),
+    /// A syscall failed
+    Syscall(String),
+    /// Data of one width was written to a label declared with another
+    WidthMismatch(String, DataWidth),
+    /// An immediate value doesn't fit the width of the destination it's moved to
+    ImmediateOverflow(u32, DataWidth),
+    /// A read of an uninitialized register or memory byte, with uninitialized tracking set to `Error`
+    UninitializedRead(Warning),
+    /// The AssertReg/AssertFlag at the instruction index failed, with the expected and actual values
+    GuestAssertionFailed(usize, u32, u32),
+    /// A routine called with `CPU::call_guest` stopped for the reason before returning to the host
+    GuestCallEnded(StopReason),
+    /// The guest called the abort syscall with the code
+    Aborted(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+/// How much the CPU reports about a run on its trace output, each level including the ones below it
+pub enum Verbosity {
+    /// Nothing
+    Quiet,
+    /// The final registers and exit code
+    Result,
+    /// One line per executed instruction: its index, disassembly and the registers it changed
+    Instruction,
+    /// A multi-line narrative of every step
+    Full,
+}
+
+impl std::str::FromStr for Verbosity {
+    type Err = String;
+
+    fn from_str(level: &str) -> Result<Verbosity, String> {
+        match level {
+            "quiet" => Ok(Verbosity::Quiet),
+            "result" => Ok(Verbosity::Result),
+            "instruction" => Ok(Verbosity::Instruction),
+            "full" => Ok(Verbosity::Full),
+            _ => Err(format!("Unknown verbosity level: {}, expected quiet, result, instruction or full", level)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Base a value is rendered in by `format_value`
+pub enum Base {
+    /// Zero-padded to the width, e.g. `0x00FF`
+    Hex,
+    Unsigned,
+    /// Two's complement at the value's width, so a word of 0xFFFF is -1
+    Signed,
+    /// Zero-padded to the width, e.g. `0b00000101`
+    Binary,
+}
+
+/// Renders a value truncated to `width` in the given base.
+///
+/// Shared by every place showing register or memory values so they render alike.
+pub fn format_value(value: u32, width: DataWidth, base: Base) -> String {
+    let value = value & width.mask();
+    let digits = width.bytes() * 8;
+    match base {
+        Base::Hex => format!("{:#0w$X}", value, w = digits / 4 + 2),
+        Base::Unsigned => value.to_string(),
+        Base::Signed => match value & width.sign_bit() {
+            0 => value.to_string(),
+            _ => (value as i64 - width.mask() as i64 - 1).to_string(),
+        },
+        Base::Binary => format!("{:#0w$b}", value, w = digits + 2),
+    }
+}
+
+/// Value CALL pushes below the return address when stack canaries are enabled
+pub const STACK_CANARY: u16 = 0xA55A;
+
+#[derive(Debug, Clone, Copy)]
+/// Stack reserved in main memory by `CpuBuilder::stack`
+struct Stack {
+    /// Offset of the lowest byte of the stack, SP can't go below it
+    base: usize,
+    size: usize,
+    /// Whether CALL pushes a canary below the return address
+    canaries: bool,
+    shadow_stack_policy: ShadowStackPolicy,
+}
+
+impl Stack {
+    /// Offset right past the stack, where SP starts
+    fn top(&self) -> usize {
+        self.base + self.size
+    }
+}
+
+/// Calls that may be active at once before a CALL faults with `CallStackOverflow`
+const CALL_STACK_LIMIT: usize = 1024;
+
+/// Return address `CPU::call_guest` records for the routine it calls, returning to it gives control back to the host.
+///
+/// On the stack in main memory it is pushed as 0xFFFF, the address no instruction can have.
+const RETURN_TO_HOST: usize = usize::MAX;
+
+/// Number of bytes diagnostics show of a memory image before eliding the rest, unless the CPU sets another limit
+const BYTES_SHOWN: usize = 64;
+
+/// Renders bytes in hex, showing at most `limit` of them followed by an elision marker and the total length.
+///
+/// Shared by every diagnostic embedding memory contents so a RAM image doesn't flood the output.
+fn format_bytes(bytes: &[u8], limit: usize) -> String {
+    let shown: Vec<String> = bytes.iter().take(limit).map(|byte| format!("{:02X}", byte)).collect();
+    match bytes.len() > limit {
+        true => format!("[{} ... ({} bytes)]", shown.join(" "), bytes.len()),
+        false => format!("[{}]", shown.join(" ")),
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Options of `CPU::format_registers`
+pub struct RegisterFormat {
+    /// Bases each register is shown in, side by side
+    pub bases: Vec<Base>,
+}
+
+impl Default for RegisterFormat {
+    fn default() -> RegisterFormat {
+        RegisterFormat { bases: vec![Base::Hex] }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Main memory usage of a program, as returned by `CPU::memory_report`
+pub struct MemoryReport {
+    /// Total size of main memory
+    pub capacity: usize,
+    /// Bytes reserved below the data base
+    pub reserved: usize,
+    /// Data section labels and their size in bytes, largest first
+    pub symbols: Vec<(String, usize)>,
+    /// Bytes of the environment block
+    pub environment: usize,
+    /// Bytes of the stack
+    pub stack: usize,
+}
+
+impl MemoryReport {
+    /// Bytes needed by the reserved memory, the data section, the environment block and the stack
+    pub fn used(&self) -> usize {
+        self.reserved + self.symbols.iter().map(|(_, size)| size).sum::<usize>() + self.environment + self.stack
+    }
+}
+
+impl std::fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let width = self.symbols.iter().map(|(label, _)| label.len()).max().unwrap_or(0).max(11);
+        writeln!(f, "{:<width$}  Bytes", "Label")?;
+        for (label, size) in &self.symbols {
+            writeln!(f, "{:<width$}  {:>5}", label, size)?;
+        }
+        writeln!(f, "{:<width$}  {:>5}", "Reserved", self.reserved)?;
+        if self.environment > 0 {
+            writeln!(f, "{:<width$}  {:>5}", "Environment", self.environment)?;
+        }
+        if self.stack > 0 {
+            writeln!(f, "{:<width$}  {:>5}", "Stack", self.stack)?;
+        }
+        write!(f, "Used {} of {} bytes, ", self.used(), self.capacity)?;
+        match self.capacity.checked_sub(self.used()) {
+            Some(free) => write!(f, "{} free", free),
+            None => write!(f, "over capacity by {}", self.used() - self.capacity),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Compact state of the CPU taken by `CPU::machine_state`, comparable without the memory image.
+///
+/// It is written as text by its `Display` implementation and read back with `str::parse`:
+///
+/// ```text
+/// registers: AX=0x0005 BX=0x0000 CX=0x0000 DX=0x0000 EAX=0x00000000 EBX=0x00000000 ECX=0x00000000 EDX=0x00000000
+/// flags: 0x0000
+/// sp: 0 bp: 0 ip: 2
+/// memory_hash: 0xCBF29CE484222325
+/// ```
+pub struct MachineState {
+    pub registers: BTreeMap<Register, u32>,
+    /// The flags packed into a word, as returned by `CPU::flags_word`
+    pub flags: u16,
+    pub sp: u32,
+    pub bp: u32,
+    pub ip: u32,
+    /// Digest of main memory, see `memory_hash`
+    pub memory_hash: u64,
+}
+
+impl std::fmt::Display for MachineState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let registers: Vec<String> = self.registers.iter()
+            .map(|(register, value)| format!("{:?}={}", register, format_value(*value, register.width(), Base::Hex)))
+            .collect();
+        writeln!(f, "registers: {}", registers.join(" "))?;
+        writeln!(f, "flags: {:#06X}", self.flags)?;
+        writeln!(f, "sp: {} bp: {} ip: {}", self.sp, self.bp, self.ip)?;
+        write!(f, "memory_hash: {:#018X}", self.memory_hash)
+    }
+}
+
+impl std::str::FromStr for MachineState {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<MachineState, String> {
+        let hex = |value: &str| u64::from_str_radix(value.trim_start_matches("0x"), 16).map_err(|_| format!("Invalid hex value: {}", value));
+        let decimal = |value: &str| value.parse::<u32>().map_err(|_| format!("Invalid value: {}", value));
+        let mut lines = text.lines();
+        let mut field = |name: &str| {
+            lines.next()
+                .and_then(|line| line.strip_prefix(name))
+                .and_then(|line| line.strip_prefix(": "))
+                .ok_or(format!("Expected a line starting with {}:", name))
+        };
+        let mut registers = BTreeMap::new();
+        for assignment in field("registers")?.split(' ') {
+            let (name, value) = assignment.split_once('=').ok_or(format!("Invalid register assignment: {}", assignment))?;
+            let register = Register::all().into_iter()
+                .find(|register| format!("{:?}", register) == name)
+                .ok_or(format!("Unknown register: {}", name))?;
+            registers.insert(register, hex(value)? as u32);
+        }
+        let flags = hex(field("flags")?)? as u16;
+        let pointers: Vec<&str> = field("sp")?.split(' ').collect();
+        let [sp, "bp:", bp, "ip:", ip] = pointers[..] else {
+            return Err(String::from("Expected sp: <sp> bp: <bp> ip: <ip>"));
+        };
+        let (sp, bp, ip) = (decimal(sp)?, decimal(bp)?, decimal(ip)?);
+        let memory_hash = hex(field("memory_hash")?)?;
+        Ok(MachineState { registers, flags, sp, bp, ip, memory_hash })
+    }
+}
+
+/// Instructions a core dump lists as the tail of the trace, the last ones executed before the fault
+const TRACE_TAIL: usize = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Post-mortem image of a faulted CPU, taken by `CPU::core_dump` and read back with `CoreDump::load`.
+///
+/// It holds everything needed to inspect the fault without the program: the machine state, the symbol
+/// table, the disassembly with the values of the labels at the fault, the last instructions executed
+/// and the whole of main memory. Its `Display` implementation writes the text of a core file, with a
+/// header followed by the `[state]`, `[symbols]`, `[trace]`, `[disassembly]` and `[memory]` sections,
+/// memory being an Intel HEX image.
+pub struct CoreDump {
+    /// Debug representation of the `CpuError` of the fault
+    pub error: String,
+    /// Index of the faulting instruction
+    pub ip: usize,
+    pub state: MachineState,
+    /// Data labels and their location, sorted by label
+    pub symbols: Vec<(String, Symbol)>,
+    /// The last instructions executed, oldest first, as `<index>  <instruction>` lines
+    pub trace: Vec<String>,
+    /// One line of `CPU::disassemble` per instruction
+    pub disassembly: Vec<String>,
+    pub memory: Vec<u8>,
+}
+
+impl CoreDump {
+    /// Reads back the text of a core file
+    pub fn load(text: &str) -> Result<CoreDump, String> {
+        let mut header = Vec::new();
+        let mut sections: HashMap<&str, Vec<&str>> = HashMap::new();
+        let mut current = None;
+        for line in text.lines().filter(|line| !line.starts_with(';')) {
+            match line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                Some(name) => current = Some(sections.entry(name).or_default()),
+                None => match &mut current {
+                    Some(section) => section.push(line),
+                    None => header.push(line),
+                },
+            }
+        }
+        let field = |name: &str| header.iter()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(": "))
+            .ok_or(format!("Missing {} in the core header", name));
+        let mut section = |name: &str| sections.remove(name).ok_or(format!("Missing [{}] section", name));
+
+        let error = field("error")?.to_string();
+        let ip = field("fault ip")?.parse().map_err(|_| String::from("Invalid fault ip"))?;
+        let state = section("state")?.join("\n").parse()?;
+        let mut symbols = Vec::new();
+        for line in section("symbols")? {
+            let [label, offset, width] = line.split(' ').collect::<Vec<&str>>()[..] else {
+                return Err(format!("Invalid symbol: {}", line));
+            };
+            let offset = u32::from_str_radix(offset.trim_start_matches("0x"), 16).map_err(|_| format!("Invalid symbol offset: {}", line))?;
+            let width = match width {
+                "byte" => DataWidth::Byte,
+                "word" => DataWidth::Word,
+                "dword" => DataWidth::Dword,
+                _ => return Err(format!("Invalid symbol width: {}", line)),
+            };
+            symbols.push((label.to_string(), Symbol { offset, width }));
+        }
+        let trace = section("trace")?.into_iter().map(str::to_string).collect();
+        let disassembly = section("disassembly")?.into_iter().map(str::to_string).collect();
+        let mut memory = MemoryUnit::new(HashMap::new(), Vec::new());
+        memory.import_ihex(&section("memory")?.join("\n")).map_err(|error| error.to_string())?;
+        Ok(CoreDump { error, ip, state, symbols, trace, disassembly, memory: memory.data_bus.data })
+    }
+
+    /// Value of a register at the fault
+    pub fn register(&self, register: Register) -> u32 {
+        self.state.registers.get(&register).copied().unwrap_or(0)
+    }
+
+    /// Value of a data label at the fault, `None` if the label wasn't declared
+    pub fn read_label(&self, label: &str) -> Option<Data> {
+        let (_, symbol) = self.symbols.iter().find(|(name, _)| name == label)?;
+        let start = symbol.offset as usize;
+        Data::from_bytes(symbol.width, self.memory.get(start..start + symbol.width.bytes())?).ok()
+    }
+
+    /// The disassembly of the instructions up to `radius` before and after the faulting one,
+    /// the faulting one marked with `=>`
+    pub fn disassemble_around(&self, radius: usize) -> String {
+        let mut output = String::new();
+        let end = (self.ip + radius + 1).min(self.disassembly.len());
+        for index in self.ip.saturating_sub(radius)..end {
+            let marker = if index == self.ip { "=>" } else { "  " };
+            output += &format!("{} {}\n", marker, self.disassembly[index]);
+        }
+        output
+    }
+}
+
+impl std::fmt::Display for CoreDump {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "; cpu core dump")?;
+        writeln!(f, "error: {}", self.error)?;
+        writeln!(f, "fault ip: {}", self.ip)?;
+        writeln!(f, "[state]\n{}", self.state)?;
+        writeln!(f, "[symbols]")?;
+        for (label, symbol) in &self.symbols {
+            writeln!(f, "{} {:#06X} {}", label, symbol.offset, format!("{:?}", symbol.width).to_lowercase())?;
+        }
+        writeln!(f, "[trace]")?;
+        for line in &self.trace {
+            writeln!(f, "{}", line)?;
+        }
+        writeln!(f, "[disassembly]")?;
+        for line in &self.disassembly {
+            writeln!(f, "{}", line)?;
+        }
+        writeln!(f, "[memory]")?;
+        let mut image = MemoryUnit::new(HashMap::new(), Vec::new());
+        image.data_bus.data = self.memory.clone();
+        // Exporting memory as a whole can't go out of bounds
+        write!(f, "{}", image.export_ihex(0..self.memory.len()).unwrap_or_default())
+    }
+}
+
+/// 64 bit FNV-1a digest of `bytes`. Unlike `DefaultHasher` it is the same on every
+/// build, so digests can be stored and compared across runs.
+fn memory_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+#[derive(Clone, PartialEq)]
+/// Architectural state of the CPU at a point of a run, taken by `CPU::snapshot`
+pub struct CpuSnapshot {
+    /// Values of the general purpose registers, in register file order
+    registers: Vec<u32>,
+    /// The flags packed into a word, bit `n` holding the flag at index `n` of the flags array
+    flags_word: u16,
+    /// Contents of main memory
+    memory: Vec<u8>,
+}
+
+impl Debug for CpuSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CpuSnapshot")
+            .field("registers", &self.registers)
+            .field("flags_word", &self.flags_word)
+            .field("memory", &format_args!("{}", format_bytes(&self.memory, BYTES_SHOWN)))
+            .finish()
+    }
+}
+
+impl CpuSnapshot {
+    /// Lists what changed from this snapshot to `other`.
+    ///
+    /// Differing memory bytes are coalesced into ranges of adjacent bytes, bytes present in only one
+    /// of the snapshots counting as different.
+    pub fn diff(&self, other: &CpuSnapshot) -> SnapshotDiff {
+        let registers = Register::all().into_iter()
+            .zip(self.registers.iter().zip(&other.registers))
+            .filter(|(_, (old, new))| old != new)
+            .map(|(register, (old, new))| (register, *old, *new))
+            .collect();
+
+        let flags = (0..FLAG_NAMES.len())
+            .filter(|index| (self.flags_word ^ other.flags_word) & (1 << index) != 0)
+            .map(|index| (index, other.flags_word & (1 << index) != 0))
+            .collect();
+
+        let mut memory: Vec<std::ops::Range<usize>> = Vec::new();
+        for offset in 0..self.memory.len().max(other.memory.len()) {
+            if self.memory.get(offset) == other.memory.get(offset) {
+                continue;
+            }
+            match memory.last_mut() {
+                Some(range) if range.end == offset => range.end += 1,
+                _ => memory.push(offset..offset + 1),
+            }
+        }
+
+        SnapshotDiff { registers, flags, memory }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Differences between two snapshots, as returned by `CpuSnapshot::diff`
+pub struct SnapshotDiff {
+    /// Changed registers with their old and new values
+    pub registers: Vec<(Register, u32, u32)>,
+    /// Changed flags, by index in the flags array, with their new state
+    pub flags: Vec<(usize, bool)>,
+    /// Ranges of main memory offsets whose bytes differ
+    pub memory: Vec<std::ops::Range<usize>>,
+}
+
+impl SnapshotDiff {
+    /// Whether the snapshots are identical
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty() && self.flags.is_empty() && self.memory.is_empty()
+    }
+}
+
+impl std::fmt::Display for SnapshotDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return write!(f, "No changes");
+        }
+        let mut lines = Vec::new();
+        for (register, old, new) in &self.registers {
+            lines.push(format!("{:?}: {} -> {}", register, format_value(*old, register.width(), Base::Hex), format_value(*new, register.width(), Base::Hex)));
+        }
+        for (index, set) in &self.flags {
+            lines.push(format!("{}: {} -> {}", FLAG_NAMES[*index], !set as u8, *set as u8));
+        }
+        for range in &self.memory {
+            lines.push(format!("Memory {:#06X}..{:#06X}: {} bytes", range.start, range.end, range.len()));
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// The syscall ABI, shared by the dispatcher, host code and the guest header printed by `cpu abi --emit-asm`.
+///
+/// The syscall number goes in `NUMBER`, arguments in `ARG1` to `ARG3`, and results come back in `RESULT`.
+pub mod syscall_abi {
+    use super::Register;
+
+    pub const SYS_READ: u8 = 1;
+    pub const SYS_WRITE: u8 = 2;
+    pub const SYS_PRINT_STRING: u8 = 3;
+    pub const SYS_COUNTERS: u8 = 4;
+    pub const SYS_MEMCPY: u8 = 5;
+    pub const SYS_MEMSET: u8 = 6;
+    pub const SYS_ENVIRONMENT: u8 = 7;
+    pub const SYS_LOG: u8 = 8;
+    pub const SYS_YIELD: u8 = 9;
+    pub const SYS_ABORT: u8 = 10;
+    pub const SYS_EXIT: u8 = 60;
+
+    pub const NUMBER: Register = Register::AX;
+    /// File descriptor, source offset, fill byte, log level or exit code
+    pub const ARG1: Register = Register::BX;
+    /// Buffer offset
+    pub const ARG2: Register = Register::CX;
+    /// Buffer length
+    pub const ARG3: Register = Register::DX;
+    pub const RESULT: Register = Register::AX;
+
+    /// Returned in `RESULT` when a syscall fails
+    pub const ERROR: u16 = 0xFFFF;
+
+    pub const LOG_DEBUG: u32 = 0;
+    pub const LOG_INFO: u32 = 1;
+    pub const LOG_WARN: u32 = 2;
+    pub const LOG_ERROR: u32 = 3;
+
+    /// Renders the constants as NASM `equ` definitions to be included by guest programs
+    pub fn asm_header() -> String {
+        let constants: [(&str, u32); 16] = [
+            ("SYS_READ", SYS_READ as u32),
+            ("SYS_WRITE", SYS_WRITE as u32),
+            ("SYS_PRINT_STRING", SYS_PRINT_STRING as u32),
+            ("SYS_COUNTERS", SYS_COUNTERS as u32),
+            ("SYS_MEMCPY", SYS_MEMCPY as u32),
+            ("SYS_MEMSET", SYS_MEMSET as u32),
+            ("SYS_ENVIRONMENT", SYS_ENVIRONMENT as u32),
+            ("SYS_LOG", SYS_LOG as u32),
+            ("SYS_YIELD", SYS_YIELD as u32),
+            ("SYS_ABORT", SYS_ABORT as u32),
+            ("SYS_EXIT", SYS_EXIT as u32),
+            ("SYS_ERROR", ERROR as u32),
+            ("LOG_DEBUG", LOG_DEBUG),
+            ("LOG_INFO", LOG_INFO),
+            ("LOG_WARN", LOG_WARN),
+            ("LOG_ERROR", LOG_ERROR),
+        ];
+        let mut header = String::from("; Syscall ABI: number in AX, arguments in BX, CX, DX, result in AX\n");
+        for (name, value) in constants {
+            header += &format!("{:<16} equ {}\n", name, value);
+        }
+        header
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Severity of a message logged by the guest, passed in BX to the log syscall (8)
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    /// Name of the level in serialized events and log prefixes, e.g. `"warn"`
+    pub fn name(&self) -> &'static str {
+        match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+        }
+    }
+
+    fn from_code(code: u32) -> Option<LogLevel> {
+        match code {
+            syscall_abi::LOG_DEBUG => Some(LogLevel::Debug),
+            syscall_abi::LOG_INFO => Some(LogLevel::Info),
+            syscall_abi::LOG_WARN => Some(LogLevel::Warn),
+            syscall_abi::LOG_ERROR => Some(LogLevel::Error),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A message logged by the guest through the log syscall (8)
+pub struct GuestLog {
+    /// Index of the syscall instruction that logged the message
+    pub instruction: usize,
+    pub level: LogLevel,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// When terminal output is colored
+pub enum ColorChoice {
+    /// Only when stdout is a terminal, so piped output stays plain text
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn enabled(&self) -> bool {
+        match self {
+            ColorChoice::Auto => stdout().is_terminal(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+impl std::str::FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(choice: &str) -> Result<ColorChoice, String> {
+        match choice {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!("Unknown color choice: {}, expected auto, always or never", choice)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Colors used to highlight terminal output
+pub enum Color {
+    /// Errors
+    Red,
+    /// Warnings
+    Yellow,
+    /// Values changed by an instruction
+    Green,
+    /// Informational messages
+    Cyan,
+}
+
+/// Wraps text in the ANSI escape sequences of a color, or returns it unchanged when color is disabled
+pub fn paint(text: &str, color: Color, enabled: bool) -> String {
+    if !enabled {
+        return text.to_string();
+    }
+    let code = match color {
+        Color::Red => 31,
+        Color::Yellow => 33,
+        Color::Green => 32,
+        Color::Cyan => 36,
+    };
+    format!("\x1b[{}m{}\x1b[0m", code, text)
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+/// Options of `CPU::disassemble`
+pub struct DisasmOptions {
+    /// Annotate memory operands with the current value of their label
+    pub current_values: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// What the run loop does after a hook installed with `CPU::hook_before` returns
+pub enum HookAction {
+    /// Execute the instruction as usual
+    Continue,
+    /// Move on to the next instruction without executing this one
+    SkipInstruction,
+    /// Return from the active call without executing the instruction, as a RET would.
+    ///
+    /// Hooked on the first instruction of a subroutine, it stubs the subroutine out for every CALL.
+    Return,
+    /// End the run before executing the instruction, as if the program ran to completion
+    Stop,
+}
+
+/// Host code run before an instruction, see `CPU::hook_before`
+struct Hook(Box<dyn FnMut(&mut CpuView) -> HookAction>);
+
+impl Debug for Hook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Hook")
+    }
+}
+
+/// Source of the time a run takes, replaced in tests to control how fast time passes
+pub trait Clock: Debug {
+    /// Time elapsed since a fixed point of the clock
+    fn now(&self) -> Duration;
+
+    /// Waits for the duration to pass, used by `CPU::run_throttled` to pace the program
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+#[derive(Debug)]
+/// The system's monotonic clock, counting from the instant it was created
+struct SystemClock(Instant);
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.0.elapsed()
+    }
+}
+
+/// Sink of the output the guest writes with syscalls
+struct GuestOutput(Box<dyn Write>);
+
+impl Debug for GuestOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "GuestOutput")
+    }
+}
+
+/// Source of the input the guest reads with syscalls
+struct GuestInput(Box<dyn Read>);
+
+impl Debug for GuestInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "GuestInput")
+    }
+}
+
+/// Sink of the CPU's trace output, shared by the stages that report on a run
+struct TraceOutput(RefCell<Box<dyn Write>>);
+
+impl Debug for TraceOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TraceOutput")
+    }
+}
+
+/// The part of the CPU a hook may read and change: registers and data section labels
+pub struct CpuView<'a> {
+    cpu: &'a mut CPU,
+}
+
+impl CpuView<'_> {
+    /// Index of the instruction about to run
+    pub fn instruction(&self) -> usize {
+        self.cpu.ip()
+    }
+
+    pub fn register(&self, register: Register) -> u32 {
+        self.cpu.register(register)
+    }
+
+    /// Sets a register, the value is truncated to the register's width
+    pub fn set_register(&mut self, register: Register, value: u32) {
+        self.cpu.registers.get_register(register.clone()).set_value(Data::from_u32(register.width(), value));
+        self.cpu.mark_register_written(&register);
+    }
+
+    pub fn read_label(&self, label: &str) -> Result<Data, CpuError> {
+        self.cpu.read_label(label)
+    }
+
+    pub fn write_label(&mut self, label: &str, data: Data) -> Result<(), CpuError> {
+        self.cpu.write_label(label, data)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Something an instruction did, reported to the observers installed with `CpuBuilder::observer`
+pub enum Event {
+    /// The instruction finished executing, with its disassembly
+    InstructionExecuted(String),
+    /// The register changed from the first value to the second
+    RegisterWrite(Register, u32, u32),
+    /// The bytes were written to main memory at the offset
+    MemoryWrite(usize, Vec<u8>),
+    /// The flag with the name, e.g. `"ZF"`, changed to the value
+    FlagChange(&'static str, bool),
+    /// The syscall with the number was called
+    Syscall(u8),
+    /// The instruction raised the warning, e.g. by reading an uninitialized register
+    Warning(Warning),
+    /// The ALU executed the operation, see `CPU::alu_trace`
+    AluOperation(AluTrace),
+    /// Pipeline view: the instruction, with its disassembly, was fetched
+    Fetched(String),
+    /// Pipeline view: the instruction resolved its source operands, each with the value read
+    Decoded(Vec<(String, u32)>),
+    /// Pipeline view: the instruction wrote its results, each destination with its new value
+    Executed(Vec<(String, u32)>),
+    /// The guest logged the message through the log syscall
+    GuestLog(GuestLog),
+    /// The expression watched with `CPU::add_watch` changed from the first value to the second
+    WatchChanged(String, i64, i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The kind of an `Event`, used to filter events
+pub enum EventKind {
+    InstructionExecuted,
+    RegisterWrite,
+    MemoryWrite,
+    FlagChange,
+    Syscall,
+    Warning,
+    AluOperation,
+    Fetched,
+    Decoded,
+    Executed,
+    GuestLog,
+    WatchChanged,
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::InstructionExecuted(_) => EventKind::InstructionExecuted,
+            Event::RegisterWrite(..) => EventKind::RegisterWrite,
+            Event::MemoryWrite(..) => EventKind::MemoryWrite,
+            Event::FlagChange(..) => EventKind::FlagChange,
+            Event::Syscall(_) => EventKind::Syscall,
+            Event::Warning(_) => EventKind::Warning,
+            Event::AluOperation(_) => EventKind::AluOperation,
+            Event::Fetched(_) => EventKind::Fetched,
+            Event::Decoded(_) => EventKind::Decoded,
+            Event::Executed(_) => EventKind::Executed,
+            Event::GuestLog(_) => EventKind::GuestLog,
+            Event::WatchChanged(..) => EventKind::WatchChanged,
+        }
+    }
+}
+
+impl EventKind {
+    /// Name of the kind in serialized events, e.g. `"register_write"`
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::InstructionExecuted => "instruction_executed",
+            EventKind::RegisterWrite => "register_write",
+            EventKind::MemoryWrite => "memory_write",
+            EventKind::FlagChange => "flag_change",
+            EventKind::Syscall => "syscall",
+            EventKind::Warning => "warning",
+            EventKind::AluOperation => "alu_operation",
+            EventKind::Fetched => "fetched",
+            EventKind::Decoded => "decoded",
+            EventKind::Executed => "executed",
+            EventKind::GuestLog => "guest_log",
+            EventKind::WatchChanged => "watch_changed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// The instruction an event comes from, passed to observers with every event
+pub struct ExecContext<'a> {
+    /// Index of the instruction in the code section
+    pub ip: usize,
+    pub instruction: &'a Instruction,
+    /// Number of the instruction among those the CPU executed, starting at 1
+    pub step_number: u64,
+}
+
+/// Receives the events of the instructions as they execute.
+///
+/// The events of an instruction are reported in the order ALU operations, memory writes, syscall, guest log,
+/// register changes, flag changes, and last `InstructionExecuted`. With the pipeline view on, `Fetched`
+/// comes first and `Decoded` and `Executed` come right before the register changes: execution stays
+/// sequential, so the stage events are only complete once the instruction has run. Warnings are reported as soon as they are raised. An
+/// instruction that faults reports no register, flag or executed events. Under `CPU::run_debug`, changes
+/// of watched expressions are reported last.
+pub trait Observer {
+    /// Called with the instruction that caused the event, including events of the syscalls it made
+    fn event(&mut self, context: &ExecContext, event: &Event);
+}
+
+/// The observers installed on a CPU
+struct Observers(Vec<Box<dyn Observer>>);
+
+impl Debug for Observers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} observers", self.0.len())
+    }
+}
+
+/// Observer writing every event as a JSON object on its own line, for tools outside Rust.
+///
+/// Each object has an `"event"` field with the name of its kind, an `"index"` field with the index of
+/// the instruction that caused it and a `"step"` field with its step number, followed by the fields of
+/// the event:
+/// - `instruction_executed`: `"instruction"`, the disassembly of the instruction
+/// - `register_write`: `"register"`, e.g. `"AX"`, and the `"old"` and `"new"` values
+/// - `memory_write`: `"offset"` and `"bytes"`, an array of the bytes written
+/// - `flag_change`: `"flag"`, e.g. `"ZF"`, and the boolean `"value"`
+/// - `syscall`: `"number"`
+/// - `warning`: `"warning"`, the debug representation of the `Warning`
+/// - `alu_operation`: `"mode"`, e.g. `"Add"`, `"width"`, `"operands"`, an array of the two operands,
+///   `"result"` and `"flags"`, e.g. `"CF=1 OF=0 ZF=1 SF=0 PF=1 AF=1"`
+/// - `fetched`: `"instruction"`, the disassembly of the instruction
+/// - `decoded` and `executed`: `"operands"`, an array of the operands read or written, e.g. `"ax"`, and
+///   `"values"`, an array of their values
+/// - `guest_log`: `"level"`, e.g. `"warn"`, and `"message"`
+/// - `watch_changed`: `"expression"` and its `"old"` and `"new"` values
+///
+/// Fields are only ever added to this schema. Failing writes are ignored, like those of the trace output.
+pub struct JsonLinesObserver<W: Write> {
+    output: W,
+    /// Kinds of the events written, all of them if `None`
+    kinds: Option<Vec<EventKind>>,
+}
+
+impl<W: Write> JsonLinesObserver<W> {
+    pub fn new(output: W) -> JsonLinesObserver<W> {
+        JsonLinesObserver { output, kinds: None }
+    }
+
+    /// Only writes the events of the kinds, to keep the volume of the stream down
+    pub fn only(mut self, kinds: &[EventKind]) -> JsonLinesObserver<W> {
+        self.kinds = Some(kinds.to_vec());
+        self
+    }
+}
+
+impl<W: Write> Observer for JsonLinesObserver<W> {
+    fn event(&mut self, context: &ExecContext, event: &Event) {
+        if let Some(kinds) = &self.kinds
+            && !kinds.contains(&event.kind()) {
+            return;
+        }
+        let fields = match event {
+            Event::InstructionExecuted(instruction) => format!("\"instruction\":{}", json_string(instruction)),
+            Event::RegisterWrite(register, old, new) => format!("\"register\":\"{:?}\",\"old\":{},\"new\":{}", register, old, new),
+            Event::MemoryWrite(offset, bytes) => {
+                let bytes: Vec<String> = bytes.iter().map(|byte| byte.to_string()).collect();
+                format!("\"offset\":{},\"bytes\":[{}]", offset, bytes.join(","))
+            },
+            Event::FlagChange(flag, value) => format!("\"flag\":\"{}\",\"value\":{}", flag, value),
+            Event::Syscall(number) => format!("\"number\":{}", number),
+            Event::Warning(warning) => format!("\"warning\":{}", json_string(&format!("{:?}", warning))),
+            Event::AluOperation(trace) => format!(
+                "\"mode\":\"{:?}\",\"width\":\"{:?}\",\"operands\":[{},{}],\"result\":{},\"flags\":\"{}\"",
+                trace.mode, trace.width, trace.operands.0, trace.operands.1, trace.result, trace.flags,
+            ),
+            Event::Fetched(instruction) => format!("\"instruction\":{}", json_string(instruction)),
+            Event::Decoded(operands) | Event::Executed(operands) => {
+                let names: Vec<String> = operands.iter().map(|(operand, _)| json_string(operand)).collect();
+                let values: Vec<String> = operands.iter().map(|(_, value)| value.to_string()).collect();
+                format!("\"operands\":[{}],\"values\":[{}]", names.join(","), values.join(","))
+            },
+            Event::WatchChanged(expression, old, new) => format!("\"expression\":{},\"old\":{},\"new\":{}", json_string(expression), old, new),
+            Event::GuestLog(log) => format!("\"level\":\"{}\",\"message\":{}", log.level.name(), json_string(&log.message)),
+        };
+        let _ = writeln!(self.output, "{{\"event\":\"{}\",\"index\":{},\"step\":{},{}}}", event.kind().name(), context.ip, context.step_number, fields);
+    }
+}
+
+/// Quotes text as a JSON string
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for character in text.chars() {
+        match character {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            character if (character as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", character as u32)),
+            character => quoted.push(character),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Summary of a finished run of a program
+pub struct RunOutcome {
+    /// Code passed to the exit syscall, 0 if the program ran past its last instruction
+    pub exit_code: u8,
+    /// Instructions executed by the CPU so far
+    pub instructions: u64,
+    /// Cycles spent by the CPU so far
+    pub cycles: u64,
+    /// Wall-clock duration of the run
+    pub duration: Duration,
+    /// Whether validation found suspicious code in the program or warnings were raised while running it
+    pub warnings: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Why `CPU::step` returned
+pub enum StopReason {
+    /// The instruction ran and the program goes on with the next one
+    Stepped,
+    /// The program exited with the code passed to the exit syscall, or with 0 by running past its last instruction
+    Exited(u8),
+    /// The hook of the instruction at the index stopped the program before the instruction executed
+    Hook(usize),
+    /// The program reached the instruction at the index, which has a breakpoint, and hasn't executed it yet
+    Breakpoint(usize),
+    /// The stop flag of `CPU::run_throttled` was set
+    Paused,
+    /// A condition set with `CPU::break_when` was met by the register, which changed from the first value
+    /// to the second, by the instruction that just executed
+    RegisterCondition(Register, u32, u32),
+    /// The expression set with `CPU::break_when_expr` became true with the instruction that just executed
+    ExpressionCondition(String),
+    /// The instruction that just executed was the yield syscall, ending the slice of a `Scheduler`.
+    ///
+    /// Everywhere else the program goes on as if it had stepped.
+    Yielded,
+    /// The routine called by `CPU::call_guest` returned to the host
+    ReturnedToHost,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// An argument passed by `CPU::call_guest` to the routine it calls
+pub enum Arg {
+    /// Sets the register to the value, truncated to its width
+    Reg(Register, u32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// What a routine called with `CPU::call_guest` returned
+pub struct GuestReturn {
+    /// Values of the general purpose registers when the routine returned, in register file order
+    registers: Vec<u32>,
+    /// Instructions the routine executed
+    pub instructions: u64,
+}
+
+impl GuestReturn {
+    /// Value of a register when the routine returned
+    pub fn register(&self, register: Register) -> u32 {
+        self.registers[register.index()]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Condition on a register's value stopping `CPU::run_debug`, see `CPU::break_when`
+pub enum Condition {
+    /// The register becomes equal to the value
+    Equals(u32),
+    /// The register becomes less than the value, compared unsigned
+    LessThan(u32),
+    /// The register changes
+    Changed,
+}
+
+impl Condition {
+    /// Whether an instruction changing a register from `old` to `new` meets the condition.
+    ///
+    /// A value condition is only met when the register enters it, not while it stays in it.
+    fn met(&self, old: u32, new: u32) -> bool {
+        match self {
+            Condition::Equals(value) => new == *value && old != *value,
+            Condition::LessThan(value) => new < *value && old >= *value,
+            Condition::Changed => new != old,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Binary operators of an `Expr`
+pub enum BinaryOp {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Add,
+    Sub,
+    Mul,
+}
+
+impl BinaryOp {
+    /// Operators from the loosest binding to the tightest, each level with its operators
+    const LEVELS: [&'static [(&'static str, BinaryOp)]; 5] = [
+        &[("||", BinaryOp::Or)],
+        &[("&&", BinaryOp::And)],
+        &[
+            ("==", BinaryOp::Equal), ("!=", BinaryOp::NotEqual), ("<=", BinaryOp::LessOrEqual),
+            (">=", BinaryOp::GreaterOrEqual), ("<", BinaryOp::Less), (">", BinaryOp::Greater),
+        ],
+        &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+        &[("*", BinaryOp::Mul)],
+    ];
+
+    fn apply(&self, left: i64, right: i64) -> i64 {
+        match self {
+            BinaryOp::Or => (left != 0 || right != 0) as i64,
+            BinaryOp::And => (left != 0 && right != 0) as i64,
+            BinaryOp::Equal => (left == right) as i64,
+            BinaryOp::NotEqual => (left != right) as i64,
+            BinaryOp::Less => (left < right) as i64,
+            BinaryOp::LessOrEqual => (left <= right) as i64,
+            BinaryOp::Greater => (left > right) as i64,
+            BinaryOp::GreaterOrEqual => (left >= right) as i64,
+            BinaryOp::Add => left.wrapping_add(right),
+            BinaryOp::Sub => left.wrapping_sub(right),
+            BinaryOp::Mul => left.wrapping_mul(right),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// An expression over the state of the machine, as used by `CPU::add_watch`, `CPU::break_when_expr`
+/// and `CPU::evaluate`, e.g. `cx - 1`, `[result] == 290` or `zf && cx < 5`.
+///
+/// Registers and flags are named in any case, `[label]` is the value of a data label and literals are
+/// decimal or `0x` hexadecimal. Comparisons and logical operators evaluate to 1 or 0, and any value
+/// other than 0 is true. Operators bind like in C, from `||` loosest through `&&`, comparisons and
+/// `+`/`-` to `*`, with `!` and `-` as prefixes and parentheses for grouping.
+pub enum Expr {
+    Register(Register),
+    /// A flag, by its index in `FLAG_NAMES`
+    Flag(usize),
+    /// Value of the data label
+    Label(String),
+    Literal(i64),
+    Not(Box<Expr>),
+    Negate(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Evaluates the expression on the current state of the CPU, failing if it reads an undeclared label
+    pub fn evaluate(&self, cpu: &CPU) -> Result<i64, CpuError> {
+        Ok(match self {
+            Expr::Register(register) => cpu.register(register.clone()) as i64,
+            Expr::Flag(index) => ((cpu.flags_word() >> index) & 1) as i64,
+            Expr::Label(label) => cpu.read_label(label)?.get_value() as i64,
+            Expr::Literal(value) => *value,
+            Expr::Not(operand) => (operand.evaluate(cpu)? == 0) as i64,
+            Expr::Negate(operand) => operand.evaluate(cpu)?.wrapping_neg(),
+            Expr::Binary(op, left, right) => op.apply(left.evaluate(cpu)?, right.evaluate(cpu)?),
+        })
+    }
+
+    /// Parses the operators of `LEVELS[level]` and tighter ones, left associative
+    fn parse_level(tokens: &mut ExprTokens, level: usize) -> Result<Expr, String> {
+        let Some(operators) = BinaryOp::LEVELS.get(level) else {
+            return Expr::parse_unary(tokens);
+        };
+        let mut left = Expr::parse_level(tokens, level + 1)?;
+        while let Some(&(symbol, op)) = operators.iter().find(|(symbol, _)| tokens.peek_symbol(symbol)) {
+            tokens.advance(symbol.len());
+            let right = Expr::parse_level(tokens, level + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(tokens: &mut ExprTokens) -> Result<Expr, String> {
+        if tokens.eat("!") {
+            return Ok(Expr::Not(Box::new(Expr::parse_unary(tokens)?)));
+        }
+        if tokens.eat("-") {
+            return Ok(Expr::Negate(Box::new(Expr::parse_unary(tokens)?)));
+        }
+        if tokens.eat("(") {
+            let inner = Expr::parse_level(tokens, 0)?;
+            tokens.expect(")")?;
+            return Ok(inner);
+        }
+        if tokens.eat("[") {
+            let label = tokens.word().ok_or("Expected a label after [")?;
+            tokens.expect("]")?;
+            return Ok(Expr::Label(label.to_string()));
+        }
+        let Some(word) = tokens.word() else {
+            return Err(format!("Expected a value at: {}", tokens.rest()));
+        };
+        if let Some(digits) = word.strip_prefix("0x") {
+            return i64::from_str_radix(digits, 16).map(Expr::Literal).map_err(|_| format!("Invalid number: {}", word));
+        }
+        if word.starts_with(|character: char| character.is_ascii_digit()) {
+            return word.parse().map(Expr::Literal).map_err(|_| format!("Invalid number: {}", word));
+        }
+        if let Some(register) = Register::all().into_iter().find(|register| format!("{:?}", register).eq_ignore_ascii_case(word)) {
+            return Ok(Expr::Register(register));
+        }
+        match FLAG_NAMES.iter().position(|name| name.eq_ignore_ascii_case(word)) {
+            Some(index) => Ok(Expr::Flag(index)),
+            None => Err(format!("Unknown register or flag: {}", word)),
+        }
+    }
+}
+
+/// The remaining text of an expression being parsed
+struct ExprTokens<'a> {
+    text: &'a str,
+}
+
+impl<'a> ExprTokens<'a> {
+    fn skip_whitespace(&mut self) {
+        self.text = self.text.trim_start();
+    }
+
+    fn rest(&self) -> &'a str {
+        self.text
+    }
+
+    /// Whether the next token is the symbol, `<` not matching the start of `<=`
+    fn peek_symbol(&mut self, symbol: &str) -> bool {
+        self.skip_whitespace();
+        self.text.starts_with(symbol) && !(symbol.len() == 1 && "<>".contains(symbol) && self.text[1..].starts_with('='))
+    }
+
+    fn advance(&mut self, length: usize) {
+        self.text = &self.text[length..];
+    }
+
+    /// Consumes the symbol if it comes next
+    fn eat(&mut self, symbol: &str) -> bool {
+        let found = self.peek_symbol(symbol);
+        if found {
+            self.advance(symbol.len());
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        match self.eat(symbol) {
+            true => Ok(()),
+            false => Err(format!("Expected {} at: {}", symbol, self.rest())),
+        }
+    }
+
+    /// Consumes the next name or number
+    fn word(&mut self) -> Option<&'a str> {
+        self.skip_whitespace();
+        let length = self.text.find(|character: char| !(character.is_ascii_alphanumeric() || character == '_')).unwrap_or(self.text.len());
+        let (word, rest) = self.text.split_at(length);
+        self.text = rest;
+        (!word.is_empty()).then_some(word)
+    }
+}
+
+impl std::str::FromStr for Expr {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Expr, String> {
+        let mut tokens = ExprTokens { text };
+        let expr = Expr::parse_level(&mut tokens, 0)?;
+        tokens.skip_whitespace();
+        match tokens.rest() {
+            "" => Ok(expr),
+            rest => Err(format!("Unexpected text at: {}", rest)),
+        }
+    }
+}
+
+/// An expression watched with `CPU::add_watch`, with its value after the last instruction
+#[derive(Debug)]
+struct Watch {
+    expression: String,
+    expr: Expr,
+    value: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A read or write of main memory by an instruction
+pub struct MemoryAccess {
+    /// Offset in main memory of the first byte accessed
+    pub offset: usize,
+    /// Number of bytes accessed
+    pub length: usize,
+    /// Whether the access wrote memory rather than read it
+    pub write: bool,
+}
+
+#[derive(Debug)]
+/// Memory accesses recorded during a run, up to a limit
+struct AccessLog {
+    limit: usize,
+    accesses: Vec<MemoryAccess>,
+    /// Accesses not recorded because the log was full
+    dropped: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Shape of a sequence of memory accesses, as classified by `classify_accesses`
+pub enum AccessPattern {
+    /// Each access starts where the previous one ended
+    Sequential,
+    /// Each access starts the same distance in bytes after the previous one
+    Strided(isize),
+    Random,
+}
+
+/// Classifies the pattern of a sequence of memory accesses from the distance between consecutive accesses.
+pub fn classify_accesses(accesses: &[MemoryAccess]) -> AccessPattern {
+    let strides: Vec<isize> = accesses.windows(2)
+        .map(|pair| pair[1].offset as isize - pair[0].offset as isize)
+        .collect();
+    if accesses.windows(2).all(|pair| pair[1].offset == pair[0].offset + pair[0].length) {
+        return AccessPattern::Sequential;
+    }
+    match strides.first() {
+        Some(stride) if strides.iter().all(|other| other == stride) => AccessPattern::Strided(*stride),
+        _ => AccessPattern::Random,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Geometry of the direct-mapped cache simulated by `analyze_cache`
+pub struct CacheConfig {
+    /// Total size of the cache in bytes
+    pub size: usize,
+    /// Size of a cache line in bytes
+    pub line_size: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Hits and misses of a simulated cache, counted per cache line touched by an access
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl CacheStats {
+    /// Fraction of the lookups that hit, 0 without any
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            total => self.hits as f64 / total as f64,
+        }
+    }
+}
+
+/// Replays memory accesses through a direct-mapped cache, starting cold.
+///
+/// An access spanning several lines counts one hit or miss per line.
+pub fn analyze_cache(accesses: &[MemoryAccess], config: CacheConfig) -> CacheStats {
+    let line_size = config.line_size.max(1);
+    let lines = (config.size / line_size).max(1);
+    let mut tags: Vec<Option<usize>> = vec![None; lines];
+    let mut stats = CacheStats { hits: 0, misses: 0 };
+    for access in accesses {
+        let first = access.offset / line_size;
+        let last = (access.offset + access.length.max(1) - 1) / line_size;
+        for line in first..=last {
+            let (index, tag) = (line % lines, line / lines);
+            if tags[index] == Some(tag) {
+                stats.hits += 1;
+            } else {
+                stats.misses += 1;
+                tags[index] = Some(tag);
+            }
+        }
+    }
+    stats
+}
+
+#[derive(Debug)]
+/// State of the machine captured at the instruction that raised an error
+pub struct FaultContext {
+    /// Index of the faulting instruction
+    ip: usize,
+    /// Disassembly of the faulting instruction
+    instruction: String,
+    /// Values of the general purpose registers, in the order AX, BX, CX, DX, EAX, EBX, ECX, EDX
+    registers: Vec<u32>,
+    /// The flags packed into a word, bit `n` holding the flag at index `n` of the flags array
+    flags_word: u16,
+    /// Return addresses of the active calls, innermost last
+    call_stack: Vec<usize>,
+    last_memory_access: Option<MemoryAccess>,
+    /// Hexdump of main memory, capped at the CPU's dump limit
+    memory: String,
+}
+
+#[derive(Debug)]
+/// An error raised while running a program, with the state of the machine when it happened
+pub struct Fault {
+    error: CpuError,
+    context: Box<FaultContext>,
+}
+
+impl Fault {
+    /// The error raised by the instruction
+    pub fn error(&self) -> &CpuError {
+        &self.error
+    }
+
+    /// The state of the machine at the faulting instruction
+    pub fn context(&self) -> &FaultContext {
+        &self.context
+    }
+}
+
+impl FaultContext {
+    /// Index of the faulting instruction, the value of IP before it executed
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    /// Disassembly of the faulting instruction
+    pub fn instruction(&self) -> &str {
+        &self.instruction
+    }
+
+    /// Values of the general purpose registers, in the order AX, BX, CX, DX, EAX, EBX, ECX, EDX
+    pub fn registers(&self) -> &[u32] {
+        &self.registers
+    }
+
+    /// The flags packed into a word, bit `n` holding the flag at index `n` of the flags array
+    pub fn flags_word(&self) -> u16 {
+        self.flags_word
+    }
+
+    /// Return addresses of the calls active at the fault, innermost last
+    pub fn call_stack(&self) -> &[usize] {
+        &self.call_stack
+    }
+
+    /// The last access of main memory before the fault, which may be the one that faulted
+    pub fn last_memory_access(&self) -> Option<MemoryAccess> {
+        self.last_memory_access
+    }
+
+    /// Hexdump of main memory at the fault, capped at the CPU's dump limit
+    pub fn memory(&self) -> &str {
+        &self.memory
+    }
+}
+
+impl std::fmt::Display for Fault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let context = &self.context;
+        writeln!(f, "CPU fault: {:?}", self.error)?;
+        writeln!(f, "IP: {:04}  {}", context.ip, context.instruction)?;
+        let names = ["AX", "BX", "CX", "DX", "EAX", "EBX", "ECX", "EDX"];
+        for (i, (name, value)) in names.iter().zip(&context.registers).enumerate() {
+            write!(f, "{:>3}: {:#010X}", name, value)?;
+            match i % 4 {
+                3 => writeln!(f)?,
+                _ => write!(f, "  ")?,
+            }
+        }
+        let set_flags: Vec<&str> = FLAG_NAMES.iter()
+            .enumerate()
+            .filter(|(index, _)| context.flags_word & (1 << index) != 0)
+            .map(|(_, name)| *name)
+            .collect();
+        writeln!(f, "Flags: {:#06X} [{}]", context.flags_word, set_flags.join(" "))?;
+        match context.call_stack.is_empty() {
+            true => writeln!(f, "Call stack: <empty>")?,
+            false => writeln!(f, "Call stack: {:?}", context.call_stack)?,
+        }
+        match context.last_memory_access {
+            Some(access) => writeln!(f, "Last memory access: {} of {} bytes at {:#06X}", if access.write { "write" } else { "read" }, access.length, access.offset)?,
+            None => writeln!(f, "Last memory access: <none>")?,
+        }
+        write!(f, "Memory: {}", context.memory)
+    }
+}
+
+impl std::fmt::Display for RunOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Exited with code {} after {} instructions ({} cycles) in {:?}", self.exit_code, self.instructions, self.cycles, self.duration)?;
+        if self.warnings {
+            write!(f, " with warnings")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+/// Central Processing Unit.
+/// 
+/// This is the main unit that controls the execution of the program.
+/// 
+/// It contains the ALU, Registers and Memory Unit.
+// TODO: Implement the CPU's store_label_data method to cater for different data sizes
+pub struct CPU {
+    alu: ALU,
+    registers: Registers,
+    flags: [FLAGS; 9],
+    memory_unit: MemoryUnit,
+    /// Cycle costs overriding the built-in cost table
+    costs: HashMap<IS, u64>,
+    instruction_count: u64,
+    cycle_count: u64,
+    /// Number of memory operands read and written by executed instructions
+    memory_reads: u64,
+    memory_writes: u64,
+    /// Exit code passed to the exit syscall, set once the program has exited
+    exit_code: Option<u8>,
+    /// Shadow state of the opt-in uninitialized read tracking
+    uninitialized: Option<UninitializedTracker>,
+    /// Warnings raised while running the program
+    warnings: Vec<Warning>,
+    /// Last writers of registers and memory, recorded when enabled
+    writers: Option<WriterJournal>,
+    last_memory_access: Option<MemoryAccess>,
+    /// Memory accesses of the run, recorded when enabled
+    access_log: Option<AccessLog>,
+    /// Instructions executed per call chain, recorded when enabled
+    profile: Option<Profile>,
+    /// Whether AssertReg/AssertFlag pseudo-instructions are checked
+    guest_assertions: bool,
+    verbosity: Verbosity,
+    /// Index of the instruction the program starts at
+    entry: usize,
+    /// Index of the instruction fetched last
+    last_fetched: Option<usize>,
+    /// Indices of the last instructions fetched, oldest first, for core dumps
+    trace_tail: VecDeque<usize>,
+    /// Instructions executed between checks of the elapsed time by `run_with_timeout`
+    timeout_check_interval: u64,
+    /// The environment block, `KEY=VALUE` pairs each followed by a 0 byte
+    environment: Vec<u8>,
+    /// Offset of the environment block in main memory
+    environment_offset: u32,
+    /// Whether trace output is colored
+    color: bool,
+    /// Where the trace output is written
+    output: TraceOutput,
+    /// Bytes of memory shown by hexdumps and fault reports before the rest is elided
+    dump_limit: usize,
+    /// Clock timing runs and their timeouts
+    clock: Box<dyn Clock>,
+    /// Where the write syscalls write to
+    guest_output: GuestOutput,
+    /// Where the read syscall reads from
+    guest_input: GuestInput,
+    /// Instructions a run may execute before it is stopped
+    instruction_limit: Option<u64>,
+    /// Return addresses of the active calls, innermost last
+    call_stack: Vec<usize>,
+    /// Stack in main memory holding the return addresses of the guest, if one is reserved
+    stack: Option<Stack>,
+    /// Messages logged by the guest through the log syscall
+    guest_logs: Vec<GuestLog>,
+    /// Whether the instruction executing is the yield syscall
+    yielded: bool,
+    /// Code of the abort syscall the executing instruction made
+    aborted: Option<u8>,
+    /// Whether the instruction executing is a RET to `RETURN_TO_HOST`
+    returned_to_host: bool,
+    /// Host code run before the instruction at each index
+    hooks: HashMap<usize, Hook>,
+    /// Indices of the instructions `run_debug` stops before
+    breakpoints: BTreeSet<usize>,
+    /// Register values `run_debug` stops at
+    register_conditions: Vec<(Register, Condition)>,
+    /// Expressions `run_debug` stops at when they become true, with their text
+    expression_conditions: Vec<(String, Expr)>,
+    /// Expressions whose changes `run_debug` reports to the observers
+    watches: Vec<Watch>,
+    /// Names of instruction indices, set with `CpuBuilder::code_label`
+    code_labels: HashMap<String, usize>,
+    /// Receivers of the events of executed instructions
+    observers: Observers,
+    /// Index of the instruction being executed while observers are installed
+    executing: Option<usize>,
+    /// Whether observers receive the stage events, see `CpuBuilder::pipeline_view`
+    pipeline_view: bool,
+    /// Semantics of the instructions that differ between x86 and teaching, see `Semantics`
+    semantics: Semantics,
+    /// Operands read by the executing instruction and their values, reported by `Event::Decoded`
+    stage_reads: Vec<(String, u32)>,
+    /// Destinations written by the executing instruction and their new values, reported by `Event::Executed`
+    stage_writes: Vec<(String, u32)>,
+}
+
+impl CPU {
+    pub fn new(data_section: HashMap<String, Data>, code_section: Vec<Instruction>)-> Result<CPU, BuildError> {
+        CpuBuilder::new(data_section, code_section).build()
+    }
+
+    #[allow(dead_code)]
+    fn preview_flags(&self){
+        println!("Flags:");
+        self.flags.iter().for_each(|flag| {
+            println!("{:?}", flag);
+        });
+    }
+
+    /// Disassembles the code section, one instruction per line prefixed by its index.
+    ///
+    /// Operands referring to data labels are annotated with the label's offset and width,
+    /// and jumps with the index of their target.
+    pub fn disassemble(&self, options: &DisasmOptions) -> String {
+        let mut output = String::new();
+        for (index, instruction) in self.memory_unit.code_section.iter().enumerate() {
+            let mut annotations = Vec::new();
+            for operand in &instruction.operands {
+                match operand {
+                    Operand::Memory(MemOp::Ptr(_, label, _)) | Operand::Memory(MemOp::Label(label)) |
+                    Operand::Memory(MemOp::Address(label)) | Operand::Memory(MemOp::Symbol(_, label)) |
+                    Operand::LabelOffset(label) => {
+                        let Some(symbol) = self.memory_unit.symbol(label) else {
+                            annotations.push(format!("{} undeclared", label));
+                            continue;
+                        };
+                        let mut annotation = format!("{} @ {:#06X}, {}", label, symbol.offset, format!("{:?}", symbol.width).to_lowercase());
+                        if options.current_values && let Ok(value) = self.memory_unit.read_value(symbol) {
+                            annotation += &format!(", = {} (current)", format_value(value, symbol.width, Base::Unsigned));
+                        }
+                        annotations.push(annotation);
+                    },
+                    Operand::JmpRel(offset) if instruction.opcode.is_jump() => {
+                        annotations.push(format!("-> {}", index as i64 + 1 + *offset as i64));
+                    },
+                    Operand::Immediate(target) if instruction.opcode.is_jump() => {
+                        annotations.push(format!("-> {}", target.get_value()));
+                    },
+                    _ => {},
+                }
+            }
+
+            let text = instruction.to_string();
+            match annotations.is_empty() {
+                true => output += &format!("{:04}  {}\n", index, text),
+                false => output += &format!("{:04}  {:<24} ; {}\n", index, text, annotations.join("; ")),
+            }
+        }
+        output
+    }
+
+    /// Renders `length` bytes of main memory from `offset` in hex, capped at the dump limit set on the builder
+    pub fn hexdump(&self, offset: usize, length: usize) -> Result<String, CpuError> {
+        Ok(format_bytes(&self.memory_unit.read_bytes(offset, length)?, self.dump_limit))
+    }
+
+    /// Reads the current value of a data section label at its declared width.
+    pub fn read_label(&self, label: &str) -> Result<Data, CpuError> {
+        match self.memory_unit.symbol(label) {
+            Some(symbol) => Ok(Data::from_u32(symbol.width, self.memory_unit.read_value(symbol)?)),
+            None => Err(self.undeclared_label(label)),
+        }
+    }
+
+    /// Exports a range of main memory as an Intel HEX image, see `import_ihex` to load it back
+    pub fn export_ihex(&self, range: std::ops::Range<usize>) -> Result<String, IhexError> {
+        self.memory_unit.export_ihex(range)
+    }
+
+    /// Writes the records of an Intel HEX image to main memory, growing it up to its capacity when needed.
+    ///
+    /// Records before an invalid one are already written when the error is returned.
+    pub fn import_ihex(&mut self, image: &str) -> Result<(), IhexError> {
+        self.memory_unit.import_ihex(image)
+    }
+
+    /// The value a data section label was declared with, whatever the program has written to it since
+    pub fn declaration(&self, label: &str) -> Option<&Data> {
+        self.memory_unit.declaration(label)
+    }
+
+    /// The symbol table the labels of the program were interned in when it was loaded
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.memory_unit.symbols
+    }
+
+    /// Where a data section label's value lives in main memory
+    pub fn symbol(&self, label: &str) -> Option<&Symbol> {
+        self.memory_unit.symbol(label)
+    }
+
+    /// Writes a value to a data section label.
+    ///
+    /// The data must have the width the label was declared with.
+    pub fn write_label(&mut self, label: &str, data: Data) -> Result<(), CpuError> {
+        let symbol = match self.memory_unit.symbol(label) {
+            Some(symbol) => symbol.clone(),
+            None => return Err(self.undeclared_label(label)),
+        };
+        if symbol.width.bytes() != data.width().bytes() {
+            return Err(CpuError::WidthMismatch(label.to_string(), data.width()));
+        }
+        self.memory_unit.write_value(&symbol, data.get_value())?;
+        // Written by the host rather than an instruction
+        self.mark_memory_written(symbol.offset as usize, symbol.width.bytes());
+        if let Some(journal) = self.writers.as_mut() {
+            journal.record_memory(symbol.offset as usize, symbol.width.bytes(), None);
+        }
+        Ok(())
+    }
+
+    /// Enables tracking of reads from registers and memory that were never written.
+    ///
+    /// General purpose registers start uninitialized, as does memory below the data base.
+    /// The data section is initialized. Tracking should be enabled before the program runs.
+    pub fn track_uninitialized(&mut self, policy: UninitializedPolicy) {
+        let data_base = self.memory_unit.data_base as usize;
+        self.uninitialized = Some(UninitializedTracker {
+            policy,
+            registers: [false; 8],
+            memory: (0..self.memory_unit.get_data_len()).map(|offset| offset >= data_base).collect(),
+        });
+    }
+
+    /// Reports an uninitialized read according to the tracking policy
+    fn uninitialized_read(&mut self, warning: Warning) -> Result<(), CpuError> {
+        match self.uninitialized.as_ref().map(|tracker| tracker.policy) {
+            Some(UninitializedPolicy::Error) => Err(CpuError::UninitializedRead(warning)),
+            _ => {
+                self.warn(warning);
+                Ok(())
+            },
+        }
+    }
+
+    /// Records a warning raised while running, tracing it and reporting it to the observers
+    fn warn(&mut self, warning: Warning) {
+        if self.verbosity > Verbosity::Quiet {
+            self.trace(format_args!("{}", paint(&format!("Warning: {:?}", warning), Color::Yellow, self.color)));
+        }
+        self.emit(Event::Warning(warning.clone()));
+        self.warnings.push(warning);
+    }
+
+    /// Index of the instruction being executed, IP has already moved past it
+    fn current_instruction(&self) -> usize {
+        (self.registers.SP[2].get_value() as usize).saturating_sub(1)
+    }
+
+    fn check_register_read(&mut self, register: &Register) -> Result<(), CpuError> {
+        let Some(tracker) = self.uninitialized.as_mut() else {
+            return Ok(());
+        };
+        if tracker.registers[register.index()] {
+            return Ok(());
+        }
+        // Only report the register once
+        tracker.registers[register.index()] = true;
+        self.uninitialized_read(Warning::UninitializedRegister(self.current_instruction(), register.clone()))
+    }
+
+    fn record_memory_access(&mut self, access: MemoryAccess) {
+        self.last_memory_access = Some(access);
+        if let Some(log) = self.access_log.as_mut() {
+            match log.accesses.len() < log.limit {
+                true => log.accesses.push(access),
+                false => log.dropped += 1,
+            }
+        }
+    }
+
+    fn check_memory_read(&mut self, offset: usize, length: usize) -> Result<(), CpuError> {
+        self.record_memory_access(MemoryAccess { offset, length, write: false });
+        let Some(tracker) = self.uninitialized.as_mut() else {
+            return Ok(());
+        };
+        let end = offset.saturating_add(length).min(tracker.memory.len());
+        let Some(uninitialized) = (offset..end).find(|offset| !tracker.memory[*offset]) else {
+            return Ok(());
+        };
+        // Only report the bytes once
+        tracker.memory[offset..end].iter_mut().for_each(|byte| *byte = true);
+        self.uninitialized_read(Warning::UninitializedMemory(self.current_instruction(), uninitialized))
+    }
+
+    fn mark_register_written(&mut self, register: &Register) {
+        if let Some(tracker) = self.uninitialized.as_mut() {
+            tracker.registers[register.index()] = true;
+        }
+        let instruction = self.current_instruction();
+        if let Some(journal) = self.writers.as_mut() {
+            journal.registers[register.index()] = Some(instruction);
+        }
+    }
+
+    fn mark_memory_written(&mut self, offset: usize, length: usize) {
+        self.record_memory_access(MemoryAccess { offset, length, write: true });
+        if let Some(tracker) = self.uninitialized.as_mut() {
+            let end = offset.saturating_add(length).min(tracker.memory.len());
+            tracker.memory[offset.min(end)..end].iter_mut().for_each(|byte| *byte = true);
+        }
+        let instruction = self.current_instruction();
+        if let Some(journal) = self.writers.as_mut() {
+            journal.record_memory(offset, length, Some(instruction));
+        }
+        if self.executing.is_some() {
+            let bytes = self.memory_unit.read_bytes(offset, length).unwrap_or_default();
+            self.emit(Event::MemoryWrite(offset, bytes));
+        }
+    }
+
+    /// Reports an event of the executing instruction to the observers
+    fn emit(&mut self, event: Event) {
+        if let Some(index) = self.executing {
+            let context = ExecContext {
+                ip: index,
+                instruction: &self.memory_unit.code_section[index],
+                step_number: self.instruction_count,
+            };
+            for observer in self.observers.0.iter_mut() {
+                observer.event(&context, &event);
+            }
+        }
+    }
+
+    /// Enables recording of the memory accesses of the run, keeping the first `limit` of them.
+    pub fn record_accesses(&mut self, limit: usize) {
+        self.access_log = Some(AccessLog { limit, accesses: Vec::new(), dropped: 0 });
+    }
+
+    /// Messages logged by the guest so far, in the order they were logged
+    pub fn guest_logs(&self) -> &[GuestLog] {
+        &self.guest_logs
+    }
+
+    /// Memory accesses recorded so far, empty unless recording is enabled
+    pub fn access_log(&self) -> &[MemoryAccess] {
+        self.access_log.as_ref().map_or(&[], |log| &log.accesses)
+    }
+
+    /// Number of recorded accesses touching each data label, sorted by label
+    pub fn access_counts(&self) -> Vec<(String, usize)> {
+        self.memory_unit.symbols.defined().into_iter()
+            .map(|(label, symbol)| {
+                let (start, end) = (symbol.offset as usize, symbol.offset as usize + symbol.width.bytes());
+                let count = self.access_log().iter()
+                    .filter(|access| access.offset < end && access.offset + access.length > start)
+                    .count();
+                (label.clone(), count)
+            })
+            .collect()
+    }
+
+    /// Enables counting the instructions executed under each chain of active calls, see `export_folded_stacks`.
+    ///
+    /// Calls already active when profiling starts aren't part of the chains.
+    pub fn enable_profiling(&mut self) {
+        self.profile = Some(Profile::default());
+    }
+
+    /// Instructions executed under each call chain in the folded stacks format of flame graph tools,
+    /// e.g. `main;int_to_str 25`, one chain per line. Empty unless profiling was enabled.
+    ///
+    /// Frames are named by the code label of the called instruction, or `sub_<index>` without one, and the
+    /// outermost frame by the code label of the entry point, or `main`.
+    pub fn export_folded_stacks(&self) -> String {
+        let Some(profile) = &self.profile else {
+            return String::new();
+        };
+        let name = |index: usize| {
+            self.code_labels.iter()
+                .filter(|(_, labelled)| **labelled == index)
+                .map(|(label, _)| label.as_str())
+                .min()
+                .map(str::to_string)
+        };
+        let root = name(self.entry).unwrap_or_else(|| "main".to_string());
+        let mut folded = String::new();
+        for (frames, count) in &profile.counts {
+            let chain: Vec<String> = std::iter::once(root.clone())
+                .chain(frames.iter().map(|index| name(*index).unwrap_or_else(|| format!("sub_{}", index))))
+                .collect();
+            folded += &format!("{} {}\n", chain.join(";"), count);
+        }
+        folded
+    }
+
+    /// Enables recording of the instruction that last wrote each register and memory byte.
+    pub fn track_writers(&mut self) {
+        self.writers = Some(WriterJournal {
+            registers: [None; 8],
+            memory: Vec::new(),
+        });
+    }
+
+    /// Index of the instruction that last wrote the register, if writers are tracked and it has been written
+    pub fn last_writer_of_register(&self, register: Register) -> Option<usize> {
+        self.writers.as_ref()?.registers[register.index()]
+    }
+
+    /// Index of the instruction that last wrote the memory byte at the offset,
+    /// if writers are tracked and an instruction has written it
+    pub fn last_writer_of_address(&self, offset: usize) -> Option<usize> {
+        *self.writers.as_ref()?.memory.get(offset)?
+    }
+
+    /// Runs `hook` each time the instruction at `index` is about to execute, replacing any hook already there.
+    ///
+    /// The hook may change registers and labels to inject inputs or stand in for guest code, and its
+    /// `HookAction` decides whether the instruction then runs, is skipped, returns from its subroutine,
+    /// or the run ends. Skipping or returning doesn't count the instruction in the instruction or cycle counts.
+    ///
+    /// A subroutine is stubbed out by hooking either its first instruction with `HookAction::Return`,
+    /// which stands in for it at every CALL, or a single CALL with `HookAction::SkipInstruction`,
+    /// which never enters it. Either way the hook sets the registers the subroutine would have returned.
+    pub fn hook_before(&mut self, index: usize, hook: impl FnMut(&mut CpuView) -> HookAction + 'static) {
+        self.hooks.insert(index, Hook(Box::new(hook)));
+    }
+
+    /// The instructions of the code section with their indices, in program order
+    pub fn code(&self) -> impl Iterator<Item = (usize, &Instruction)> {
+        self.memory_unit.code_section.iter().enumerate()
+    }
+
+    /// Number of instructions in the code section
+    pub fn code_len(&self) -> usize {
+        self.memory_unit.code_section.len()
+    }
+
+    /// The instruction at `index`, or `None` past the end of the code section
+    pub fn instruction_at(&self, index: usize) -> Option<&Instruction> {
+        self.memory_unit.code_section.get(index)
+    }
+
+    /// Indices of the instructions matching `predicate`, e.g.
+    /// `cpu.find_instructions(|i| matches!(i.opcode(), IS::Syscall))`
+    pub fn find_instructions(&self, predicate: impl Fn(&Instruction) -> bool) -> Vec<usize> {
+        self.code().filter(|(_, instruction)| predicate(instruction)).map(|(index, _)| index).collect()
+    }
+
+    /// Instructions this CPU can execute
+    pub fn supported_instructions(&self) -> Vec<IS> {
+        IS::all().iter().filter(|opcode| opcode.info().supported).cloned().collect()
+    }
+
+    /// Overrides the cycle cost of an instruction in the built-in cost table.
+    pub fn set_cost(&mut self, opcode: IS, cycles: u64) {
+        self.costs.insert(opcode, cycles);
+    }
+
+    /// Cycle cost of an instruction: the cost of its opcode plus the cost of each memory operand.
+    fn instruction_cost(&self, instruction: &Instruction) -> u64 {
+        let opcode_cost = match self.costs.get(&instruction.opcode) {
+            Some(cycles) => *cycles,
+            None => instruction.opcode.default_cost(),
+        };
+        // A label operand evaluates to the label's offset and doesn't access memory
+        let memory_operands = instruction.operands.iter()
+            .filter(|operand| matches!(operand, Operand::Memory(operand) if !matches!(operand, MemOp::Label(_))))
+            .count() as u64;
+        opcode_cost + memory_operands * MEMORY_OPERAND_COST
+    }
+
+    /// Runs the program until it exits or IP moves past the last instruction.
+    ///
+    /// Stops at the first instruction that fails, returning the error along with the state of the machine.
+    pub fn run(&mut self) -> Result<RunOutcome, Fault> {
+        self.run_for(None)
+    }
+
+    /// Runs the program like `run`, but stops with a `Timeout` error once it has run for longer than `timeout`.
+    ///
+    /// The elapsed time is read from the builder's clock, and only every `timeout_check_interval` instructions
+    /// to keep the overhead low.
+    pub fn run_with_timeout(&mut self, timeout: Duration) -> Result<RunOutcome, Fault> {
+        self.run_for(Some(timeout))
+    }
+
+    fn run_for(&mut self, timeout: Option<Duration>) -> Result<RunOutcome, Fault> {
+        let start = self.clock.now();
+        let first_instruction = self.instruction_count;
+        let static_warnings = !self.validate().is_empty();
+        if self.memory_unit.code_section.len() == 0 {
+            self.narrate(format_args!("Program is empty"));
+        } else {
+            while matches!(self.step()?, StopReason::Stepped | StopReason::Yielded) {
+                if let Err(error) = self.check_instruction_limit(first_instruction) {
+                    return Err(self.fault(error));
+                }
+                if let Some(timeout) = timeout
+                    && (self.instruction_count - first_instruction).is_multiple_of(self.timeout_check_interval)
+                    && self.clock.now().saturating_sub(start) >= timeout {
+                    return Err(self.fault(CpuError::Timeout(self.instruction_count - first_instruction)));
+                }
+            }
+        }
+        if self.verbosity >= Verbosity::Result {
+            let registers: Vec<String> = Register::all().iter()
+                .zip(self.register_values())
+                .map(|(register, value)| format!("{:?}={}", register, format_value(value, register.width(), Base::Hex)))
+                .collect();
+            self.trace(format_args!("Registers: {}", registers.join(" ")));
+            self.trace(format_args!("Exit code: {}", self.exit_code.unwrap_or(0)));
+        }
+        Ok(self.outcome(start, static_warnings))
+    }
+
+    /// Summarizes a run that started when the clock read `start`
+    fn outcome(&self, start: Duration, static_warnings: bool) -> RunOutcome {
+        RunOutcome {
+            // Running past the last instruction is a successful exit
+            exit_code: self.exit_code.unwrap_or(0),
+            instructions: self.instruction_count,
+            cycles: self.cycle_count,
+            duration: self.clock.now().saturating_sub(start),
+            warnings: static_warnings || !self.warnings.is_empty(),
+        }
+    }
+
+    /// Executes the next instruction, after running its hook if it has one.
+    ///
+    /// Once the program has exited or ran past its last instruction, nothing is executed and the exit is reported again.
+    /// Unlike `run`, a step is never stopped by the instruction limit or a timeout.
+    pub fn step(&mut self) -> Result<StopReason, Fault> {
+        if let Some(reason) = self.finished() {
+            return Ok(reason);
+        }
+        let ip = self.registers.SP[2].get_value() as usize;
+        if let Some(mut hook) = self.hooks.remove(&ip) {
+            let action = (hook.0)(&mut CpuView { cpu: self });
+            self.hooks.insert(ip, hook);
+            match action {
+                HookAction::Continue => {},
+                HookAction::SkipInstruction => {
+                    self.registers.SP[2].set_value(Data::Word((ip + 1) as u16));
+                    return Ok(self.finished().unwrap_or(StopReason::Stepped));
+                },
+                HookAction::Return => {
+                    if let Err(error) = self.decode(Instruction::new(IS::Ret, vec![])) {
+                        return Err(self.fault(error));
+                    }
+                    if std::mem::take(&mut self.returned_to_host) {
+                        return Ok(StopReason::ReturnedToHost);
+                    }
+                    return Ok(self.finished().unwrap_or(StopReason::Stepped));
+                },
+                HookAction::Stop => return Ok(StopReason::Hook(ip)),
+            }
+        }
+        if let Err(error) = self.fetch() {
+            return Err(self.fault(error));
+        }
+        if std::mem::take(&mut self.returned_to_host) {
+            return Ok(StopReason::ReturnedToHost);
+        }
+        let stepped = if std::mem::take(&mut self.yielded) { StopReason::Yielded } else { StopReason::Stepped };
+        Ok(self.finished().unwrap_or(stepped))
+    }
+
+    /// Sets a breakpoint on every syscall instruction, returning their indices
+    pub fn break_on_syscalls(&mut self) -> Vec<usize> {
+        let syscalls = self.find_instructions(|instruction| matches!(instruction.opcode, IS::Syscall));
+        self.breakpoints.extend(&syscalls);
+        syscalls
+    }
+
+    /// Sets a breakpoint on the instruction at the index, stopping `run_debug` before it executes
+    pub fn add_breakpoint(&mut self, index: usize) {
+        self.breakpoints.insert(index);
+    }
+
+    /// Removes the breakpoint of the instruction at the index, returning whether it had one
+    pub fn remove_breakpoint(&mut self, index: usize) -> bool {
+        self.breakpoints.remove(&index)
+    }
+
+    /// Stops `run_debug` after an instruction makes the register meet the condition, e.g. CX becoming 0.
+    ///
+    /// The condition is checked after each instruction and stays set, see `clear_conditions`.
+    pub fn break_when(&mut self, register: Register, condition: Condition) {
+        self.register_conditions.push((register, condition));
+    }
+
+    /// Stops `run_debug` after an instruction makes the expression true, e.g. `[count] == 3 && zf`.
+    ///
+    /// Like the conditions of `break_when`, it stops when the expression becomes true, not while it stays
+    /// true, and stays set until `clear_conditions`. Fails if the expression doesn't parse or reads an
+    /// undeclared label.
+    pub fn break_when_expr(&mut self, expression: &str) -> Result<(), String> {
+        let expr: Expr = expression.parse()?;
+        expr.evaluate(self).map_err(|error| format!("{:?}", error))?;
+        self.expression_conditions.push((expression.to_string(), expr));
+        Ok(())
+    }
+
+    /// Removes every condition set with `break_when` and `break_when_expr`
+    pub fn clear_conditions(&mut self) {
+        self.register_conditions.clear();
+        self.expression_conditions.clear();
+    }
+
+    /// Watches the expression during `run_debug`, reporting every change of its value to the observers
+    /// with `Event::WatchChanged`. Fails if the expression doesn't parse or reads an undeclared label.
+    pub fn add_watch(&mut self, expression: &str) -> Result<(), String> {
+        let expr: Expr = expression.parse()?;
+        let value = expr.evaluate(self).map_err(|error| format!("{:?}", error))?;
+        self.watches.push(Watch { expression: expression.to_string(), expr, value });
+        Ok(())
+    }
+
+    /// Evaluates the expression on the current state, as a debugger's `print` command would
+    pub fn evaluate(&self, expression: &str) -> Result<i64, String> {
+        let expr: Expr = expression.parse()?;
+        expr.evaluate(self).map_err(|error| format!("{:?}", error))
+    }
+
+    /// Reports the watches whose value changed with the instruction that just executed
+    fn update_watches(&mut self) -> Result<(), CpuError> {
+        let mut changes = Vec::new();
+        for (index, watch) in self.watches.iter().enumerate() {
+            let value = watch.expr.evaluate(self)?;
+            if value != watch.value {
+                changes.push((index, value));
+            }
+        }
+        // The events come from the instruction that just executed
+        let executing = std::mem::replace(&mut self.executing, self.last_fetched);
+        for (index, value) in changes {
+            let old = std::mem::replace(&mut self.watches[index].value, value);
+            self.emit(Event::WatchChanged(self.watches[index].expression.clone(), old, value));
+        }
+        self.executing = executing;
+        Ok(())
+    }
+
+    /// Index of the instruction named by the code label
+    pub fn code_label(&self, name: &str) -> Option<usize> {
+        self.code_labels.get(name).copied()
+    }
+
+    /// Runs the program like `run_debug` until it reaches the instruction at the index.
+    ///
+    /// The stop is a one-shot breakpoint: it is removed once `run_debug` returns, whether it fired or the
+    /// program stopped for another reason, so continuing doesn't stop there again. A breakpoint already
+    /// set on the instruction is kept.
+    pub fn run_until_ip(&mut self, index: usize) -> Result<StopReason, Fault> {
+        let one_shot = self.breakpoints.insert(index);
+        let reason = self.run_debug();
+        if one_shot {
+            self.breakpoints.remove(&index);
+        }
+        reason
+    }
+
+    /// Runs the program like `run_until_ip` until it reaches the instruction named by the code label.
+    ///
+    /// Fails with `CpuError::UndeclaredLabel` without running anything if there is no such code label.
+    pub fn run_until_label(&mut self, name: &str) -> Result<StopReason, Fault> {
+        match self.code_label(name) {
+            Some(index) => self.run_until_ip(index),
+            None => {
+                let suggestion = closest_match(name, self.code_labels.keys().map(String::as_str)).map(str::to_string);
+                Err(self.fault(CpuError::UndeclaredLabel(name.to_string(), suggestion)))
+            },
+        }
+    }
+
+    /// Calls the guest routine at a code label like a function, leaving the rest of the program alone.
+    ///
+    /// The calling convention is the guest's own: the arguments are set in their registers, the host's
+    /// return address is pushed like a CALL would and the routine runs until its RET returns to the host.
+    /// The result is in whichever registers the routine leaves it, and memory keeps what the routine
+    /// wrote so the next call sees it. IP and the exit code are restored afterwards, whether the call
+    /// returned or not, so the program can go on or the routine be called again.
+    ///
+    /// A routine that stops before returning, by exiting or being stopped by a hook, fails with
+    /// `GuestCallEnded`. The instruction limit applies to the instructions of each call.
+    pub fn call_guest(&mut self, label: &str, args: &[Arg]) -> Result<GuestReturn, Fault> {
+        let Some(target) = self.code_label(label) else {
+            let suggestion = closest_match(label, self.code_labels.keys().map(String::as_str)).map(str::to_string);
+            return Err(self.fault(CpuError::UndeclaredLabel(label.to_string(), suggestion)));
+        };
+        let (ip, exit_code, depth) = (self.ip(), self.exit_code.take(), self.call_stack.len());
+        let frames = self.profile.as_ref().map_or(0, |profile| profile.frames.len());
+        let first_instruction = self.instruction_count;
+        let result = self.call_at(target, args, first_instruction);
+        self.registers.SP[2].set_value(Data::Word(ip as u16));
+        self.exit_code = exit_code;
+        self.call_stack.truncate(depth);
+        if let Some(profile) = &mut self.profile {
+            profile.frames.truncate(frames);
+        }
+        result.map(|()| GuestReturn {
+            registers: self.register_values(),
+            instructions: self.instruction_count - first_instruction,
+        })
+    }
+
+    /// Sets the arguments, pushes the host's return address and steps from the target until it returns to the host
+    fn call_at(&mut self, target: usize, args: &[Arg], first_instruction: u64) -> Result<(), Fault> {
+        for Arg::Reg(register, value) in args {
+            self.registers.get_register(register.clone()).set_value(Data::from_u32(register.width(), *value));
+            self.mark_register_written(register);
+        }
+        if let Err(error) = self.enter_from_host(target) {
+            return Err(self.fault(error));
+        }
+        loop {
+            match self.step()? {
+                StopReason::ReturnedToHost => return Ok(()),
+                StopReason::Stepped | StopReason::Yielded => {},
+                reason => return Err(self.fault(CpuError::GuestCallEnded(reason))),
+            }
+            if let Err(error) = self.check_instruction_limit(first_instruction) {
+                return Err(self.fault(error));
+            }
+        }
+    }
+
+    /// Calls the target from the host like a CALL would, pushing `RETURN_TO_HOST` as the return address
+    fn enter_from_host(&mut self, target: usize) -> Result<(), CpuError> {
+        if let Some(stack) = self.stack {
+            self.push_word(u16::MAX)?;
+            if stack.canaries {
+                self.push_word(STACK_CANARY)?;
+            }
+        }
+        self.call_stack.push(RETURN_TO_HOST);
+        if let Some(profile) = &mut self.profile {
+            profile.frames.push(target);
+        }
+        self.registers.SP[2].set_value(Data::Word(target as u16));
+        Ok(())
+    }
+
+    /// Steps the program until it stops for any other reason than having executed an instruction.
+    ///
+    /// The instruction at IP executes even if it has a breakpoint, so calling `run_debug` again after
+    /// a breakpoint resumes the program. The instruction limit applies to the instructions of each call.
+    pub fn run_debug(&mut self) -> Result<StopReason, Fault> {
+        self.debug_loop(None)
+    }
+
+    /// Runs the program like `run_debug`, executing `ips` instructions per second of the clock.
+    ///
+    /// The stop flag is checked before every instruction, returning `StopReason::Paused` once it is set,
+    /// so another thread can pause the program.
+    pub fn run_throttled(&mut self, ips: u32, stop: &AtomicBool) -> Result<StopReason, Fault> {
+        self.debug_loop(Some((ips, stop)))
+    }
+
+    /// Steps the program until it stops, pacing it when throttled
+    fn debug_loop(&mut self, throttle: Option<(u32, &AtomicBool)>) -> Result<StopReason, Fault> {
+        let start = self.clock.now();
+        let first_instruction = self.instruction_count;
+        let mut resuming = true;
+        loop {
+            if let Some((_, stop)) = throttle
+                && stop.load(Ordering::Relaxed) {
+                return Ok(StopReason::Paused);
+            }
+            let ip = self.registers.SP[2].get_value() as usize;
+            if !resuming && self.breakpoints.contains(&ip) {
+                return Ok(StopReason::Breakpoint(ip));
+            }
+            resuming = false;
+
+            let before = self.register_values();
+            let mut held = Vec::new();
+            for (_, expr) in &self.expression_conditions {
+                match expr.evaluate(self) {
+                    Ok(value) => held.push(value != 0),
+                    Err(error) => return Err(self.fault(error)),
+                }
+            }
+            let reason = self.step()?;
+            if let Some((ips, _)) = throttle {
+                // Each instruction takes an interval of 1/ips seconds from the start of the run
+                let due = start + Duration::from_secs(self.instruction_count - first_instruction) / ips.max(1);
+                let now = self.clock.now();
+                if due > now {
+                    self.clock.sleep(due - now);
+                }
+            }
+            if !matches!(reason, StopReason::Stepped | StopReason::Yielded) {
+                return Ok(reason);
+            }
+            for (register, condition) in &self.register_conditions {
+                let (old, new) = (before[register.index()], self.register(register.clone()));
+                if condition.met(old, new) {
+                    return Ok(StopReason::RegisterCondition(register.clone(), old, new));
+                }
+            }
+            if let Err(error) = self.update_watches() {
+                return Err(self.fault(error));
+            }
+            for ((expression, expr), held) in self.expression_conditions.iter().zip(held) {
+                match expr.evaluate(self) {
+                    Ok(value) if value != 0 && !held => return Ok(StopReason::ExpressionCondition(expression.clone())),
+                    Ok(_) => {},
+                    Err(error) => return Err(self.fault(error)),
+                }
+            }
+            if let Err(error) = self.check_instruction_limit(first_instruction) {
+                return Err(self.fault(error));
+            }
+        }
+    }
+
+    /// How the program exited, if it can't run any further
+    fn finished(&self) -> Option<StopReason> {
+        match self.exit_code {
+            Some(code) => Some(StopReason::Exited(code)),
+            // Only running right past the last instruction ends the program, IP beyond it is caught by fetch
+            None if self.registers.SP[2].get_value() == self.memory_unit.code_section.len() as u32 => Some(StopReason::Exited(0)),
+            None => None,
+        }
+    }
+
+    /// Fails once a run that started at `first_instruction` has executed as many instructions as the limit allows
+    fn check_instruction_limit(&self, first_instruction: u64) -> Result<(), CpuError> {
+        match self.instruction_limit {
+            Some(limit) if self.instruction_count - first_instruction >= limit => Err(CpuError::InstructionLimit(limit)),
+            _ => Ok(()),
+        }
+    }
+
+    /// The last operation executed by the ALU, if any
+    pub fn alu_trace(&self) -> Option<&AluTrace> {
+        self.alu.trace.as_ref()
+    }
+
+    /// Renders every general purpose register, one per line, in each of the bases of the options
+    pub fn format_registers(&self, options: &RegisterFormat) -> String {
+        let mut output = String::new();
+        for (register, value) in Register::all().iter().zip(self.register_values()) {
+            let values: Vec<String> = options.bases.iter()
+                .map(|base| format_value(value, register.width(), *base))
+                .collect();
+            output += &format!("{:<3} = {}\n", format!("{:?}", register), values.join("  "));
+        }
+        output
+    }
+
+    /// Values of the general purpose registers, in the order AX, BX, CX, DX, EAX, EBX, ECX, EDX
+    pub fn register_values(&self) -> Vec<u32> {
+        self.registers.GP.iter().map(|register| register.get_value()).collect()
+    }
+
+    /// Index of the instruction the program executes next
+    pub fn ip(&self) -> usize {
+        self.registers.SP[2].get_value() as usize
+    }
+
+    /// Current value of a general purpose register
+    pub fn register(&self, register: Register) -> u32 {
+        self.registers.GP[register.index()].get_value()
+    }
+
+    /// Number of memory operands read by the instructions executed so far
+    pub fn memory_reads(&self) -> u64 {
+        self.memory_reads
+    }
+
+    /// Number of memory operands written by the instructions executed so far
+    pub fn memory_writes(&self) -> u64 {
+        self.memory_writes
+    }
+
+    /// Warnings raised while running the program, e.g. by uninitialized tracking
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// The flags packed into a word, bit `n` holding the flag at index `n` of the flags array
+    fn flags_word(&self) -> u16 {
+        let mut flags_word = 0;
+        for (index, flag) in self.flags.iter().enumerate() {
+            flags_word |= (flag.get_value() as u16 & 1) << index;
+        }
+        flags_word
+    }
+
+    /// Captures the registers, flags and main memory, to be compared with a later snapshot.
+    pub fn snapshot(&self) -> CpuSnapshot {
+        CpuSnapshot {
+            registers: self.register_values(),
+            flags_word: self.flags_word(),
+            memory: self.memory_unit.data_bus.data.clone(),
+        }
+    }
+
+    /// Captures the state of the machine at a fault for post-mortem inspection, see `CoreDump`.
+    ///
+    /// Nothing is executed, so it is safe to call on the failure path of a run.
+    pub fn core_dump(&self, fault: &Fault) -> CoreDump {
+        let symbols = self.memory_unit.symbols.defined().into_iter()
+            .map(|(label, symbol)| (label.clone(), symbol.clone()))
+            .collect();
+        let trace = self.trace_tail.iter()
+            .map(|index| format!("{:04}  {}", index, self.memory_unit.code_section[*index]))
+            .collect();
+        let disassembly = self.disassemble(&DisasmOptions { current_values: true });
+        CoreDump {
+            error: format!("{:?}", fault.error),
+            ip: fault.context.ip,
+            state: self.machine_state(),
+            symbols,
+            trace,
+            disassembly: disassembly.lines().map(str::to_string).collect(),
+            memory: self.memory_unit.data_bus.data.clone(),
+        }
+    }
+
+    /// Captures the registers, flags and a digest of main memory
+    pub fn machine_state(&self) -> MachineState {
+        MachineState {
+            registers: Register::all().into_iter().map(|register| {
+                let value = self.registers.GP[register.index()].get_value();
+                (register, value)
+            }).collect(),
+            flags: self.flags_word(),
+            sp: self.registers.SP[0].get_value(),
+            bp: self.registers.SP[1].get_value(),
+            ip: self.registers.SP[2].get_value(),
+            memory_hash: memory_hash(&self.memory_unit.data_bus.data),
+        }
+    }
+
+    /// Prints a step of the run's narrative, shown at `Verbosity::Full` only
+    fn narrate(&self, message: std::fmt::Arguments) {
+        if self.verbosity == Verbosity::Full {
+            self.trace(message);
+        }
+    }
+
+    /// Writes a line of trace output, a failing sink doesn't stop the run
+    fn trace(&self, message: std::fmt::Arguments) {
+        let _ = writeln!(self.output.0.borrow_mut(), "{}", message);
+    }
+
+    /// Captures the state of the machine at the instruction that raised the error.
+    ///
+    /// When fetch fails, that's the previous instruction, which moved IP to where it can't fetch from.
+    fn fault(&mut self, error: CpuError) -> Fault {
+        let ip = self.last_fetched.unwrap_or_else(|| self.current_instruction());
+        let instruction = match self.memory_unit.code_section.get(ip) {
+            Some(instruction) => instruction.to_string(),
+            None => String::from("<none>"),
+        };
+        let flags_word = self.flags_word();
+        Fault {
+            error,
+            context: Box::new(FaultContext {
+                ip,
+                instruction,
+                registers: self.register_values(),
+                flags_word,
+                call_stack: self.call_stack.clone(),
+                last_memory_access: self.last_memory_access,
+                memory: format_bytes(&self.memory_unit.data_bus.data, self.dump_limit),
+            }),
+        }
+    }
+
+    /// Breakdown of the main memory needed by the data section.
+    pub fn memory_report(&self) -> MemoryReport {
+        let mut symbols: Vec<(String, usize)> = self.memory_unit.data_section.iter()
+            .map(|(label, data)| (label.clone(), data.width().bytes()))
+            .collect();
+        symbols.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        MemoryReport {
+            capacity: self.memory_unit.get_data_len() + self.memory_unit.get_mem_capacity(),
+            reserved: self.memory_unit.data_base as usize,
+            symbols,
+            environment: self.environment.len(),
+            stack: self.stack.map_or(0, |stack| stack.size),
+        }
+    }
+
+    /// Rejects programs referencing labels that aren't declared in the data section.
+    fn check_labels(&self) -> Result<(), BuildError> {
+        for (index, instruction) in self.memory_unit.code_section.iter().enumerate() {
+            for label in instruction.labels() {
+                if self.memory_unit.symbol(label).is_none() {
+                    return Err(BuildError::UndeclaredLabel(index, label.clone(), self.memory_unit.symbols.suggest(label)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Lays out the data section in main memory and records each label's location in the symbol table.
+    ///
+    /// Labels are stored back to back in alphabetical order so the layout doesn't depend on HashMap iteration order.
+    /// Fails before touching memory if the data section doesn't fit.
+    fn store_label_data(&mut self) -> Result<(), BuildError> {
+        // Check if we have enough space in data_bus, including the memory reserved below the data base
+        let report = self.memory_report();
+        if report.used() > report.capacity {
+            return Err(BuildError::OverCapacity(report));
+        }
+        let data_base = self.memory_unit.data_base as usize;
+
+        // Reserve the memory below the data base
+        self.memory_unit.data_bus.data.resize(data_base, 0);
+        self.memory_unit.data_bus.capacity -= data_base;
+
+        let mut labels: Vec<&String> = self.memory_unit.data_section.keys().collect();
+        labels.sort();
+    
+        // Store data, tracking the offset of the next free byte in the data bus
+        for label in labels {
+            let offset = self.memory_unit.data_bus.data.len() as u32;
+            let data = &self.memory_unit.data_section[label];
+            let (bytes, width) = (data.to_bytes(), data.width());
+            self.memory_unit.data_bus.data.extend(&bytes);
+            self.memory_unit.data_bus.capacity -= bytes.len();
+
+            let symbol = Symbol { offset, width };
+            self.narrate(format_args!("Stored symbol: {:?} -> {:?}", label, symbol));
+            self.memory_unit.symbols.define(label, symbol);
+        }
+
+        // The environment block follows the data section
+        self.environment_offset = self.memory_unit.data_bus.data.len() as u32;
+        self.memory_unit.data_bus.data.extend(&self.environment);
+        self.memory_unit.data_bus.capacity -= self.environment.len();
+
+        // And the stack follows the environment block, starting empty
+        if let Some(stack) = self.stack.as_mut() {
+            stack.base = self.memory_unit.data_bus.data.len();
+            self.memory_unit.data_bus.data.resize(stack.top(), 0);
+            self.memory_unit.data_bus.capacity -= stack.size;
+            self.registers.SP[0].set_value(Data::Word(stack.top() as u16));
+        }
+        Ok(())
+    }
+    
+
+    /// Checks the program for suspicious code without running it.
+    ///
+    /// Registers are checked in program order, jumps are only followed by the reachability walk.
+    pub fn validate(&self) -> Vec<Warning> {
+        let code = &self.memory_unit.code_section;
+        let mut warnings = Vec::new();
+
+        // Data labels never referenced by the code
+        let referenced: Vec<&String> = code.iter().flat_map(|instruction| instruction.labels()).collect();
+        let mut labels: Vec<&String> = self.memory_unit.data_section.keys().collect();
+        labels.sort();
+        for label in labels {
+            if !referenced.contains(&label) {
+                warnings.push(Warning::UnusedLabel(label.clone()));
+            }
+        }
+
+        // Registers read before they are written, reported once per register
+        let mut written: Vec<Register> = Vec::new();
+        for (index, instruction) in code.iter().enumerate() {
+            // Only the syscall number is known to be read, the arguments read depend on it
+            let reads = match instruction.opcode {
+                IS::Syscall => vec![syscall_abi::NUMBER],
+                _ => instruction.register_reads(),
+            };
+            for register in reads {
+                if !written.contains(&register) {
+                    warnings.push(Warning::ReadBeforeWrite(index, register.clone()));
+                    written.push(register);
+                }
+            }
+            written.extend(instruction.register_writes());
+        }
+
+        // ADD/SUB/AND/OR results overwritten before they are read, up to the next jump
+        for (index, instruction) in code.iter().enumerate() {
+            if !matches!(instruction.opcode, IS::Add | IS::Sub | IS::And | IS::Or) {
+                continue;
+            }
+            let Some(Operand::Register(register)) = instruction.operands.first() else {
+                continue;
+            };
+            for next in &code[index + 1..] {
+                if next.opcode.is_jump() || next.register_reads().contains(register) {
+                    break;
+                }
+                if next.register_writes().contains(register) {
+                    warnings.push(Warning::UnusedResult(index, register.clone()));
+                    break;
+                }
+            }
+        }
+
+        // Instructions whose operands make them leave their destination unchanged.
+        // ADD/SUB of 0 still update the flags, but flags are meant to be set with CMP/TEST
+        for (index, instruction) in code.iter().enumerate() {
+            let no_effect = match (&instruction.opcode, instruction.operands.first(), instruction.operands.get(1)) {
+                (IS::Mov, Some(dest), Some(src)) => dest == src,
+                (IS::Add | IS::Sub, Some(_), Some(Operand::Immediate(data))) => data.get_value() == 0,
+                _ => false,
+            };
+            if no_effect {
+                warnings.push(Warning::NoEffect(index));
+            }
+        }
+
+        // Code that can't be reached by falling through or jumping from the entry point of the program
+        let mut reachable = vec![false; code.len()];
+        let mut pending = vec![self.entry];
+        while let Some(index) = pending.pop() {
+            if index >= code.len() || reachable[index] {
+                continue;
+            }
+            reachable[index] = true;
+            let instruction = &code[index];
+            if instruction.opcode.is_jump() {
+                match instruction.operands.first() {
+                    Some(Operand::Immediate(target)) => pending.push(target.get_value() as usize),
+                    Some(Operand::JmpRel(offset)) => pending.push((index as i64 + 1 + *offset as i64).max(0) as usize),
+                    _ => {},
+                }
+            }
+            // Only unconditional jumps and returns never fall through, a call falls through once it returns
+            if !matches!(instruction.opcode, IS::Jmp | IS::Ret) {
+                pending.push(index + 1);
+            }
+        }
+        for (index, is_reachable) in reachable.iter().enumerate() {
+            if !is_reachable && (index == 0 || reachable[index - 1]) {
+                warnings.push(Warning::UnreachableCode(index));
+            }
+        }
+
+        warnings
+    }
+
+    /// Replaces the label of every `[label]` operand in the code section with its interned symbol id.
+    fn intern_operands(&mut self) {
+        let memory = &mut self.memory_unit;
+        for instruction in memory.code_section.iter_mut() {
+            for operand in instruction.operands.iter_mut() {
+                if let Operand::Memory(MemOp::Address(label)) = operand {
+                    let id = memory.symbols.intern(label);
+                    *operand = Operand::Memory(MemOp::Symbol(id, label.clone()));
+                }
+            }
+        }
+    }
+
+    /// Error for a reference to an undeclared label, suggesting the declared label it's likely a typo of
+    /// Pushes a word on the stack, faulting with `CallStackOverflow` once the stack is full
+    fn push_word(&mut self, value: u16) -> Result<(), CpuError> {
+        let base = self.stack.map_or(0, |stack| stack.base);
+        let sp = self.registers.SP[0].get_value() as usize;
+        if sp < base + 2 {
+            return Err(CpuError::CallStackOverflow);
+        }
+        self.memory_unit.write_bytes(sp - 2, &value.to_le_bytes())?;
+        self.registers.SP[0].set_value(Data::Word((sp - 2) as u16));
+        self.mark_memory_written(sp - 2, 2);
+        Ok(())
+    }
+
+    /// Pops a word off the stack, faulting with `ReturnWithoutCall` if the stack is empty
+    fn pop_word(&mut self) -> Result<u16, CpuError> {
+        let top = self.stack.map_or(0, |stack| stack.top());
+        let sp = self.registers.SP[0].get_value() as usize;
+        if sp + 2 > top {
+            return Err(CpuError::ReturnWithoutCall);
+        }
+        self.check_memory_read(sp, 2)?;
+        let bytes = self.memory_unit.read_bytes(sp, 2)?;
+        self.registers.SP[0].set_value(Data::Word((sp + 2) as u16));
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn undeclared_label(&self, label: &str) -> CpuError {
+        CpuError::UndeclaredLabel(label.to_string(), self.memory_unit.symbols.suggest(label))
+    }
+
+    /// Resolves a memory operand to the location it refers to in main memory.
+    fn memory_location(&self, operand: &MemOp) -> Result<Symbol, CpuError> {
+        match operand {
+            MemOp::Address(label) => match self.memory_unit.symbol(label) {
+                Some(symbol) => Ok(symbol.clone()),
+                None => Err(self.undeclared_label(label)),
+            },
+            MemOp::Symbol(id, label) => match self.memory_unit.symbols.get(*id) {
+                Some(symbol) => Ok(symbol.clone()),
+                None => Err(self.undeclared_label(label)),
+            },
+            MemOp::Label(label) => Err(CpuError::NotAMemoryLocation(label.clone())),
+            MemOp::Ptr(width, label, displacement) => {
+                let symbol = match self.memory_unit.symbol(label) {
+                    Some(symbol) => symbol,
+                    None => return Err(self.undeclared_label(label)),
+                };
+                let offset = symbol.offset as i64 + *displacement as i64;
+                if offset < 0 || offset + width.bytes() as i64 > self.memory_unit.get_data_len() as i64 {
+                    return Err(CpuError::AddressOutOfBounds(label.clone(), *displacement));
+                }
+                Ok(Symbol { offset: offset as u32, width: *width })
+            },
+            MemOp::Indirect(width, register, displacement) => {
+                let address = self.registers.GP[register.index()].get_value() as i64 + *displacement as i64;
+                if address < 0 || address + width.bytes() as i64 > self.memory_unit.get_data_len() as i64 {
+                    return Err(CpuError::MemoryOutOfBounds(address.max(0) as usize, width.bytes()));
+                }
+                Ok(Symbol { offset: address as u32, width: *width })
+            },
+        }
+    }
+
+    /// Reads the value of a source operand along with its width.
+    ///
+    /// A label operand evaluates to the label's offset in main memory rather than its contents.
+    /// In pipeline view the operand and its value are recorded for `Event::Decoded`.
+    fn resolve_source(&mut self, operand: &Operand) -> Result<(u32, DataWidth), CpuError> {
+        let (value, width) = self.read_source(operand)?;
+        if self.pipeline_view && self.executing.is_some() {
+            self.stage_reads.push((operand.to_string(), value));
+        }
+        Ok((value, width))
+    }
+
+    /// Reads the value of a source operand, see `resolve_source`
+    fn read_source(&mut self, operand: &Operand) -> Result<(u32, DataWidth), CpuError> {
+        match operand {
+            Operand::Register(register) => {
+                self.check_register_read(register)?;
+                let value = self.registers.get_register(register.clone()).get_value();
+                Ok((value, register.width()))
+            },
+            Operand::Immediate(data) => Ok((data.get_value(), data.width())),
+            Operand::Memory(MemOp::Label(label)) => match self.memory_unit.symbol(label) {
+                Some(symbol) => Ok((symbol.offset, DataWidth::Dword)),
+                None => Err(self.undeclared_label(label)),
+            },
+            Operand::Memory(operand) => {
+                let location = self.memory_location(operand)?;
+                self.check_memory_read(location.offset as usize, location.width.bytes())?;
+                self.memory_reads += 1;
+                Ok((self.memory_unit.read_value(&location)?, location.width))
+            },
+            Operand::LabelOffset(label) => match self.memory_unit.symbol(label) {
+                Some(symbol) => Ok((symbol.offset, DataWidth::Dword)),
+                None => Err(self.undeclared_label(label)),
+            },
+            Operand::JmpRel(_) => Err(CpuError::InvalidOperand(operand.clone())),
+        }
+    }
+
+    /// Width of the data a destination operand holds.
+    fn destination_width(&self, operand: &Operand) -> Result<DataWidth, CpuError> {
+        match operand {
+            Operand::Register(register) => Ok(register.width()),
+            Operand::Memory(operand) => Ok(self.memory_location(operand)?.width),
+            _ => Err(CpuError::InvalidDestination(operand.clone())),
+        }
+    }
+
+    /// Writes a value to a destination operand, truncated to `width`.
+    ///
+    /// Only the bytes covered by `width` are written, so neighbouring register halves and memory variables are untouched.
+    /// In pipeline view the destination and its new value are recorded for `Event::Executed`.
+    fn write_destination(&mut self, operand: &Operand, value: u32, width: DataWidth) -> Result<(), CpuError> {
+        if self.pipeline_view && self.executing.is_some() {
+            self.stage_writes.push((operand.to_string(), value & width.mask()));
+        }
+        match operand {
+            Operand::Register(register) => {
+                self.registers.get_register(register.clone()).set_value(Data::from_u32(width, value));
+                self.mark_register_written(register);
+                Ok(())
+            },
+            Operand::Memory(operand) => {
+                let location = self.memory_location(operand)?;
+                self.memory_writes += 1;
+                self.memory_unit.write_value(&Symbol { offset: location.offset, width }, value)?;
+                self.mark_memory_written(location.offset as usize, width.bytes());
+                Ok(())
+            },
+            _ => Err(CpuError::InvalidDestination(operand.clone())),
+        }
+    }
+
+    /// Moves the value of `src` into `dest` at the width of the destination and returns the moved value.
+    ///
+    /// A memory destination is written at its label's declared width, or at the width of its
+    /// `byte/word/dword ptr` qualifier. Immediates that don't fit that width are rejected instead of truncated.
+    ///
+    /// Every operand combination follows the same width rule: the destination is always written in full,
+    /// sources narrower than it are zero-extended (`mov ax, [byte_label]` clears AH) and register or memory
+    /// sources wider than it are truncated to its width, so no stale bytes are left behind.
+    /// The flags are never modified, as declared by `IS::Mov.flag_mask()`.
+    fn move_operand(&mut self, dest: &Operand, src: &Operand) -> Result<u32, CpuError> {
+        if let (Operand::Memory(_), Operand::Memory(_)) = (dest, src) {
+            return Err(CpuError::MemoryToMemory);
+        }
+        let width = self.destination_width(dest)?;
+        let (value, _) = self.resolve_source(src)?;
+        if let Operand::Immediate(_) = src && value & !width.mask() != 0 {
+            return Err(CpuError::ImmediateOverflow(value, width));
+        }
+        self.write_destination(dest, value, width)?;
+        Ok(value)
+    }
+
+    /// Updates the flags selected by `mask` from the flags computed by the ALU, leaving the others untouched.
+    fn apply_flags(&mut self, delta: FlagsDelta, mask: u16) {
+        let flags = [
+            (FLAG_PF, 0, delta.parity), (FLAG_AF, 1, delta.aux), (FLAG_ZF, 2, delta.zero),
+            (FLAG_SF, 3, delta.sign), (FLAG_OF, 7, delta.overflow), (FLAG_CF, 8, delta.carry),
+        ];
+        for (flag, index, value) in flags {
+            if mask & flag != 0 {
+                self.flags[index].set_value(value as u8);
+            }
+        }
+    }
+
+    /// Runs `dest = dest <op> src` through the ALU at the width of the destination, writes the result back
+    /// and updates the flags selected by `flags`.
+    ///
+    /// Returns the destination and source values along with the result.
+    fn binary_op(&mut self, mode: ALUMode, flags: u16, dest: &Operand, src: &Operand) -> Result<(u32, u32, u32), CpuError> {
+        let (dest_value, src_value, result, delta, width) = self.alu_binary(mode, dest, src)?;
+        self.write_destination(dest, result, width)?;
+        self.apply_flags(delta, flags);
+        Ok((dest_value, src_value, result))
+    }
+
+    /// Subtracts `src` from `dest` through the ALU like SUB, updating the flags but leaving `dest` unchanged.
+    ///
+    /// Returns the destination and source values.
+    fn compare(&mut self, dest: &Operand, src: &Operand) -> Result<(u32, u32), CpuError> {
+        let (dest_value, src_value, _, delta, _) = self.alu_binary(ALUMode::Sub, dest, src)?;
+        self.apply_flags(delta, IS::Cmp.flag_mask());
+        Ok((dest_value, src_value))
+    }
+
+    /// Executes the operation the ALU is set up for and turns it off, reporting the operation to the observers
+    fn execute_alu(&mut self, width: DataWidth) -> (u32, FlagsDelta) {
+        let (result, delta) = self.alu.execute(width);
+        self.alu.set_mode(ALUMode::Off);
+        if let Some(trace) = self.alu.trace {
+            self.emit(Event::AluOperation(trace));
+        }
+        (result, delta)
+    }
+
+    /// Runs `dest <op> src` through the ALU at the width of the destination without writing the result.
+    ///
+    /// Returns the destination and source values, the result, its flags and the width of the operation.
+    fn alu_binary(&mut self, mode: ALUMode, dest: &Operand, src: &Operand) -> Result<(u32, u32, u32, FlagsDelta, DataWidth), CpuError> {
+        if let (Operand::Memory(_), Operand::Memory(_)) = (dest, src) {
+            return Err(CpuError::MemoryToMemory);
+        }
+        let width = self.destination_width(dest)?;
+        let (dest_value, _) = self.resolve_source(dest)?;
+        let (src_value, _) = self.resolve_source(src)?;
+
+        self.alu.set_mode(mode);
+        self.alu.operand_fetch(dest_value, src_value);
+        let (result, delta) = self.execute_alu(width);
+        Ok((dest_value, src_value, result, delta, width))
+    }
+
+    /// Runs `dest = <op> dest` through the ALU at the width of the destination, writes the result back
+    /// and updates the flags selected by `flags`.
+    ///
+    /// Returns the destination value along with the result.
+    fn unary_op(&mut self, mode: ALUMode, flags: u16, dest: &Operand) -> Result<(u32, u32), CpuError> {
+        let width = self.destination_width(dest)?;
+        let (dest_value, _) = self.resolve_source(dest)?;
+
+        self.alu.set_mode(mode);
+        self.alu.operand_fetch_single(dest_value);
+        let (result, delta) = self.execute_alu(width);
+
+        self.write_destination(dest, result, width)?;
+        self.apply_flags(delta, flags);
+        Ok((dest_value, result))
+    }
+
+    /// The fetch stage operation of CPU's workflow.
+    ///
+    /// IP is incremented before the instruction is decoded, so during decode IP refers to the next instruction.
+    /// Control flow instructions follow this convention:
+    /// - absolute jumps set IP directly to the target instruction index.
+    /// - relative jumps add their signed offset to the already incremented IP.
+    /// - instructions saving a return address (Call, Int) save the incremented IP.
+    fn fetch(&mut self) -> Result<(), CpuError> {
+            let pc = self.registers.SP[2].get_value();
+            let Some(instruction) = self.memory_unit.code_section.get(pc as usize).cloned() else {
+                return Err(CpuError::InvalidInstructionPointer(pc, self.last_fetched));
+            };
+            self.last_fetched = Some(pc as usize);
+            if self.trace_tail.len() == TRACE_TAIL {
+                self.trace_tail.pop_front();
+            }
+            self.trace_tail.push_back(pc as usize);
+            self.registers.SP[2].set_value(Data::Word((pc + 1) as u16));
+            self.instruction_count += 1;
+            self.cycle_count += self.instruction_cost(&instruction);
+            if let Some(profile) = &mut self.profile {
+                *profile.counts.entry(profile.frames.clone()).or_default() += 1;
+            }
+            if self.verbosity < Verbosity::Instruction {
+                return self.execute(pc as usize, instruction);
+            }
+
+            let text = instruction.to_string();
+            let before = self.register_values();
+            self.execute(pc as usize, instruction)?;
+            let changed: Vec<String> = Register::all().iter()
+                .zip(before.iter().zip(self.register_values()))
+                .filter(|(_, (old, new))| *old != new)
+                .map(|(register, (old, new))| format!(
+                    "{}: {} -> {}",
+                    Operand::Register(register.clone()),
+                    format_value(*old, register.width(), Base::Hex),
+                    paint(&format_value(new, register.width(), Base::Hex), Color::Green, self.color),
+                ))
+                .collect();
+            match changed.is_empty() {
+                true => self.trace(format_args!("{:04}  {}", pc, text)),
+                false => self.trace(format_args!("{:04}  {:<24} ; {}", pc, text, changed.join(", "))),
+            }
+            Ok(())
+        }
+
+    /// Decodes the instruction at the index, reporting what it changed to the observers if there are any
+    fn execute(&mut self, index: usize, instruction: Instruction) -> Result<(), CpuError> {
+        if self.observers.0.is_empty() {
+            return self.decode(instruction);
+        }
+        let text = instruction.to_string();
+        let (registers, flags) = (self.register_values(), self.flags_word());
+        self.executing = Some(index);
+        if self.pipeline_view {
+            self.emit(Event::Fetched(text.clone()));
+        }
+        let result = self.decode(instruction);
+        let (reads, writes) = (std::mem::take(&mut self.stage_reads), std::mem::take(&mut self.stage_writes));
+        if result.is_ok() {
+            if self.pipeline_view {
+                self.emit(Event::Decoded(reads));
+                self.emit(Event::Executed(writes));
+            }
+            for ((register, old), new) in Register::all().into_iter().zip(registers).zip(self.register_values()) {
+                if old != new {
+                    self.emit(Event::RegisterWrite(register, old, new));
+                }
+            }
+            let changed = flags ^ self.flags_word();
+            for (bit, name) in FLAG_NAMES.iter().enumerate().filter(|(bit, _)| changed & (1 << bit) != 0) {
+                self.emit(Event::FlagChange(name, self.flags_word() & (1 << bit) != 0));
+            }
+            self.emit(Event::InstructionExecuted(text));
+        }
+        self.executing = None;
+        result
+    }
+
+    /// The decode stage operation of CPU's workflow.
+    fn decode(&mut self, instruction: Instruction) -> Result<(), CpuError> {
+        let info = instruction.opcode.info();
+        if !info.supported {
+            return Err(CpuError::UnsupportedInstruction(instruction.opcode));
+        }
+        if !instruction.verify_operands() {
+            return Err(instruction.invalid_operands());
+        }
+
+        match instruction.opcode {
+            IS::Mov => {
+                let dest = instruction.dest()?;
+                let src = instruction.src()?;
+                let value = self.move_operand(dest, src)?;
+                self.narrate(format_args!("Data movement occured:\n{0:?} -> {1:?}\n{1:?} updated to: \n{2:?}", src, dest, value));
+            },
+            IS::Add => {
+                let dest = instruction.dest()?;
+                let src = instruction.src()?;
+                let (dest_value, src_value, result) = self.binary_op(ALUMode::Add, IS::Add.flag_mask(), dest, src)?;
+                self.narrate(format_args!("Data addition occured:\n{0:?}: {1:?} + {2:?}: {3:?}\n{0:?} updated to: \n{4:?}", dest, dest_value, src, src_value, result));
+            },
+            IS::Sub => {
+                let dest = instruction.dest()?;
+                let src = instruction.src()?;
+                let (dest_value, src_value, result) = self.binary_op(ALUMode::Sub, IS::Sub.flag_mask(), dest, src)?;
+                self.narrate(format_args!("Subtraction occured:\n{0:?}: {1:?} - {2:?}: {3:?}\n{0:?} updated to: \n{4:?}", dest, dest_value, src, src_value, result));
+            },
+            IS::And => {
+                let dest = instruction.dest()?;
+                let src = instruction.src()?;
+                let (dest_value, src_value, result) = self.binary_op(ALUMode::And, IS::And.flag_mask(), dest, src)?;
+                self.narrate(format_args!("Bitwise AND occured:\n{0:?}: {1:?} & {2:?}: {3:?}\n{0:?} updated to: \n{4:?}", dest, dest_value, src, src_value, result));
+            },
+            IS::Or => {
+                let dest = instruction.dest()?;
+                let src = instruction.src()?;
+                let (dest_value, src_value, result) = self.binary_op(ALUMode::Or, IS::Or.flag_mask(), dest, src)?;
+                self.narrate(format_args!("Bitwise OR occured:\n{0:?}: {1:?} | {2:?}: {3:?}\n{0:?} updated to: \n{4:?}", dest, dest_value, src, src_value, result));
+            },
+            IS::Cmp => {
+                let dest = instruction.dest()?;
+                let src = instruction.src()?;
+                let (dest_value, src_value) = self.compare(dest, src)?;
+                self.narrate(format_args!("Comparison occured:\n{0:?}: {1:?} - {2:?}: {3:?}\nFlags updated, {0:?} unchanged", dest, dest_value, src, src_value));
+            },
+            IS::Jmp | IS::Jz | IS::Jnz | IS::Jc | IS::Jnc => {
+                // IP already points at the instruction after the jump
+                let next_ip = self.registers.SP[2].get_value();
+                let target = match instruction.only()?.clone() {
+                    Operand::Immediate(value) => value.get_value() as i64,
+                    Operand::JmpRel(offset) => next_ip as i64 + offset as i64,
+                    operand => return Err(CpuError::InvalidOperand(operand)),
+                };
+                let (zero, carry) = (self.flags[2].get_value() != 0, self.flags[8].get_value() != 0);
+                let taken = match instruction.opcode {
+                    IS::Jz => zero,
+                    IS::Jnz => !zero,
+                    IS::Jc => carry,
+                    IS::Jnc => !carry,
+                    _ => true,
+                };
+                if !taken {
+                    self.narrate(format_args!("Jump not taken:\nIP: {:?}", next_ip));
+                    return Ok(());
+                }
+
+                if target < 0 || target > u16::MAX as i64 {
+                    return Err(CpuError::JumpOutOfRange(target));
+                }
+                self.registers.SP[2].set_value(Data::Word(target as u16));
+                self.narrate(format_args!("Jump occured:\nIP: {:?} -> IP: {:?}", next_ip, target));
+            },
+            IS::Call => {
+                // The return address is the instruction after the call, where IP already points
+                let return_address = self.registers.SP[2].get_value() as usize;
+                if self.call_stack.len() >= CALL_STACK_LIMIT {
+                    return Err(CpuError::CallStackOverflow);
+                }
+                let target = match instruction.only()?.clone() {
+                    Operand::Immediate(value) => value.get_value() as i64,
+                    Operand::JmpRel(offset) => return_address as i64 + offset as i64,
+                    operand => return Err(CpuError::InvalidOperand(operand)),
+                };
+                if target < 0 || target > u16::MAX as i64 {
+                    return Err(CpuError::JumpOutOfRange(target));
+                }
+                if let Some(stack) = self.stack {
+                    self.push_word(return_address as u16)?;
+                    if stack.canaries {
+                        self.push_word(STACK_CANARY)?;
+                    }
+                }
+                self.call_stack.push(return_address);
+                if let Some(profile) = &mut self.profile {
+                    profile.frames.push(target as usize);
+                }
+                self.registers.SP[2].set_value(Data::Word(target as u16));
+                self.narrate(format_args!("Call occured:\nIP: {:?} -> IP: {:?}, returning to {:?}", return_address, target, return_address));
+            },
+            IS::Ret => {
+                let Some(&expected) = self.call_stack.last() else {
+                    return Err(CpuError::ReturnWithoutCall);
+                };
+                let return_address = match self.stack {
+                    Some(stack) => {
+                        if stack.canaries {
+                            let canary = self.pop_word()?;
+                            if canary != STACK_CANARY {
+                                return Err(CpuError::CorruptedReturnAddress(STACK_CANARY, canary, expected));
+                            }
+                        }
+                        let popped = match self.pop_word()? {
+                            u16::MAX if expected == RETURN_TO_HOST => RETURN_TO_HOST,
+                            popped => popped as usize,
+                        };
+                        if popped != expected {
+                            match stack.shadow_stack_policy {
+                                ShadowStackPolicy::Error => return Err(CpuError::ShadowStackMismatch(popped, expected)),
+                                ShadowStackPolicy::Warn => self.warn(Warning::ShadowStackMismatch(self.current_instruction(), popped, expected)),
+                            }
+                        }
+                        popped
+                    },
+                    None => expected,
+                };
+                self.call_stack.pop();
+                if let Some(profile) = &mut self.profile {
+                    profile.frames.pop();
+                }
+                if return_address == RETURN_TO_HOST {
+                    self.returned_to_host = true;
+                    self.narrate(format_args!("Return occured:\nIP -> host"));
+                    return Ok(());
+                }
+                self.registers.SP[2].set_value(Data::Word(return_address as u16));
+                self.narrate(format_args!("Return occured:\nIP -> IP: {:?}", return_address));
+            },
+            IS::Daa | IS::Das => {
+                self.check_register_read(&Register::AX)?;
+                let subtract = matches!(instruction.opcode, IS::Das);
+                let (old_al, al) = self.decimal_adjust(subtract);
+                self.narrate(format_args!("Decimal adjustment occured:\nAL: {:#04X} -> AL: {:#04X}", old_al, al));
+            },
+            IS::Aam | IS::Aad => {
+                let base = match instruction.operands.first() {
+                    None => 10,
+                    Some(Operand::Immediate(value)) => value.get_value() as u8,
+                    Some(operand) => return Err(CpuError::InvalidOperand(operand.clone())),
+                };
+                self.check_register_read(&Register::AX)?;
+                let (old_ax, ax) = match instruction.opcode {
+                    IS::Aam => self.ascii_adjust_multiply(base)?,
+                    _ => self.ascii_adjust_divide(base),
+                };
+                self.narrate(format_args!("ASCII adjustment occured:\nAX: {:#06X} -> AX: {:#06X}", old_ax, ax));
+            },
+            IS::Div => {
+                let (divisor, width) = self.resolve_source(instruction.only()?)?;
+                let (low, high) = width.dividend_registers();
+                self.check_register_read(&low)?;
+                let mut dividend = self.registers.get_register(low.clone()).get_value() as u64;
+                if let Some(high) = &high {
+                    self.check_register_read(high)?;
+                    dividend |= (self.registers.get_register(high.clone()).get_value() as u64) << (width.bytes() * 8);
+                }
+                let (quotient, remainder) = self.semantics.divide(dividend, divisor, width)?;
+                match &high {
+                    Some(high) => {
+                        self.registers.get_register(low.clone()).set_value(Data::from_u32(width, quotient));
+                        self.registers.get_register(high.clone()).set_value(Data::from_u32(width, remainder));
+                        self.mark_register_written(high);
+                    },
+                    // A byte division leaves the quotient in AL and the remainder in AH
+                    None => {
+                        self.registers.get_register(low.clone()).set_value(Data::Word(u16::from_le_bytes([quotient as u8, remainder as u8])));
+                    },
+                }
+                self.mark_register_written(&low);
+                self.narrate(format_args!("Division occured:\n{:#X} / {:#X} = {:#X} remainder {:#X}", dividend, divisor, quotient, remainder));
+            },
+            IS::Not => {
+                let dest = instruction.only()?;
+                let (value, result) = self.unary_op(ALUMode::Not, instruction.opcode.flag_mask(), dest)?;
+                self.narrate(format_args!("Complement occured:\n!{0:?}: {1:?}\n{0:?} updated to: \n{2:?}", dest, value, result));
+            },
+            IS::Syscall => {
+                let result = self.syscall();
+                if let Some(code) = self.aborted.take() {
+                    return Err(CpuError::Aborted(code));
+                }
+                result.map_err(CpuError::Syscall)?;
+            },
+            IS::AssertReg | IS::AssertFlag if !self.guest_assertions => {},
+            IS::AssertReg => {
+                let actual = match instruction.dest()? {
+                    Operand::Register(register) => self.registers.get_register(register.clone()).get_value(),
+                    operand => return Err(CpuError::InvalidOperand(operand.clone())),
+                };
+                let (expected, _) = self.resolve_source(instruction.src()?)?;
+                if actual != expected {
+                    return Err(CpuError::GuestAssertionFailed(self.current_instruction(), expected, actual));
+                }
+            },
+            IS::AssertFlag => {
+                // The flag is given by its mask, e.g. FLAG_ZF
+                let (flag, _) = self.resolve_source(instruction.dest()?)?;
+                let (expected, _) = self.resolve_source(instruction.src()?)?;
+                if !flag.is_power_of_two() || flag.trailing_zeros() as usize >= self.flags.len() {
+                    return Err(CpuError::InvalidOperand(instruction.dest()?.clone()));
+                }
+                let actual = self.flags[flag.trailing_zeros() as usize].get_value() as u32;
+                if actual != expected {
+                    return Err(CpuError::GuestAssertionFailed(self.current_instruction(), expected, actual));
+                }
+            },
+
+            opcode => return Err(CpuError::UnsupportedInstruction(opcode)),
+        }
+        Ok(())
+    }
+
+    /// Adjusts AL to a packed BCD value after an addition (DAA) or subtraction (DAS) of two packed BCD bytes.
+    ///
+    /// Follows the x86 algorithm: the low digit is corrected when it exceeds 9 or AF is set,
+    /// then the high digit when the original AL exceeded 0x99 or CF was set. OF is left untouched.
+    /// Returns the original and adjusted AL.
+    fn decimal_adjust(&mut self, subtract: bool) -> (u8, u8) {
+        let old_al = self.registers.get_register(Register::AX).get_value() as u8;
+        let old_carry = self.flags[8].get_value() == 1;
+        let mut al = old_al;
+        let mut carry = false;
+        let mut aux = false;
+
+        if old_al & 0x0F > 9 || self.flags[1].get_value() == 1 {
+            let (adjusted, borrow_or_carry) = match subtract {
+                true => al.overflowing_sub(0x06),
+                false => al.overflowing_add(0x06),
+            };
+            al = adjusted;
+            carry = old_carry || borrow_or_carry;
+            aux = true;
+        }
+        if old_al > 0x99 || old_carry {
+            al = match subtract {
+                true => al.wrapping_sub(0x60),
+                false => al.wrapping_add(0x60),
+            };
+            carry = true;
+        } else if !subtract {
+            carry = false;
+        }
+
+        self.registers.get_register(Register::AX).set_value(Data::Byte(al));
+        self.mark_register_written(&Register::AX);
+        let delta = FlagsDelta::from_result(al as u32, DataWidth::Byte, carry, false, aux);
+        let opcode = match subtract {
+            true => IS::Das,
+            false => IS::Daa,
+        };
+        self.apply_flags(delta, opcode.flag_mask());
+        (old_al, al)
+    }
+
+    /// Splits AL into two digits of `base` after a multiplication (AAM): AH = AL / base, AL = AL % base.
+    ///
+    /// Updates ZF, SF and PF from AL. Returns the original and adjusted AX.
+    fn ascii_adjust_multiply(&mut self, base: u8) -> Result<(u16, u16), CpuError> {
+        let old_ax = self.registers.get_register(Register::AX).get_value() as u16;
+        let al = old_ax as u8;
+        let (quotient, remainder) = self.semantics.divide(al as u64, base as u32, DataWidth::Byte)?;
+        let ax = u16::from_le_bytes([remainder as u8, quotient as u8]);
+        self.set_ascii_adjusted(ax);
+        Ok((old_ax, ax))
+    }
+
+    /// Combines the two digits of `base` in AH and AL before a division (AAD): AL = AH * base + AL, AH = 0.
+    ///
+    /// Updates ZF, SF and PF from AL. Returns the original and adjusted AX.
+    fn ascii_adjust_divide(&mut self, base: u8) -> (u16, u16) {
+        let old_ax = self.registers.get_register(Register::AX).get_value() as u16;
+        let [al, ah] = old_ax.to_le_bytes();
+        let ax = ah.wrapping_mul(base).wrapping_add(al) as u16;
+        self.set_ascii_adjusted(ax);
+        (old_ax, ax)
+    }
+
+    fn set_ascii_adjusted(&mut self, ax: u16) {
+        self.registers.get_register(Register::AX).set_value(Data::Word(ax));
+        self.mark_register_written(&Register::AX);
+        let delta = FlagsDelta::from_result(ax as u8 as u32, DataWidth::Byte, false, false, false);
+        // AAM and AAD update the same flags
+        self.apply_flags(delta, IS::Aam.flag_mask());
+    }
+
+    fn syscall(&mut self)-> Result<(), String> {
+        self.check_register_read(&syscall_abi::NUMBER).map_err(|err| format!("{:?}", err))?;
+        let syscall_number: u8 = self.registers.get_register(syscall_abi::NUMBER).get_value() as u8;
+        let file_descriptor: u8 = self.registers.get_register(syscall_abi::ARG1).get_value() as u8;
+        let data_length: u16  = self.registers.get_register(syscall_abi::ARG3).get_value() as u16;
+        let offset = self.registers.get_register(syscall_abi::ARG2).get_value() as usize;
+        self.emit(Event::Syscall(syscall_number));
+
+        // CX holds the offset of the buffer in memory (as loaded from a label) and DX the length of data
+        match syscall_number {
+            // Read from file descriptor(file or keyboard)
+            // Currently reads from the guest input set on the builder whatever the descriptor
+            // Returns the number of bytes read in AX, fewer than requested once the input ends
+            syscall_abi::SYS_READ => {
+                // Nothing is read unless the whole buffer lies in main memory
+                let range = self.memory_unit.checked_range(offset, data_length as usize).map_err(|err| format!("{:?}", err))?;
+                let mut read_buffer = Vec::with_capacity(range.len());
+                self.guest_input.0.by_ref().take(range.len() as u64).read_to_end(&mut read_buffer)
+                    .map_err(|err| format!("Read failed: {}", err))?;
+
+                self.memory_unit.write_bytes(offset, &read_buffer).map_err(|err| format!("{:?}", err))?;
+                self.mark_memory_written(offset, read_buffer.len());
+                self.registers.get_register(syscall_abi::RESULT).set_value(Data::Word(read_buffer.len() as u16));
+                self.mark_register_written(&syscall_abi::RESULT);
+                Ok(())
+            },
+            // Write to file descriptor(file or screen)
+            // Currently writes to the guest output set on the builder whatever the descriptor
+            syscall_abi::SYS_WRITE => {
+                self.check_memory_read(offset, data_length as usize).map_err(|err| format!("{:?}", err))?;
+                let write_buffer = self.memory_unit.read_bytes(offset, data_length as usize).map_err(|err| format!("{:?}", err))?;
+                self.guest_output.0.write_all(&write_buffer).map_err(|err| format!("Write failed: {}", err))?;
+                Ok(())
+            }
+            // Write a null-terminated string to the screen, returning its length in AX
+            // The string is cut at the end of main memory if no terminating 0 byte is found
+            syscall_abi::SYS_PRINT_STRING => {
+                let memory = self.memory_unit.data_bus.data.get(offset..).unwrap_or(&[]);
+                let string_length = memory.iter().position(|byte| *byte == 0).unwrap_or(memory.len());
+                self.check_memory_read(offset, string_length).map_err(|err| format!("{:?}", err))?;
+                let write_buffer = self.memory_unit.read_bytes(offset, string_length).map_err(|err| format!("{:?}", err))?;
+                self.guest_output.0.write_all(&write_buffer).map_err(|err| format!("Write failed: {}", err))?;
+                self.registers.get_register(syscall_abi::RESULT).set_value(Data::Word(string_length as u16));
+                self.mark_register_written(&syscall_abi::RESULT);
+                Ok(())
+            }
+            // Write the performance counters to the buffer, as little endian u64 values in the order:
+            // instructions executed, cycles, memory reads, memory writes
+            // The counts include the syscall instruction itself
+            // Returns the number of bytes written in AX, or -1 if the buffer is outside main memory
+            syscall_abi::SYS_COUNTERS => {
+                let mut counters = Vec::new();
+                for counter in [self.instruction_count, self.cycle_count, self.memory_reads, self.memory_writes] {
+                    counters.extend(counter.to_le_bytes());
+                }
+                let result = match offset + counters.len() <= self.memory_unit.get_data_len() {
+                    true => {
+                        self.memory_unit.write_bytes(offset, &counters).map_err(|err| format!("{:?}", err))?;
+                        self.mark_memory_written(offset, counters.len());
+                        counters.len() as u16
+                    },
+                    false => syscall_abi::ERROR,
+                };
+                self.registers.get_register(syscall_abi::RESULT).set_value(Data::Word(result));
+                self.mark_register_written(&syscall_abi::RESULT);
+                Ok(())
+            }
+            // Copy DX bytes from the offset in BX to the offset in CX, overlapping ranges are handled like memmove
+            // Fill DX bytes at the offset in CX with the byte in BL
+            // Both return the number of bytes written in AX, or -1 if a range is outside main memory
+            syscall_abi::SYS_MEMCPY | syscall_abi::SYS_MEMSET => {
+                let length = data_length as usize;
+                let source = self.registers.get_register(syscall_abi::ARG1).get_value() as usize;
+                let in_bounds = |start: usize| start + length <= self.memory_unit.get_data_len();
+                let written = match (syscall_number, in_bounds(offset), in_bounds(source)) {
+                    (syscall_abi::SYS_MEMCPY, true, true) => {
+                        self.check_memory_read(source, length).map_err(|err| format!("{:?}", err))?;
+                        // Reading the whole source before writing makes overlapping copies safe
+                        let bytes = self.memory_unit.read_bytes(source, length).map_err(|err| format!("{:?}", err))?;
+                        self.memory_unit.write_bytes(offset, &bytes).map_err(|err| format!("{:?}", err))?;
+                        Some(length)
+                    },
+                    (syscall_abi::SYS_MEMSET, true, _) => {
+                        self.memory_unit.write_bytes(offset, &vec![file_descriptor; length]).map_err(|err| format!("{:?}", err))?;
+                        Some(length)
+                    },
+                    _ => None,
+                };
+                if written.is_some() {
+                    self.mark_memory_written(offset, length);
+                }
+                let result = written.map_or(syscall_abi::ERROR, |length| length as u16);
+                self.registers.get_register(syscall_abi::RESULT).set_value(Data::Word(result));
+                self.mark_register_written(&syscall_abi::RESULT);
+                Ok(())
+            }
+            // Set CX to the offset of the environment block and DX to its length,
+            // ready to be passed to the write syscall (2) or scanned for a variable
+            syscall_abi::SYS_ENVIRONMENT => {
+                self.registers.get_register(syscall_abi::ARG2).set_value(Data::Word(self.environment_offset as u16));
+                self.registers.get_register(syscall_abi::ARG3).set_value(Data::Word(self.environment.len() as u16));
+                self.mark_register_written(&syscall_abi::ARG2);
+                self.mark_register_written(&syscall_abi::ARG3);
+                Ok(())
+            }
+            // Log the DX bytes at the offset in CX with the level in BX (0 debug, 1 info, 2 warn, 3 error)
+            // The message is kept and reported to the observers, apart from the program's output
+            syscall_abi::SYS_LOG => {
+                let level = self.registers.get_register(syscall_abi::ARG1).get_value();
+                let level = LogLevel::from_code(level).ok_or(format!("Unknown log level: {}", level))?;
+                self.check_memory_read(offset, data_length as usize).map_err(|err| format!("{:?}", err))?;
+                let bytes = self.memory_unit.read_bytes(offset, data_length as usize).map_err(|err| format!("{:?}", err))?;
+                let message = String::from_utf8_lossy(&bytes).into_owned();
+                let log = GuestLog { instruction: self.current_instruction(), level, message };
+                self.emit(Event::GuestLog(log.clone()));
+                self.guest_logs.push(log);
+                Ok(())
+            }
+            // Fault with the code in BX, e.g. when the guest finds its state corrupted
+            syscall_abi::SYS_ABORT => {
+                self.aborted = Some(file_descriptor);
+                Err(format!("Aborted with code {}", file_descriptor))
+            }
+            // Give up the rest of the slice when run by a scheduler, otherwise do nothing
+            syscall_abi::SYS_YIELD => {
+                self.yielded = true;
+                Ok(())
+            }
+            syscall_abi::SYS_EXIT => {
+                self.narrate(format_args!("Program exited with code: {}", file_descriptor));
+                self.exit_code = Some(file_descriptor);
+                Ok(())
+            }
+            _ => {
+                let err_msg = format!("Unknown file systemcall number: {}", syscall_number);
+                Err(err_msg)
+            }
+        }
+    }
+
+    /// Saves the full contents of main memory to a file.
+    ///
+    /// The image starts with a header holding the memory capacity, the data base and the symbol table,
+    /// so the data section layout survives a reload. All integers are stored little endian.
+    ///
+    /// Layout: `CPUMEM` magic, capacity (u32), data base (u32), symbol count (u32),
+    /// then per symbol its name length (u16), name, offset (u32) and width in bytes (u8),
+    /// then the RAM length (u32) and its bytes.
+    pub fn save_memory(&self, path: &str) -> Result<(), String> {
+        let memory = &self.memory_unit;
+        let mut image = MEMORY_IMAGE_MAGIC.to_vec();
+        image.extend(((memory.get_data_len() + memory.get_mem_capacity()) as u32).to_le_bytes());
+        image.extend(memory.data_base.to_le_bytes());
+
+        let symbols = memory.symbols.defined();
+        image.extend((symbols.len() as u32).to_le_bytes());
+        for (label, symbol) in symbols {
+            image.extend((label.len() as u16).to_le_bytes());
+            image.extend(label.as_bytes());
+            image.extend(symbol.offset.to_le_bytes());
+            image.push(symbol.width.bytes() as u8);
+        }
+
+        image.extend((memory.get_data_len() as u32).to_le_bytes());
+        image.extend(&memory.data_bus.data);
+
+        std::fs::write(path, image).map_err(|err| format!("Failed to write memory image {:?}: {}", path, err))
+    }
+
+    /// Restores the contents of main memory and the symbol table from a file written by `save_memory`.
+    ///
+    /// Fails without modifying memory if the image is malformed or needs more capacity than this CPU's RAM has.
+    pub fn load_memory(&mut self, path: &str) -> Result<(), String> {
+        let image = std::fs::read(path).map_err(|err| format!("Failed to read memory image {:?}: {}", path, err))?;
+        let mut cursor = 0;
+        let mut take = |length: usize| -> Result<&[u8], String> {
+            if cursor + length > image.len() {
+                return Err(format!("Memory image {:?} is truncated", path));
+            }
+            cursor += length;
+            Ok(&image[cursor - length..cursor])
+        };
+        let read_u32 = |bytes: &[u8]| u32::from_le_bytes(bytes.try_into().unwrap());
+
+        if take(MEMORY_IMAGE_MAGIC.len())? != MEMORY_IMAGE_MAGIC {
+            return Err(format!("{:?} is not a memory image", path));
+        }
+        let capacity = read_u32(take(4)?) as usize;
+        let total_capacity = self.memory_unit.get_data_len() + self.memory_unit.get_mem_capacity();
+        if capacity > total_capacity {
+            return Err(format!("Memory image needs a capacity of {} bytes but the RAM only has {}", capacity, total_capacity));
+        }
+        let data_base = read_u32(take(4)?);
+
+        let mut symbols = Vec::new();
+        let symbol_count = read_u32(take(4)?);
+        for _ in 0..symbol_count {
+            let name_length = u16::from_le_bytes(take(2)?.try_into().unwrap()) as usize;
+            let label = String::from_utf8(take(name_length)?.to_vec()).map_err(|_| format!("Memory image {:?} has an invalid symbol name", path))?;
+            let offset = read_u32(take(4)?);
+            let width = match take(1)?[0] {
+                1 => DataWidth::Byte,
+                2 => DataWidth::Word,
+                4 => DataWidth::Dword,
+                width => return Err(format!("Memory image {:?} has an invalid width {} for symbol {:?}", path, width, label)),
+            };
+            symbols.push((label, Symbol { offset, width }));
+        }
+
+        let data_length = read_u32(take(4)?) as usize;
+        let data = take(data_length)?.to_vec();
+        if data_length > total_capacity {
+            return Err(format!("Memory image holds {} bytes but the RAM only has {}", data_length, total_capacity));
+        }
+
+        self.memory_unit.data_bus.capacity = total_capacity - data.len();
+        self.memory_unit.data_bus.data = data;
+        self.memory_unit.data_base = data_base;
+        // Redefine the symbols in place so ids interned in the code section stay valid
+        self.memory_unit.symbols.clear();
+        for (label, symbol) in symbols {
+            self.memory_unit.symbols.define(&label, symbol);
+        }
+        Ok(())
+    }
+
+    fn display_registers(&self) {
+        self.registers.GP.iter().for_each(|reg| {
+            println!("{:?}", reg);
+        });
+    }
+}
+
+#[derive(Debug)]
+/// Several cores running their own code over one shared main memory.
+///
+/// Each core is a `CPU` with its own registers, flags and code section. The first core holds the shared memory
+/// and lends it to the others for each instruction they execute, so every core must be built from the same data
+/// section, data base and environment.
+///
+/// Cores take turns one instruction at a time, in the order they were given, skipping the ones that have stopped.
+/// An instruction sees the writes of every instruction executed before it, whichever core executed them,
+/// and nothing makes a sequence of instructions atomic.
+pub struct Machine {
+    cores: Vec<CPU>,
+    /// How each core stopped, once it has
+    stopped: Vec<Option<StopReason>>,
+}
+
+impl Machine {
+    /// Puts the cores together over the memory of the first one.
+    ///
+    /// Fails if a core's data section, data base or environment differs from the first core's,
+    /// as its labels wouldn't resolve to the same memory.
+    pub fn new(cores: Vec<CPU>) -> Result<Machine, BuildError> {
+        if let Some(first) = cores.first()
+            && let Some(index) = cores.iter().position(|core| {
+                core.memory_unit.data_section != first.memory_unit.data_section
+                    || core.memory_unit.data_base != first.memory_unit.data_base
+                    || core.environment != first.environment
+            }) {
+            return Err(BuildError::CoreLayoutMismatch(index));
+        }
+        let stopped = vec![None; cores.len()];
+        Ok(Machine { cores, stopped })
+    }
+
+    /// The core at the index, reading the shared memory if it is the first one
+    pub fn core(&self, index: usize) -> Option<&CPU> {
+        self.cores.get(index)
+    }
+
+    /// Reads the current value of a data section label from the shared memory
+    pub fn read_label(&self, label: &str) -> Result<Data, CpuError> {
+        match self.cores.first() {
+            Some(core) => core.read_label(label),
+            None => Err(CpuError::UndeclaredLabel(label.to_string(), None)),
+        }
+    }
+
+    /// Executes one instruction of every core that hasn't stopped, in order.
+    ///
+    /// Returns whether any core can go on, or the index of the core that faulted with its fault.
+    pub fn step(&mut self) -> Result<bool, (usize, Fault)> {
+        for index in 0..self.cores.len() {
+            if self.stopped[index].is_some() {
+                continue;
+            }
+            let reason = self.step_core(index).map_err(|fault| (index, fault))?;
+            if !matches!(reason, StopReason::Stepped | StopReason::Yielded) {
+                self.stopped[index] = Some(reason);
+            }
+        }
+        Ok(self.stopped.iter().any(|reason| reason.is_none()))
+    }
+
+    /// Steps the cores until every one of them has stopped, returning how each one did.
+    ///
+    /// Each core's instruction limit applies to the instructions it executes itself.
+    pub fn run(&mut self) -> Result<Vec<StopReason>, (usize, Fault)> {
+        let first_instructions: Vec<u64> = self.cores.iter().map(|core| core.instruction_count).collect();
+        while self.step()? {
+            for (index, core) in self.cores.iter_mut().enumerate() {
+                if let Err(error) = core.check_instruction_limit(first_instructions[index]) {
+                    return Err((index, core.fault(error)));
+                }
+            }
+        }
+        Ok(self.stopped.iter().flatten().cloned().collect())
+    }
+
+    /// Steps a single core over the shared memory
+    fn step_core(&mut self, index: usize) -> Result<StopReason, Fault> {
+        let (first, rest) = self.cores.split_at_mut(1);
+        if index == 0 {
+            return first[0].step();
+        }
+        let (shared, core) = (&mut first[0].memory_unit.data_bus.data, &mut rest[index - 1]);
+        std::mem::swap(shared, &mut core.memory_unit.data_bus.data);
+        let reason = core.step();
+        std::mem::swap(shared, &mut core.memory_unit.data_bus.data);
+        reason
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A turn of a program on a `Scheduler`
+pub struct Slice {
+    /// Index of the program's CPU
+    pub cpu: usize,
+    /// Instructions executed by every program before the slice started
+    pub start: u64,
+    /// Instructions executed by the program during the slice
+    pub instructions: u64,
+}
+
+#[derive(Debug)]
+/// Runs several programs time-sliced on their own CPUs, without interrupts.
+///
+/// The programs take turns round-robin, in the order they were given. Each turn, a slice, runs the program
+/// for the quantum of instructions, or less if the program stops or yields during it. A stopped program leaves the
+/// rotation with its outcome, the others go on in the same order.
+pub struct Scheduler {
+    cpus: Vec<CPU>,
+    quantum: u64,
+    slices: Vec<Slice>,
+}
+
+impl Scheduler {
+    /// Schedules the programs of the CPUs, with a quantum of at least one instruction
+    pub fn new(cpus: Vec<CPU>, quantum: u64) -> Scheduler {
+        Scheduler { cpus, quantum: quantum.max(1), slices: Vec::new() }
+    }
+
+    /// The CPU of the program at the index
+    pub fn cpu(&self, index: usize) -> Option<&CPU> {
+        self.cpus.get(index)
+    }
+
+    /// Slices executed so far, in the order they ran
+    pub fn slices(&self) -> &[Slice] {
+        &self.slices
+    }
+
+    /// Runs the programs until every one of them has stopped, returning their outcomes in order.
+    ///
+    /// Like `CPU::run`, a program stops when it exits, when a hook stops it or when it faults, including
+    /// by reaching its instruction limit. The duration of an outcome is measured by the program's clock
+    /// from the start of the scheduler's run.
+    pub fn run(&mut self) -> Vec<Result<RunOutcome, Fault>> {
+        let starts: Vec<(Duration, u64, bool)> = self.cpus.iter()
+            .map(|cpu| (cpu.clock.now(), cpu.instruction_count, !cpu.validate().is_empty()))
+            .collect();
+        let mut outcomes: Vec<Option<Result<RunOutcome, Fault>>> = self.cpus.iter().map(|_| None).collect();
+        let mut executed = 0;
+        while outcomes.iter().any(Option::is_none) {
+            for (index, cpu) in self.cpus.iter_mut().enumerate() {
+                if outcomes[index].is_some() {
+                    continue;
+                }
+                let (start, first_instruction, static_warnings) = starts[index];
+                let mut slice = Slice { cpu: index, start: executed, instructions: 0 };
+                let mut result = None;
+                let mut yielded = false;
+                while slice.instructions < self.quantum && result.is_none() && !yielded {
+                    let before = cpu.instruction_count;
+                    let reason = cpu.step();
+                    yielded = matches!(reason, Ok(StopReason::Yielded));
+                    let stopped = match reason {
+                        Ok(StopReason::Stepped | StopReason::Yielded) => match cpu.check_instruction_limit(first_instruction) {
+                            Err(error) => Some(Err(cpu.fault(error))),
+                            // Stop right after the last instruction rather than in a slice of its own
+                            Ok(()) => cpu.finished().map(|_| Ok(())),
+                        },
+                        Ok(_) => Some(Ok(())),
+                        Err(fault) => Some(Err(fault)),
+                    };
+                    slice.instructions += cpu.instruction_count - before;
+                    result = stopped.map(|stopped| stopped.map(|()| cpu.outcome(start, static_warnings)));
+                }
+                executed += slice.instructions;
+                self.slices.push(slice);
+                outcomes[index] = result;
+            }
+        }
+        outcomes.into_iter().flatten().collect()
+    }
+}
+
+/// Source of the choices made by the fuzzing generators, taken from the bytes of a fuzzer's input.
+///
+/// Every choice consumes input bytes, and once they run out every choice is the first option,
+/// so any input generates something, even an empty one.
+pub struct FuzzInput<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> FuzzInput<'a> {
+    pub fn new(bytes: &'a [u8]) -> FuzzInput<'a> {
+        FuzzInput { bytes }
+    }
+
+    /// The next input byte, 0 once the input is exhausted
+    pub fn byte(&mut self) -> u8 {
+        match self.bytes.split_first() {
+            Some((byte, rest)) => {
+                self.bytes = rest;
+                *byte
+            },
+            None => 0,
+        }
+    }
+
+    /// The next four input bytes as a little endian number
+    pub fn u32(&mut self) -> u32 {
+        u32::from_le_bytes([self.byte(), self.byte(), self.byte(), self.byte()])
+    }
+
+    /// One of `count` options, at most 256, 0 if there are none
+    pub fn choose(&mut self, count: usize) -> usize {
+        match count {
+            0 => 0,
+            count => self.byte() as usize % count,
+        }
+    }
+}
+
+impl Data {
+    /// Generates data of any width and value
+    pub fn arbitrary(input: &mut FuzzInput) -> Data {
+        let width = [DataWidth::Byte, DataWidth::Word, DataWidth::Dword][input.choose(3)];
+        Data::from_u32(width, input.u32())
+    }
+}
+
+impl Operand {
+    /// Generates an operand of any kind, whose memory operands and label offsets reference one of the labels.
+    ///
+    /// Without labels, no memory operand or label offset is generated.
+    pub fn arbitrary(input: &mut FuzzInput, labels: &[String]) -> Operand {
+        let register = |input: &mut FuzzInput| Register::all()[input.choose(8)].clone();
+        let width = |input: &mut FuzzInput| [DataWidth::Byte, DataWidth::Word, DataWidth::Dword][input.choose(3)];
+        // Mostly small displacements and jumps, which land near labels and instructions
+        let offset = |input: &mut FuzzInput| match input.choose(4) {
+            0 => input.u32() as i32,
+            _ => input.byte() as i8 as i32,
+        };
+        let kinds = if labels.is_empty() { 3 } else { 5 };
+        match input.choose(kinds) {
+            0 => Operand::Register(register(input)),
+            1 => Operand::Immediate(Data::arbitrary(input)),
+            2 => Operand::JmpRel(offset(input)),
+            3 => Operand::LabelOffset(labels[input.choose(labels.len())].clone()),
+            _ => {
+                let label = labels[input.choose(labels.len())].clone();
+                Operand::Memory(match input.choose(4) {
+                    0 => MemOp::Address(label),
+                    1 => MemOp::Label(label),
+                    2 => MemOp::Ptr(width(input), label, offset(input)),
+                    _ => MemOp::Indirect(width(input), register(input), offset(input)),
+                })
+            },
+        }
+    }
+}
+
+impl Instruction {
+    /// Generates any instruction of the instruction set, supported or not, with an operand count it accepts
+    /// but operands of any kind, see `Operand::arbitrary`
+    pub fn arbitrary(input: &mut FuzzInput, labels: &[String]) -> Instruction {
+        let opcode = IS::all()[input.choose(IS::all().len())].clone();
+        let counts = opcode.info().operand_count;
+        let count = counts.start() + input.choose((counts.end() - counts.start() + 1) as usize) as u8;
+        let operands = (0..count).map(|_| Operand::arbitrary(input, labels)).collect();
+        Instruction::new(opcode, operands)
+    }
+}
+
+/// Generates a program from a fuzzer's input: a data section of up to 8 labels
+/// and up to 32 instructions whose memory operands reference them.
+///
+/// A fuzz target builds a CPU for the program and runs it under an instruction limit, where the only
+/// acceptable outcomes are a build error, an exit, or a fault, such as reaching the limit. Never a panic.
+pub fn arbitrary_program(bytes: &[u8]) -> (HashMap<String, Data>, Vec<Instruction>) {
+    let mut input = FuzzInput::new(bytes);
+    let labels: Vec<String> = (0..input.choose(9)).map(|index| format!("label{}", index)).collect();
+    let data_section = labels.iter().map(|label| (label.clone(), Data::arbitrary(&mut input))).collect();
+    let code_section = (0..input.choose(33)).map(|_| Instruction::arbitrary(&mut input, &labels)).collect();
+    (data_section, code_section)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a CPU running quietly with the data section and code
+    fn cpu(data: &[(&str, Data)], code: Vec<Instruction>) -> CPU {
+        let data = data.iter().map(|(label, data)| (label.to_string(), data.clone())).collect();
+        CpuBuilder::new(data, code).verbosity(Verbosity::Quiet).build().unwrap()
+    }
+
+    fn reg(register: Register) -> Operand {
+        Operand::Register(register)
+    }
+
+    fn mem(label: &str) -> Operand {
+        Operand::Memory(MemOp::Address(label.to_string()))
+    }
+
+    fn word(value: u16) -> Operand {
+        Operand::Immediate(Data::Word(value))
+    }
+
+    fn flag(cpu: &CPU, name: &str) -> bool {
+        let index = FLAG_NAMES.iter().position(|flag| *flag == name).unwrap();
+        cpu.flags_word() & (1 << index) != 0
+    }
+
+    #[test]
+    fn add_and_sub_write_memory_destination_with_immediate_source() {
+        let mut cpu = cpu(&[("num", Data::Word(10)), ("num2", Data::Word(20))], vec![
+            Instruction::new(IS::Add, vec![mem("num"), word(5)]),
+            Instruction::new(IS::Sub, vec![mem("num2"), word(0x000F)]),
+        ]);
+        cpu.run().unwrap();
+        assert_eq!(cpu.read_label("num").unwrap(), Data::Word(15));
+        assert_eq!(cpu.read_label("num2").unwrap(), Data::Word(5));
+        assert!(!flag(&cpu, "ZF"));
+        assert!(!flag(&cpu, "CF"));
+    }
+
+    #[test]
+    fn sub_memory_destination_sets_flags_from_written_result() {
+        let mut cpu = cpu(&[("num", Data::Byte(3))], vec![
+            Instruction::new(IS::Sub, vec![mem("num"), Operand::Immediate(Data::Byte(4))]),
+        ]);
+        cpu.run().unwrap();
+        assert_eq!(cpu.read_label("num").unwrap(), Data::Byte(0xFF));
+        assert!(flag(&cpu, "CF"));
+        assert!(flag(&cpu, "SF"));
+    }
+
+    #[test]
+    fn mixed_size_labels_read_back_their_initial_values() {
+        let cpu = cpu(&[("a", Data::Byte(0x12)), ("b", Data::Word(0x3456)), ("c", Data::Dword(0x789A_BCDE))], vec![]);
+        assert_eq!(cpu.read_label("a").unwrap(), Data::Byte(0x12));
+        assert_eq!(cpu.read_label("b").unwrap(), Data::Word(0x3456));
+        assert_eq!(cpu.read_label("c").unwrap(), Data::Dword(0x789A_BCDE));
+        // Labels are packed at their true byte offsets
+        assert_eq!(cpu.memory_unit.symbol("b").unwrap().offset, 1);
+        assert_eq!(cpu.memory_unit.symbol("c").unwrap().offset, 3);
+    }
+
+    /// Renders the final state of a fixture: the `MachineState` followed by every data label's value
+    fn render_state(cpu: &CPU) -> String {
+        let mut output = format!("{}\n", cpu.machine_state());
+        let mut labels: Vec<(&String, &Symbol)> = cpu.memory_unit.symbols.defined();
+        labels.sort_by_key(|(label, _)| *label);
+        for (label, symbol) in labels {
+            let value = cpu.read_label(label).unwrap();
+            output += &format!("[{}] = {}\n", label, format_value(value.get_value(), symbol.width, Base::Hex));
+        }
+        output
+    }
+
+    /// Runs a fixture program and compares its final state with `tests/golden/<name>.txt`.
+    ///
+    /// Run the tests with `UPDATE_FIXTURES=1` to rewrite the expectations after an intended behavior change.
+    fn assert_golden(name: &str, data: &[(&str, Data)], code: Vec<Instruction>) {
+        let mut cpu = cpu(data, code);
+        cpu.run().unwrap();
+        compare_golden(name, &render_state(&cpu));
+    }
+
+    /// Compares text rendered by a test with `tests/golden/<name>.txt`, rewriting it with `UPDATE_FIXTURES=1`
+    fn compare_golden(name: &str, actual: &str) {
+        let path = format!("{}/tests/golden/{}.txt", env!("CARGO_MANIFEST_DIR"), name);
+        if std::env::var_os("UPDATE_FIXTURES").is_some() {
+            std::fs::write(&path, actual).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&path).unwrap_or_else(|_| panic!("Missing golden state {}, run with UPDATE_FIXTURES=1", path));
+        assert_eq!(actual, expected, "output of {} differs from {}", name, path);
+    }
+
+    #[test]
+    fn golden_mov() {
+        assert_golden("mov", &[("num", Data::Word(10)), ("small", Data::Byte(0xFF)), ("wide", Data::Dword(0x1234_5678))], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(300)]),
+            Instruction::new(IS::Mov, vec![reg(Register::BX), mem("num")]),
+            Instruction::new(IS::Mov, vec![mem("num"), reg(Register::AX)]),
+            Instruction::new(IS::Mov, vec![reg(Register::CX), mem("small")]),
+            Instruction::new(IS::Mov, vec![reg(Register::EAX), mem("wide")]),
+            Instruction::new(IS::Mov, vec![reg(Register::DX), Operand::LabelOffset("wide".to_string())]),
+        ]);
+    }
+
+    #[test]
+    fn golden_add() {
+        assert_golden("add", &[("total", Data::Dword(0xFFFF_FFF0))], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(0x7FFF)]),
+            Instruction::new(IS::Add, vec![reg(Register::AX), word(1)]),
+            Instruction::new(IS::Mov, vec![reg(Register::EBX), Operand::Immediate(Data::Dword(0x20))]),
+            Instruction::new(IS::Add, vec![mem("total"), reg(Register::EBX)]),
+            Instruction::new(IS::Add, vec![reg(Register::CX), mem("total")]),
+        ]);
+    }
+
+    #[test]
+    fn golden_sub() {
+        assert_golden("sub", &[("num", Data::Word(10)), ("num2", Data::Word(20)), ("result", Data::Word(0))], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(300)]),
+            Instruction::new(IS::Mov, vec![reg(Register::BX), mem("num")]),
+            Instruction::new(IS::Add, vec![reg(Register::CX), reg(Register::AX)]),
+            Instruction::new(IS::Sub, vec![reg(Register::CX), reg(Register::BX)]),
+            Instruction::new(IS::Mov, vec![mem("result"), reg(Register::CX)]),
+            Instruction::new(IS::Sub, vec![mem("num2"), word(0x000F)]),
+            Instruction::new(IS::Sub, vec![reg(Register::DX), word(1)]),
+        ]);
+    }
+
+    #[test]
+    fn golden_memory() {
+        let ptr = |width, displacement| Operand::Memory(MemOp::Ptr(width, "buffer".to_string(), displacement));
+        assert_golden("memory", &[("buffer", Data::Dword(0)), ("tail", Data::Word(0xBEEF))], vec![
+            Instruction::new(IS::Mov, vec![ptr(DataWidth::Byte, 0), Operand::Immediate(Data::Byte(0x11))]),
+            Instruction::new(IS::Mov, vec![ptr(DataWidth::Byte, 3), Operand::Immediate(Data::Byte(0x44))]),
+            Instruction::new(IS::Mov, vec![ptr(DataWidth::Word, 1), word(0x3322)]),
+            Instruction::new(IS::Mov, vec![reg(Register::AX), ptr(DataWidth::Word, 4)]),
+            Instruction::new(IS::Add, vec![ptr(DataWidth::Byte, 5), Operand::Immediate(Data::Byte(1))]),
+        ]);
+    }
+
+    #[test]
+    fn golden_syscall() {
+        let syscall = || Instruction::new(IS::Syscall, vec![]);
+        assert_golden("syscall", &[("source", Data::Dword(0x0403_0201)), ("target", Data::Dword(0))], vec![
+            // memcpy(target, source, 4)
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(syscall_abi::SYS_MEMCPY as u16)]),
+            Instruction::new(IS::Mov, vec![reg(Register::BX), Operand::LabelOffset("source".to_string())]),
+            Instruction::new(IS::Mov, vec![reg(Register::CX), Operand::LabelOffset("target".to_string())]),
+            Instruction::new(IS::Mov, vec![reg(Register::DX), word(4)]),
+            syscall(),
+            // memset(source, 0xAA, 2)
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(syscall_abi::SYS_MEMSET as u16)]),
+            Instruction::new(IS::Mov, vec![reg(Register::BX), word(0xAA)]),
+            Instruction::new(IS::Mov, vec![reg(Register::CX), Operand::LabelOffset("source".to_string())]),
+            Instruction::new(IS::Mov, vec![reg(Register::DX), word(2)]),
+            syscall(),
+            // exit(3)
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(syscall_abi::SYS_EXIT as u16)]),
+            Instruction::new(IS::Mov, vec![reg(Register::BX), word(3)]),
+            syscall(),
+        ]);
+    }
+
+    #[test]
+    fn counters_syscall_reports_counters_or_fails_outside_memory() {
+        let program = |buffer| vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(syscall_abi::SYS_COUNTERS as u16)]),
+            Instruction::new(IS::Mov, vec![reg(Register::CX), buffer]),
+            Instruction::new(IS::Syscall, vec![]),
+        ];
+        // Eight adjacent Dwords make room for the four u64 counters
+        let labels: Vec<String> = (0..8).map(|i| format!("c{}", i)).collect();
+        let buffer: Vec<(&str, Data)> = labels.iter().map(|label| (label.as_str(), Data::Dword(0))).collect();
+        let mut cpu = cpu(&buffer, program(Operand::LabelOffset("c0".to_string())));
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers.get_register(Register::AX).get_value(), 32);
+        assert_eq!(cpu.memory_unit.read_bytes(0, 8).unwrap(), cpu.instruction_count.to_le_bytes());
+
+        let mut cpu = self::cpu(&[("num", Data::Word(0))], program(word(1000)));
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers.get_register(Register::AX).get_value(), syscall_abi::ERROR as u32);
+    }
+
+    #[test]
+    fn memcpy_handles_overlaps_like_memmove_and_memset_fills_within_bounds() {
+        let run = |number: u8, arg1: u16, arg2: u16, length: u16| {
+            let buffer = [
+                ("b0", Data::Dword(0x0403_0201)),
+                ("b1", Data::Dword(0x0807_0605)),
+                ("b2", Data::Dword(0x0C0B_0A09)),
+                ("b3", Data::Dword(0x100F_0E0D)),
+            ];
+            let mut cpu = cpu(&buffer, vec![
+                Instruction::new(IS::Mov, vec![reg(Register::AX), word(number as u16)]),
+                Instruction::new(IS::Mov, vec![reg(Register::BX), word(arg1)]),
+                Instruction::new(IS::Mov, vec![reg(Register::CX), word(arg2)]),
+                Instruction::new(IS::Mov, vec![reg(Register::DX), word(length)]),
+                Instruction::new(IS::Syscall, vec![]),
+            ]);
+            cpu.run().unwrap();
+            (cpu.registers.get_register(Register::AX).get_value(), cpu.memory_unit.read_bytes(0, 16).unwrap())
+        };
+        let original: Vec<u8> = (1..=16).collect();
+
+        // Forward overlap: the destination starts inside the source
+        let (result, bytes) = run(syscall_abi::SYS_MEMCPY, 0, 2, 6);
+        assert_eq!(result, 6);
+        assert_eq!(bytes, [1, 2, 1, 2, 3, 4, 5, 6, 9, 10, 11, 12, 13, 14, 15, 16]);
+
+        // Backward overlap: the source starts inside the destination
+        let (result, bytes) = run(syscall_abi::SYS_MEMCPY, 2, 0, 6);
+        assert_eq!(result, 6);
+        assert_eq!(bytes, [3, 4, 5, 6, 7, 8, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]);
+
+        // Fill with the low byte of BX
+        let (result, bytes) = run(syscall_abi::SYS_MEMSET, 0x12AA, 5, 3);
+        assert_eq!(result, 3);
+        assert_eq!(bytes, [1, 2, 3, 4, 5, 0xAA, 0xAA, 0xAA, 9, 10, 11, 12, 13, 14, 15, 16]);
+
+        // A source, destination or fill running one byte past memory is rejected without writing
+        for (number, arg1, arg2, length) in [
+            (syscall_abi::SYS_MEMCPY, 9, 0, 8),
+            (syscall_abi::SYS_MEMCPY, 0, 9, 8),
+            (syscall_abi::SYS_MEMSET, 0xAA, 15, 2),
+        ] {
+            assert_eq!(run(number, arg1, arg2, length), (syscall_abi::ERROR as u32, original.clone()));
+        }
+        assert_eq!(run(syscall_abi::SYS_MEMSET, 0xAA, 14, 2).0, 2);
+    }
+
+    /// DAA/DAS as written in the Intel SDM pseudocode, returning AL, CF and AF
+    fn reference_decimal_adjust(al: u8, aux: bool, carry: bool, subtract: bool) -> (u8, bool, bool) {
+        let (old_al, old_carry) = (al, carry);
+        let (mut al, mut carry, mut aux_out) = (al, false, false);
+        if al & 0x0F > 9 || aux {
+            let (adjusted, borrow_or_carry) = match subtract {
+                true => al.overflowing_sub(6),
+                false => al.overflowing_add(6),
+            };
+            al = adjusted;
+            carry = old_carry || borrow_or_carry;
+            aux_out = true;
+        }
+        if old_al > 0x99 || old_carry {
+            al = match subtract {
+                true => al.wrapping_sub(0x60),
+                false => al.wrapping_add(0x60),
+            };
+            carry = true;
+        } else if !subtract {
+            // Only DAA clears CF here, DAS keeps the borrow of the low digit adjustment
+            carry = false;
+        }
+        (al, carry, aux_out)
+    }
+
+    #[test]
+    fn decimal_adjust_matches_reference_for_every_input() {
+        for (opcode, subtract) in [(IS::Daa, false), (IS::Das, true)] {
+            for al in 0..=255u8 {
+                for (aux, carry) in [(false, false), (false, true), (true, false), (true, true)] {
+                    let mut cpu = cpu(&[], vec![Instruction::new(opcode.clone(), vec![])]);
+                    // AH must be left untouched
+                    cpu.registers.get_register(Register::AX).set_value(Data::Word(0xA500 | al as u16));
+                    cpu.flags[1].set_value(aux as u8);
+                    cpu.flags[8].set_value(carry as u8);
+                    cpu.run().unwrap();
+
+                    let (expected_al, expected_carry, expected_aux) = reference_decimal_adjust(al, aux, carry, subtract);
+                    let case = format!("{:?} al={:#04X} af={} cf={}", opcode, al, aux, carry);
+                    assert_eq!(cpu.registers.get_register(Register::AX).get_value(), 0xA500 | expected_al as u32, "{}", case);
+                    assert_eq!(flag(&cpu, "CF"), expected_carry, "{}", case);
+                    assert_eq!(flag(&cpu, "AF"), expected_aux, "{}", case);
+                    assert_eq!(flag(&cpu, "ZF"), expected_al == 0, "{}", case);
+                    assert_eq!(flag(&cpu, "SF"), expected_al & 0x80 != 0, "{}", case);
+                    assert_eq!(flag(&cpu, "PF"), expected_al.count_ones() % 2 == 0, "{}", case);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn syscall_arguments_count_as_reads() {
+        let cpu = cpu(&[], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(syscall_abi::SYS_EXIT as u16)]),
+            Instruction::new(IS::Add, vec![reg(Register::BX), word(1)]),
+            Instruction::new(IS::Syscall, vec![]),
+            Instruction::new(IS::Mov, vec![reg(Register::BX), word(0)]),
+        ]);
+        let warnings = cpu.validate();
+        assert!(!warnings.iter().any(|warning| matches!(warning, Warning::UnusedResult(..))), "{:?}", warnings);
+        // Only the ADD reads BX before it is written, the syscall doesn't report unwritten arguments
+        assert_eq!(warnings, vec![Warning::ReadBeforeWrite(1, Register::BX)]);
+    }
+
+    #[test]
+    fn uninitialized_tracking_reports_registers_read_before_write() {
+        let add = || Instruction::new(IS::Add, vec![reg(Register::CX), reg(Register::AX)]);
+        let mut cpu = cpu(&[], vec![add()]);
+        cpu.track_uninitialized(UninitializedPolicy::Warn);
+        cpu.run().unwrap();
+        assert_eq!(cpu.warnings, vec![Warning::UninitializedRegister(0, Register::CX), Warning::UninitializedRegister(0, Register::AX)]);
+
+        let mut cpu = self::cpu(&[], vec![Instruction::new(IS::Mov, vec![reg(Register::AX), word(5)]), add()]);
+        cpu.track_uninitialized(UninitializedPolicy::Warn);
+        cpu.run().unwrap();
+        assert_eq!(cpu.warnings, vec![Warning::UninitializedRegister(1, Register::CX)]);
+    }
+
+    #[test]
+    fn offset_operands_load_label_offsets_and_reject_undeclared_labels() {
+        let mut cpu = cpu(&[("first", Data::Word(1)), ("msg", Data::Dword(0))], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::CX), Operand::LabelOffset("msg".to_string())]),
+        ]);
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers.get_register(Register::CX).get_value(), 2);
+
+        // The offset is the buffer of the write syscall, printing the label's bytes rather than its neighbour's
+        let output = SharedOutput::default();
+        let data = HashMap::from([
+            ("first".to_string(), Data::Word(u16::from_le_bytes(*b"no"))),
+            ("msg".to_string(), Data::Dword(u32::from_le_bytes(*b"Hi!\n"))),
+        ]);
+        let mut writing = CpuBuilder::new(data, vec![
+            Instruction::new(IS::Mov, vec![reg(syscall_abi::NUMBER), word(syscall_abi::SYS_WRITE as u16)]),
+            Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG1), word(1)]),
+            Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG2), Operand::LabelOffset("msg".to_string())]),
+            Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG3), word(4)]),
+            Instruction::new(IS::Syscall, vec![]),
+        ]).verbosity(Verbosity::Quiet).guest_output(output.clone()).build().unwrap();
+        writing.run().unwrap();
+        assert_eq!(*output.0.borrow(), b"Hi!\n");
+
+        let data = HashMap::from([("msg".to_string(), Data::Dword(0))]);
+        let code = vec![Instruction::new(IS::Mov, vec![reg(Register::CX), Operand::LabelOffset("undefined".to_string())])];
+        match CpuBuilder::new(data, code).verbosity(Verbosity::Quiet).build() {
+            Err(BuildError::UndeclaredLabel(0, label, None)) => assert_eq!(label, "undefined"),
+            other => panic!("expected an undeclared label error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn out_of_bounds_memory_read_faults_with_context() {
+        let mut cpu = cpu(&[("msg", Data::Word(0))], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(syscall_abi::SYS_WRITE as u16)]),
+            Instruction::new(IS::Mov, vec![reg(Register::CX), word(1000)]),
+            Instruction::new(IS::Mov, vec![reg(Register::DX), word(10)]),
+            Instruction::new(IS::Syscall, vec![]),
+        ]);
+        let fault = cpu.run().unwrap_err();
+        assert_eq!(fault.context.ip, 3);
+        assert_eq!(fault.context.instruction, "syscall");
+        assert!(matches!(fault.error, CpuError::Syscall(ref message) if message.contains("MemoryOutOfBounds(1000, 10)")), "{:?}", fault.error);
+        assert_eq!(fault.context().last_memory_access(), Some(MemoryAccess { offset: 1000, length: 10, write: false }));
+
+        assert!(matches!(cpu.memory_unit.read_bytes(usize::MAX, 2), Err(CpuError::MemoryOutOfBounds(usize::MAX, 2))));
+        assert!(matches!(cpu.memory_unit.write_bytes(1, &[0, 0]), Err(CpuError::MemoryOutOfBounds(1, 2))));
+    }
+
+    #[test]
+    fn a_sequential_sum_hits_the_cache_where_a_strided_one_misses() {
+        // Sums `count` words of a 32 byte array, `stride` bytes apart
+        let sum = |stride: u32, count: u16| {
+            let words: Vec<(String, Data)> = (0..16).map(|index| (format!("w{:02}", index), Data::Word(index))).collect();
+            let words: Vec<(&str, Data)> = words.iter().map(|(label, data)| (label.as_str(), data.clone())).collect();
+            let mut summing = cpu(&words, vec![
+                Instruction::new(IS::Mov, vec![reg(Register::EBX), Operand::LabelOffset("w00".to_string())]),
+                Instruction::new(IS::Mov, vec![reg(Register::CX), word(0)]),
+                Instruction::new(IS::Add, vec![reg(Register::AX), Operand::Memory(MemOp::Indirect(DataWidth::Word, Register::EBX, 0))]),
+                Instruction::new(IS::Add, vec![reg(Register::EBX), Operand::Immediate(Data::Dword(stride))]),
+                Instruction::new(IS::Add, vec![reg(Register::CX), word(1)]),
+                Instruction::new(IS::Cmp, vec![reg(Register::CX), word(count)]),
+                Instruction::new(IS::Jnz, vec![word(2)]),
+            ]);
+            summing.record_accesses(64);
+            summing.run().unwrap();
+            summing
+        };
+        // Two lines of 8 bytes, each holding four words
+        let config = CacheConfig { size: 16, line_size: 8 };
+
+        let sequential = sum(2, 16);
+        assert_eq!(sequential.register(Register::AX), (0..16).sum::<u32>());
+        let start = sequential.symbol("w00").unwrap().offset as usize;
+        assert_eq!(sequential.access_log().len(), 16);
+        assert_eq!(sequential.access_log()[1], MemoryAccess { offset: start + 2, length: 2, write: false });
+        assert_eq!(classify_accesses(sequential.access_log()), AccessPattern::Sequential);
+        let stats = analyze_cache(sequential.access_log(), config);
+        assert_eq!(stats, CacheStats { hits: 12, misses: 4 });
+        assert_eq!(stats.hit_rate(), 0.75);
+
+        // Every access lands on a line of its own
+        let strided = sum(8, 4);
+        assert_eq!(strided.register(Register::AX), 4 + 8 + 12);
+        assert_eq!(classify_accesses(strided.access_log()), AccessPattern::Strided(8));
+        let stats = analyze_cache(strided.access_log(), config);
+        assert_eq!(stats, CacheStats { hits: 0, misses: 4 });
+        assert_eq!(stats.hit_rate(), 0.0);
+        assert_eq!(strided.access_counts().iter().filter(|(_, count)| *count == 1).count(), 4);
+    }
+
+    #[test]
+    fn log_syscall_records_messages_and_rejects_buffers_outside_memory() {
+        let program = |offset| vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(syscall_abi::SYS_LOG as u16)]),
+            Instruction::new(IS::Mov, vec![reg(Register::BX), word(syscall_abi::LOG_WARN as u16)]),
+            Instruction::new(IS::Mov, vec![reg(Register::CX), offset]),
+            Instruction::new(IS::Mov, vec![reg(Register::DX), word(4)]),
+            Instruction::new(IS::Syscall, vec![]),
+        ];
+        let message = Data::Dword(u32::from_le_bytes(*b"hi!!"));
+        let mut cpu = cpu(&[("msg", message.clone())], program(Operand::LabelOffset("msg".to_string())));
+        cpu.run().unwrap();
+        assert_eq!(cpu.guest_logs().len(), 1);
+        assert_eq!((cpu.guest_logs()[0].level, cpu.guest_logs()[0].message.as_str()), (LogLevel::Warn, "hi!!"));
+
+        let mut cpu = self::cpu(&[("msg", message)], program(word(0xFFFE)));
+        let fault = cpu.run().unwrap_err();
+        assert!(matches!(fault.error, CpuError::Syscall(ref message) if message.contains("MemoryOutOfBounds")), "{:?}", fault.error);
+        assert!(cpu.guest_logs().is_empty());
+    }
+
+    #[test]
+    fn guest_logs_reach_the_observers_at_every_level_and_stay_out_of_the_program_output() {
+        struct Recorder(std::rc::Rc<RefCell<Vec<(usize, Event)>>>);
+        impl Observer for Recorder {
+            fn event(&mut self, context: &ExecContext, event: &Event) {
+                if event.kind() == EventKind::GuestLog {
+                    self.0.borrow_mut().push((context.ip, event.clone()));
+                }
+            }
+        }
+        let levels = [
+            (syscall_abi::LOG_DEBUG, "dbg.", LogLevel::Debug), (syscall_abi::LOG_INFO, "inf.", LogLevel::Info),
+            (syscall_abi::LOG_WARN, "wrn.", LogLevel::Warn), (syscall_abi::LOG_ERROR, "err.", LogLevel::Error),
+        ];
+        let mut data: HashMap<String, Data> = levels.iter().enumerate()
+            .map(|(index, (_, message, _))| (format!("m{}", index), Data::Dword(u32::from_le_bytes(message.as_bytes().try_into().unwrap()))))
+            .collect();
+        data.insert("out".to_string(), Data::Dword(u32::from_le_bytes(*b"out\n")));
+        let mut code = Vec::new();
+        for (index, (level, _, _)) in levels.iter().enumerate() {
+            code.extend([
+                Instruction::new(IS::Mov, vec![reg(syscall_abi::NUMBER), word(syscall_abi::SYS_LOG as u16)]),
+                Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG1), word(*level as u16)]),
+                Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG2), Operand::LabelOffset(format!("m{}", index))]),
+                Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG3), word(4)]),
+                Instruction::new(IS::Syscall, vec![]),
+            ]);
+        }
+        code.extend([
+            Instruction::new(IS::Mov, vec![reg(syscall_abi::NUMBER), word(syscall_abi::SYS_WRITE as u16)]),
+            Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG1), word(1)]),
+            Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG2), Operand::LabelOffset("out".to_string())]),
+            Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG3), word(4)]),
+            Instruction::new(IS::Syscall, vec![]),
+        ]);
+        let (events, output, json) = (std::rc::Rc::default(), SharedOutput::default(), SharedOutput::default());
+        let mut logging = CpuBuilder::new(data, code)
+            .verbosity(Verbosity::Result)
+            .output(std::io::sink())
+            .guest_output(output.clone())
+            .observer(Recorder(std::rc::Rc::clone(&events)))
+            .observer(JsonLinesObserver::new(json.clone()).only(&[EventKind::GuestLog]))
+            .build()
+            .unwrap();
+        logging.run().unwrap();
+
+        let expected: Vec<(usize, Event)> = levels.iter().enumerate()
+            .map(|(index, (_, message, level))| {
+                let instruction = index * 5 + 4;
+                (instruction, Event::GuestLog(GuestLog { instruction, level: *level, message: message.to_string() }))
+            })
+            .collect();
+        assert_eq!(*events.borrow(), expected);
+        let logs: Vec<Event> = logging.guest_logs().iter().cloned().map(Event::GuestLog).collect();
+        assert_eq!(logs, expected.into_iter().map(|(_, event)| event).collect::<Vec<Event>>());
+        assert_eq!(String::from_utf8(output.0.borrow().clone()).unwrap(), "out\n");
+        let json = String::from_utf8(json.0.borrow().clone()).unwrap();
+        assert_eq!(json.lines().nth(2), Some("{\"event\":\"guest_log\",\"index\":14,\"step\":15,\"level\":\"warn\",\"message\":\"wrn.\"}"));
+    }
+
+    /// Deterministic xorshift64 generator for randomized tests
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        /// A value in `0..bound`
+        fn below(&mut self, bound: u64) -> u64 {
+            self.next() % bound
+        }
+    }
+
+    /// A memory unit holding `length` bytes of main memory, all 0
+    fn memory_unit(length: usize) -> MemoryUnit {
+        let mut memory_unit = MemoryUnit::new(HashMap::new(), vec![]);
+        memory_unit.data_bus.data = vec![0; length];
+        memory_unit.data_bus.capacity -= length;
+        memory_unit
+    }
+
+    #[test]
+    fn memory_unit_accesses_stay_in_bounds_and_read_back_writes() {
+        let mut rng = XorShift(0x2545_F491_4F6C_DD1D);
+        for _ in 0..200 {
+            let length = rng.below(64) as usize;
+            let mut memory_unit = memory_unit(length);
+            let capacity = memory_unit.get_mem_capacity();
+            // Model of main memory the unit must agree with
+            let mut model = vec![0u8; length];
+            for _ in 0..100 {
+                let offset = rng.below(length as u64 + 8) as usize;
+                let width = [DataWidth::Byte, DataWidth::Word, DataWidth::Dword][rng.below(3) as usize];
+                let symbol = Symbol { offset: offset as u32, width };
+                let in_bounds = offset + width.bytes() <= length;
+                let bytes: Vec<u8> = (0..width.bytes()).map(|_| rng.next() as u8).collect();
+
+                let written = match rng.below(2) {
+                    0 => memory_unit.write_data(&symbol, bytes.clone()),
+                    _ => memory_unit.write_bytes(offset, &bytes),
+                };
+                assert_eq!(written.is_ok(), in_bounds, "write of {} bytes at {} in {} bytes", bytes.len(), offset, length);
+                if in_bounds {
+                    model[offset..offset + bytes.len()].copy_from_slice(&bytes);
+                    assert_eq!(memory_unit.read_data(&symbol).unwrap(), bytes);
+                } else {
+                    assert!(matches!(memory_unit.read_bytes(offset, width.bytes()), Err(CpuError::MemoryOutOfBounds(..))));
+                }
+                // Writes happen in place: memory neither grows nor loses capacity
+                assert_eq!(memory_unit.data_bus.data, model);
+                assert_eq!(memory_unit.get_mem_capacity(), capacity);
+            }
+        }
+    }
+
+    #[test]
+    fn memory_unit_writes_in_place_instead_of_appending() {
+        let mut memory_unit = memory_unit(4);
+        let capacity = memory_unit.get_mem_capacity();
+        memory_unit.write_data(&Symbol { offset: 1, width: DataWidth::Word }, vec![0xAA, 0xBB]).unwrap();
+        assert_eq!(memory_unit.data_bus.data, vec![0, 0xAA, 0xBB, 0]);
+        // A shorter value is zero-filled to the symbol's width
+        memory_unit.write_data(&Symbol { offset: 0, width: DataWidth::Dword }, vec![0x11]).unwrap();
+        assert_eq!(memory_unit.data_bus.data, vec![0x11, 0, 0, 0]);
+
+        // Regression: a write past the end used to be appended to memory, wherever its address pointed,
+        // without the appended bytes being taken from the capacity
+        assert!(memory_unit.write_data(&Symbol { offset: 4, width: DataWidth::Word }, vec![0xCC, 0xDD]).is_err());
+        assert_eq!(memory_unit.data_bus.data, vec![0x11, 0, 0, 0]);
+        assert_eq!(memory_unit.get_mem_capacity(), capacity);
+    }
+
+    #[test]
+    fn loading_labels_accounts_for_every_byte_of_capacity() {
+        let cpu = cpu(&[("a", Data::Byte(1)), ("b", Data::Word(2)), ("c", Data::Dword(3))], vec![]);
+        let report = cpu.memory_report();
+        assert_eq!(cpu.memory_unit.get_data_len(), 7);
+        assert_eq!(cpu.memory_unit.get_mem_capacity(), report.capacity - 7);
+        assert_eq!(report.used(), 7);
+    }
+
+    /// Position of each instruction in `IS::all()`.
+    ///
+    /// The match has no wildcard arm, so a new `IS` variant doesn't compile until it is given a position here,
+    /// and `every_instruction_is_listed` then fails until `IS::all()` lists it.
+    fn listed_position(opcode: &IS) -> usize {
+        match opcode {
+            IS::Mov => 0, IS::Add => 1, IS::Sub => 2,
+            IS::Mul => 3, IS::Div => 4, IS::And => 5,
+            IS::Or => 6, IS::Xor => 7, IS::Not => 8,
+            IS::Jmp => 9, IS::Daa => 10, IS::Das => 11,
+            IS::Aam => 12, IS::Aad => 13, IS::Syscall => 14,
+            IS::Cmp => 15, IS::Jz => 16, IS::Jnz => 17,
+            IS::Jc => 18, IS::Jnc => 19, IS::Call => 20,
+            IS::Ret => 21,
+            IS::AssertReg => 22, IS::AssertFlag => 23,
+        }
+    }
+
+    #[test]
+    fn every_instruction_is_listed() {
+        // One more than the largest position given by `listed_position`
+        const INSTRUCTION_COUNT: usize = 24;
+        assert_eq!(IS::all().len(), INSTRUCTION_COUNT);
+        for (position, opcode) in IS::all().iter().enumerate() {
+            assert_eq!(listed_position(opcode), position, "{:?}", opcode);
+        }
+    }
+
+    #[test]
+    fn instruction_metadata_comes_from_the_table() {
+        let mov = IS::Mov.info();
+        assert!(mov.supported);
+        assert_eq!(mov.operand_count, 2..=2);
+        assert_eq!(mov.operand_kinds, &[&[OperandKind::Register, OperandKind::Memory][..], &[OperandKind::Register, OperandKind::Memory, OperandKind::Immediate][..]]);
+        assert_eq!(mov.flags, 0);
+
+        let add = IS::Add.info();
+        assert_eq!(add.operand_count, 2..=2);
+        assert_eq!(add.flags, STATUS_FLAGS);
+        assert_eq!(IS::Add.flag_mask(), add.flags);
+
+        let syscall = IS::Syscall.info();
+        assert!(syscall.supported);
+        assert_eq!(syscall.operand_count, 0..=0);
+        assert_eq!(syscall.flags, 0);
+        assert_eq!(IS::Syscall.default_cost(), syscall.cost);
+
+        let cpu = cpu(&[], vec![]);
+        assert!(cpu.supported_instructions().contains(&IS::Mov));
+        assert!(!cpu.supported_instructions().contains(&IS::Mul));
+    }
+
+    /// Trace output sink whose contents the test reads back after the run
+    #[derive(Clone, Default)]
+    struct SharedOutput(std::rc::Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Runs a small program at the verbosity and returns the lines of trace output
+    fn trace_lines(verbosity: Verbosity) -> Vec<String> {
+        let output = SharedOutput::default();
+        let mut cpu = CpuBuilder::new(HashMap::new(), vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(5)]),
+            Instruction::new(IS::Add, vec![reg(Register::AX), word(1)]),
+        ]).verbosity(verbosity).output(output.clone()).build().unwrap();
+        cpu.run().unwrap();
+        let text = String::from_utf8(output.0.borrow().clone()).unwrap();
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn each_verbosity_level_includes_the_output_of_the_ones_below_it() {
+        assert!(trace_lines(Verbosity::Quiet).is_empty());
+
+        let result = trace_lines(Verbosity::Result);
+        assert_eq!(result.len(), 2, "{:?}", result);
+        assert!(result[0].starts_with("Registers: AX=0x0006 "), "{:?}", result);
+        assert_eq!(result[1], "Exit code: 0");
+
+        let instruction = trace_lines(Verbosity::Instruction);
+        assert_eq!(instruction.len(), 4, "{:?}", instruction);
+        assert!(instruction[0].starts_with("0000  mov ax, 5 "), "{:?}", instruction);
+        assert!(instruction[1].starts_with("0001  add ax, 1 "), "{:?}", instruction);
+        assert!(instruction[1].ends_with("; ax: 0x0005 -> 0x0006"), "{:?}", instruction);
+        assert_eq!(instruction[2..], result[..]);
+
+        let full = trace_lines(Verbosity::Full);
+        assert!(full.len() > instruction.len(), "{:?}", full);
+        let mut remaining = full.iter();
+        for line in &instruction {
+            assert!(remaining.any(|full_line| full_line == line), "{:?} missing from {:?}", line, full);
+        }
+    }
+
+    #[test]
+    fn colored_trace_highlights_changed_registers_and_warnings_with_escape_sequences() {
+        let output = SharedOutput::default();
+        let mut colored = CpuBuilder::new(HashMap::new(), vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(5)]),
+            Instruction::new(IS::Add, vec![reg(Register::AX), reg(Register::CX)]),
+        ]).verbosity(Verbosity::Instruction).color(true).output(output.clone()).build().unwrap();
+        colored.track_uninitialized(UninitializedPolicy::Warn);
+        colored.run().unwrap();
+        let text = String::from_utf8(output.0.borrow().clone()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[..3], [
+            "0000  mov ax, 5                ; ax: 0x0000 -> \x1b[32m0x0005\x1b[0m",
+            "\x1b[33mWarning: UninitializedRegister(1, CX)\x1b[0m",
+            "0001  add ax, cx",
+        ]);
+
+        assert_eq!(paint("fault", Color::Red, true), "\x1b[31mfault\x1b[0m");
+        assert_eq!(paint("info", Color::Cyan, true), "\x1b[36minfo\x1b[0m");
+        assert_eq!(paint("plain", Color::Red, false), "plain");
+    }
+
+
+    #[test]
+    fn mov_writes_every_width_combination_in_full() {
+        let data = [
+            ("b8", Data::Byte(0x81)), ("w16", Data::Word(0x8182)), ("d32", Data::Dword(0x81828384)),
+            ("out8", Data::Byte(0xFF)), ("out16", Data::Word(0xFFFF)), ("out32", Data::Dword(0xFFFFFFFF)),
+        ];
+        let byte = |value| Operand::Immediate(Data::Byte(value));
+        let dword = |value| Operand::Immediate(Data::Dword(value));
+        // Destination, source and the destination's value afterwards, every destination starting with all bits set
+        let cases = [
+            (reg(Register::AX), reg(Register::BX), 0x8182),
+            (reg(Register::AX), reg(Register::EBX), 0x8384),
+            (reg(Register::EAX), reg(Register::BX), 0x8182),
+            (reg(Register::EAX), reg(Register::EBX), 0x81828384),
+            (reg(Register::AX), mem("b8"), 0x81),
+            (reg(Register::AX), mem("d32"), 0x8384),
+            (reg(Register::EAX), mem("b8"), 0x81),
+            (reg(Register::EAX), mem("w16"), 0x8182),
+            (mem("out8"), reg(Register::BX), 0x82),
+            (mem("out32"), reg(Register::BX), 0x8182),
+            (mem("out32"), byte(0x81), 0x81),
+            (mem("out8"), word(0x0081), 0x81),
+        ];
+        for (dest, src, expected) in cases {
+            let text = Instruction::new(IS::Mov, vec![dest.clone(), src.clone()]).to_string();
+            let mut cpu = cpu(&data, vec![
+                Instruction::new(IS::Mov, vec![reg(Register::AX), word(0xFFFF)]),
+                Instruction::new(IS::Mov, vec![reg(Register::EAX), dword(0xFFFFFFFF)]),
+                Instruction::new(IS::Mov, vec![reg(Register::BX), word(0x8182)]),
+                Instruction::new(IS::Mov, vec![reg(Register::EBX), dword(0x81828384)]),
+                Instruction::new(IS::Mov, vec![dest.clone(), src]),
+            ]);
+            cpu.run().unwrap();
+            let value = match &dest {
+                Operand::Register(register) => cpu.register_values()[register.index()],
+                Operand::Memory(MemOp::Address(label)) => cpu.read_label(label).unwrap().get_value(),
+                _ => unreachable!(),
+            };
+            assert_eq!(value, expected, "{}", text);
+        }
+        // The one combination left out, memory to memory, is rejected without writing the destination
+        let mut cpu = cpu(&data, vec![Instruction::new(IS::Mov, vec![mem("out16"), mem("b8")])]);
+        assert!(cpu.run().is_err());
+        assert_eq!(cpu.read_label("out16").unwrap(), Data::Word(0xFFFF));
+    }
+
+
+    /// Runs one operation on a fresh ALU
+    fn alu(mode: ALUMode, a: u32, b: u32, width: DataWidth) -> (u32, FlagsDelta) {
+        let mut alu = ALU::new();
+        alu.set_mode(mode);
+        alu.operand_fetch(a, b);
+        alu.execute(width)
+    }
+
+    /// The value read as a two's complement number of the width
+    fn signed(value: u32, width: DataWidth) -> i64 {
+        match value & width.sign_bit() {
+            0 => value as i64,
+            _ => value as i64 - (width.mask() as i64 + 1),
+        }
+    }
+
+    /// Checks the Add and Sub flags of one pair of operands against values computed from plain integers
+    fn check_add_sub_invariants(a: u32, b: u32, width: DataWidth) {
+        let (min, max) = (signed(width.sign_bit(), width), signed(width.sign_bit() - 1, width));
+
+        let (result, flags) = alu(ALUMode::Sub, a, b, width);
+        let difference = signed(a, width) - signed(b, width);
+        assert_eq!(result, a.wrapping_sub(b) & width.mask(), "{:#X} - {:#X} at {:?}", a, b, width);
+        assert_eq!(flags.zero, a == b, "ZF of {:#X} - {:#X} at {:?}", a, b, width);
+        assert_eq!(flags.carry, a < b, "CF of {:#X} - {:#X} at {:?}", a, b, width);
+        assert_eq!(flags.overflow, !(min..=max).contains(&difference), "OF of {:#X} - {:#X} at {:?}", a, b, width);
+
+        let (result, flags) = alu(ALUMode::Add, a, b, width);
+        let sum = signed(a, width) + signed(b, width);
+        assert_eq!(result, a.wrapping_add(b) & width.mask(), "{:#X} + {:#X} at {:?}", a, b, width);
+        assert_eq!(flags.zero, result == 0, "ZF of {:#X} + {:#X} at {:?}", a, b, width);
+        assert_eq!(flags.carry, a as u64 + b as u64 > width.mask() as u64, "CF of {:#X} + {:#X} at {:?}", a, b, width);
+        assert_eq!(flags.overflow, !(min..=max).contains(&sum), "OF of {:#X} + {:#X} at {:?}", a, b, width);
+    }
+
+    #[test]
+    fn add_and_sub_flags_hold_for_every_byte_pair() {
+        for a in 0..=0xFF {
+            for b in 0..=0xFF {
+                check_add_sub_invariants(a, b, DataWidth::Byte);
+            }
+        }
+    }
+
+    #[test]
+    fn add_and_sub_flags_hold_for_word_and_dword_operands() {
+        let mut random = XorShift(0x2545F4914F6CDD1D);
+        for width in [DataWidth::Word, DataWidth::Dword] {
+            // Every pair of the values at the edges of the signed and unsigned ranges, then random pairs
+            let edges = [0, 1, width.sign_bit() - 1, width.sign_bit(), width.sign_bit() + 1, width.mask() - 1, width.mask()];
+            for a in edges {
+                for b in edges {
+                    check_add_sub_invariants(a, b, width);
+                }
+            }
+            for _ in 0..4000 {
+                let a = random.next() as u32 & width.mask();
+                let b = random.next() as u32 & width.mask();
+                check_add_sub_invariants(a, b, width);
+            }
+        }
+    }
+
+
+    /// Registers and flags compared by the differential test, see `reference_step`
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct State {
+        registers: [u32; 8],
+        flags: u16,
+    }
+
+    impl State {
+        fn of(cpu: &CPU) -> State {
+            let machine_state = cpu.machine_state();
+            let mut registers = [0; 8];
+            for (register, value) in machine_state.registers {
+                registers[register.index()] = value;
+            }
+            State { registers, flags: machine_state.flags }
+        }
+    }
+
+    /// Reference semantics of MOV, ADD, SUB, AND, OR and NOT on register and immediate operands.
+    ///
+    /// Written from the instruction set's definition with plain integer arithmetic and kept free of
+    /// the ALU, so the CPU can be checked against it.
+    fn reference_step(state: State, instruction: &Instruction) -> State {
+        let Operand::Register(dest) = &instruction.operands[0] else { unreachable!() };
+        let width = dest.width();
+        let a = state.registers[dest.index()];
+        let b = match instruction.operands.get(1) {
+            Some(Operand::Register(src)) => state.registers[src.index()] & width.mask(),
+            Some(Operand::Immediate(data)) => data.get_value(),
+            _ => 0,
+        };
+        let (bits, sign_bit) = (width.bytes() as u32 * 8, width.sign_bit());
+        let signed = |value: u32| ((value as i64) << (64 - bits)) >> (64 - bits);
+        let (result, carry, overflow) = match instruction.opcode {
+            IS::Mov => (b, false, false),
+            IS::Add => (a.wrapping_add(b) & width.mask(), a as u64 + b as u64 > width.mask() as u64,
+                signed(a) + signed(b) != signed((a as u64 + b as u64) as u32 & width.mask())),
+            IS::Sub => (a.wrapping_sub(b) & width.mask(), a < b, signed(a) - signed(b) != signed(a.wrapping_sub(b) & width.mask())),
+            IS::And => (a & b, false, false),
+            IS::Or => (a | b, false, false),
+            IS::Not => (!a & width.mask(), false, false),
+            _ => unreachable!(),
+        };
+        let mut next = state;
+        next.registers[dest.index()] = result;
+        let flags = [
+            (FLAG_CF, carry), (FLAG_OF, overflow), (FLAG_ZF, result == 0), (FLAG_SF, result & sign_bit != 0),
+            (FLAG_PF, (result & 0xFF).count_ones() % 2 == 0), (FLAG_AF, (a ^ b ^ result) & 0x10 != 0),
+        ];
+        let updated = match instruction.opcode {
+            IS::Add | IS::Sub => STATUS_FLAGS,
+            IS::And | IS::Or => FLAG_CF | FLAG_OF | FLAG_ZF | FLAG_SF | FLAG_PF,
+            _ => 0,
+        };
+        for (flag, value) in flags {
+            if updated & flag != 0 {
+                next.flags = (next.flags & !flag) | if value { flag } else { 0 };
+            }
+        }
+        next
+    }
+
+    /// A random instruction of the ones `reference_step` covers, with an immediate that fits the destination
+    fn random_instruction(random: &mut XorShift) -> Instruction {
+        let registers = Register::all();
+        let dest = registers[random.below(8) as usize].clone();
+        let opcode = [IS::Mov, IS::Add, IS::Sub, IS::And, IS::Or, IS::Not][random.below(6) as usize].clone();
+        if opcode == IS::Not {
+            return Instruction::new(opcode, vec![reg(dest)]);
+        }
+        // Immediates are biased towards the values around 0 and the sign bit where the flags change
+        let value = match random.below(3) {
+            0 => random.below(4) as u32,
+            1 => dest.width().sign_bit().wrapping_sub(2).wrapping_add(random.below(4) as u32),
+            _ => random.next() as u32,
+        } & dest.width().mask();
+        let src = match random.below(2) {
+            0 => reg(registers[random.below(8) as usize].clone()),
+            _ => Operand::Immediate(Data::from_u32(dest.width(), value)),
+        };
+        Instruction::new(opcode, vec![reg(dest), src])
+    }
+
+    #[test]
+    fn cpu_matches_the_reference_semantics_on_random_programs() {
+        // Fixed seeds keep the run reproducible, a divergence found later is added to them as a regression
+        for seed in 1..=64u64 {
+            let mut random = XorShift(seed.wrapping_mul(0x9E3779B97F4A7C15));
+            let program: Vec<Instruction> = (0..64).map(|_| random_instruction(&mut random)).collect();
+            let mut cpu = cpu(&[], program.clone());
+            let mut expected = State::of(&cpu);
+            for instruction in &program {
+                expected = reference_step(expected, instruction);
+                cpu.fetch().unwrap();
+                assert_eq!(State::of(&cpu), expected, "seed {} diverged after {}", seed, instruction);
+            }
+        }
+    }
+
+
+    #[test]
+    fn register_codes_round_trip_and_index_the_register_file() {
+        // Matching without a wildcard stops a new register from compiling until it is listed by `Register::all`
+        let listed = |register: &Register| match register {
+            Register::AX | Register::BX | Register::CX | Register::DX |
+            Register::EAX | Register::EBX | Register::ECX | Register::EDX => Register::all().contains(register),
+        };
+        let cpu = cpu(&[], vec![]);
+        for (position, register) in Register::all().iter().enumerate() {
+            assert!(listed(register));
+            assert_eq!(register.code() as usize, position, "{:?}", register);
+            assert_eq!(Register::from_code(register.code()).as_ref(), Some(register));
+            let slot = format!("{:?}", cpu.registers.GP[register.index()]);
+            assert!(slot.starts_with(&format!("{:?}:", register)), "{:?} is stored as {}", register, slot);
+        }
+        assert_eq!(Register::from_code(Register::all().len() as u8), None);
+    }
+
+    #[test]
+    fn flag_codes_round_trip_and_index_the_flags_array() {
+        let cpu = cpu(&[], vec![]);
+        let mut listed = 0;
+        for code in 0..=u8::MAX {
+            let Some(flag) = FLAGS::from_code(code) else { continue };
+            // Matching without a wildcard stops a new flag from compiling until it is given a code
+            let name = match flag {
+                FLAGS::PF(_) => "PF", FLAGS::AF(_) => "AF", FLAGS::ZF(_) => "ZF",
+                FLAGS::SF(_) => "SF", FLAGS::TF(_) => "TF", FLAGS::IF(_) => "IF",
+                FLAGS::DF(_) => "DF", FLAGS::OF(_) => "OF", FLAGS::CF(_) => "CF",
+            };
+            assert_eq!(flag.code(), code);
+            assert_eq!(FLAG_NAMES[code as usize], name);
+            assert_eq!(cpu.flags[code as usize].code(), code);
+            listed += 1;
+        }
+        assert_eq!(listed, cpu.flags.len());
+        assert_eq!(listed, FLAG_NAMES.len());
+    }
+
+
+    #[test]
+    fn data_helpers_respect_width_boundaries() {
+        assert_eq!(Data::Byte(0xFE).checked_add(1), Ok(Data::Byte(0xFF)));
+        assert!(Data::Byte(0xFF).checked_add(1).is_err());
+        assert_eq!(Data::Word(0xFFFE).checked_add(1), Ok(Data::Word(0xFFFF)));
+        assert!(Data::Word(0xFFFF).checked_add(1).is_err());
+        assert!(Data::Word(0).checked_add(0x10000).is_err());
+        assert_eq!(Data::Dword(0xFFFFFFFE).checked_add(1), Ok(Data::Dword(0xFFFFFFFF)));
+        assert!(Data::Dword(0xFFFFFFFF).checked_add(1).is_err());
+
+        assert_eq!(Data::split_dword(0), (Data::Word(0), Data::Word(0)));
+        assert_eq!(Data::split_dword(0x0000FFFF), (Data::Word(0xFFFF), Data::Word(0)));
+        assert_eq!(Data::split_dword(0x00010000), (Data::Word(0), Data::Word(1)));
+        assert_eq!(Data::split_dword(0xFFFFFFFF), (Data::Word(0xFFFF), Data::Word(0xFFFF)));
+
+        for data in [Data::Byte(0), Data::Byte(0xFF), Data::Word(0x00FF), Data::Word(0xFFFF), Data::Dword(0x0000FFFF), Data::Dword(0xFFFFFFFF)] {
+            let bytes = data.to_bytes();
+            assert_eq!(bytes.len(), data.width().bytes());
+            assert_eq!(Data::from_bytes(data.width(), &bytes), Ok(data));
+        }
+        assert_eq!(Data::Word(0x1234).to_bytes(), vec![0x34, 0x12]);
+        assert_eq!(Data::from_bytes(DataWidth::Dword, &[0x78, 0x56, 0x34, 0x12]), Ok(Data::Dword(0x12345678)));
+        for width in [DataWidth::Byte, DataWidth::Word, DataWidth::Dword] {
+            assert!(Data::from_bytes(width, &vec![0; width.bytes() - 1]).is_err());
+            assert!(Data::from_bytes(width, &vec![0; width.bytes() + 1]).is_err());
+        }
+    }
+
+
+    #[test]
+    fn and_masks_the_low_byte_of_ax() {
+        let mut cpu = cpu(&[], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(0x1234)]),
+            Instruction::new(IS::And, vec![reg(Register::AX), word(0x00FF)]),
+        ]);
+        cpu.flags[8].set_value(1);
+        cpu.flags[7].set_value(1);
+        cpu.run().unwrap();
+        assert_eq!(cpu.register_values()[Register::AX.index()], 0x0034);
+        assert!(!flag(&cpu, "ZF"));
+        assert!(!flag(&cpu, "SF"));
+        assert!(!flag(&cpu, "CF"));
+        assert!(!flag(&cpu, "OF"));
+
+        let mut cpu = self::cpu(&[], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(0x8000)]),
+            Instruction::new(IS::And, vec![reg(Register::AX), word(0xFF00)]),
+            Instruction::new(IS::Mov, vec![reg(Register::BX), word(0x1200)]),
+            Instruction::new(IS::And, vec![reg(Register::BX), word(0x00FF)]),
+        ]);
+        cpu.run().unwrap();
+        assert_eq!(cpu.register_values()[Register::AX.index()], 0x8000);
+        assert_eq!(cpu.register_values()[Register::BX.index()], 0);
+        assert!(flag(&cpu, "ZF"));
+        assert!(!flag(&cpu, "SF"));
+    }
+
+    #[test]
+    fn or_sets_zero_flag_for_register_operands_and_writes_memory_back() {
+        let mut cpu = cpu(&[], vec![Instruction::new(IS::Or, vec![reg(Register::AX), reg(Register::AX)])]);
+        cpu.flags[8].set_value(1);
+        cpu.run().unwrap();
+        assert_eq!(cpu.register_values()[Register::AX.index()], 0);
+        assert!(flag(&cpu, "ZF"));
+        assert!(!flag(&cpu, "CF"));
+
+        let mut cpu = self::cpu(&[], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(0x8001)]),
+            Instruction::new(IS::Or, vec![reg(Register::AX), reg(Register::AX)]),
+        ]);
+        cpu.run().unwrap();
+        assert_eq!(cpu.register_values()[Register::AX.index()], 0x8001);
+        assert!(!flag(&cpu, "ZF"));
+        assert!(flag(&cpu, "SF"));
+
+        let mut cpu = self::cpu(&[("mask", Data::Word(0x0F00)), ("after", Data::Word(0xAAAA))], vec![
+            Instruction::new(IS::Or, vec![mem("mask"), word(0x00F0)]),
+        ]);
+        cpu.run().unwrap();
+        assert_eq!(cpu.read_label("mask").unwrap(), Data::Word(0x0FF0));
+        assert_eq!(cpu.read_label("after").unwrap(), Data::Word(0xAAAA));
+        assert!(!flag(&cpu, "ZF"));
+    }
+
+
+    #[test]
+    fn memory_dumps_are_capped_at_the_dump_limit() {
+        let labels: Vec<(String, Data)> = (0..25).map(|i| (format!("d{:02}", i), Data::Dword(0x01010101 * i))).collect();
+        let data: Vec<(&str, Data)> = labels.iter().map(|(label, data)| (label.as_str(), data.clone())).collect();
+        let cpu = self::cpu(&data, vec![]);
+        // The 100 byte data section is cut after the default 64 bytes
+        let dump = cpu.hexdump(0, 100).unwrap();
+        assert!(dump.starts_with("[00 00 00 00 01 01 01 01 02 02 02 02 "), "{}", dump);
+        assert!(dump.ends_with(" 0F 0F 0F 0F ... (100 bytes)]"), "{}", dump);
+        assert_eq!(dump.matches(' ').count(), 64 + 2);
+        assert_eq!(cpu.hexdump(4, 8).unwrap(), "[01 01 01 01 02 02 02 02]");
+        assert!(cpu.hexdump(96, 8).is_err());
+
+        let data: HashMap<String, Data> = data.iter().map(|(label, data)| (label.to_string(), data.clone())).collect();
+        let mut cpu = CpuBuilder::new(data, vec![Instruction::new(IS::Jmp, vec![word(7)])])
+            .verbosity(Verbosity::Quiet)
+            .dump_limit(6)
+            .build()
+            .unwrap();
+        assert_eq!(cpu.hexdump(0, 6).unwrap(), "[00 00 00 00 01 01]");
+        assert_eq!(cpu.hexdump(0, 7).unwrap(), "[00 00 00 00 01 01 ... (7 bytes)]");
+        let fault = cpu.run().unwrap_err();
+        assert_eq!(fault.context.memory, "[00 00 00 00 01 01 ... (100 bytes)]");
+        assert!(fault.to_string().ends_with("Memory: [00 00 00 00 01 01 ... (100 bytes)]"), "{}", fault);
+    }
+
+
+    /// Clock moving forward by a fixed step every time it is read, and by the slept duration when sleeping.
+    ///
+    /// The time is shared so tests can read it once the CPU owns the clock.
+    #[derive(Debug)]
+    struct MockClock {
+        time: std::rc::Rc<std::cell::Cell<Duration>>,
+        step: Duration,
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> Duration {
+            let time = self.time.get();
+            self.time.set(time + self.step);
+            time
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.time.set(self.time.get() + duration);
+        }
+    }
+
+    /// Runs the program for at most `timeout`, checking a clock that moves 10ms per read every 4 instructions
+    fn run_with_mock_clock(code: Vec<Instruction>, timeout: Duration) -> Result<RunOutcome, Fault> {
+        let clock = MockClock { time: Default::default(), step: Duration::from_millis(10) };
+        CpuBuilder::new(HashMap::new(), code)
+            .verbosity(Verbosity::Quiet)
+            .clock(clock)
+            .timeout_check_interval(4)
+            .build()
+            .unwrap()
+            .run_with_timeout(timeout)
+    }
+
+    #[test]
+    fn timeout_triggers_at_the_first_check_past_it() {
+        let endless = || vec![Instruction::new(IS::Jmp, vec![word(0)])];
+        // The clock reads 10ms, 20ms, 30ms and 40ms after 4, 8, 12 and 16 instructions
+        for (timeout, instructions) in [(1, 4), (35, 16), (40, 16), (41, 20)] {
+            let fault = run_with_mock_clock(endless(), Duration::from_millis(timeout)).unwrap_err();
+            assert!(matches!(fault.error, CpuError::Timeout(count) if count == instructions), "{}ms: {:?}", timeout, fault.error);
+        }
+
+        let fast = vec![Instruction::new(IS::Mov, vec![reg(Register::AX), word(1)]); 3];
+        let outcome = run_with_mock_clock(fast, Duration::from_millis(1)).unwrap();
+        assert_eq!(outcome.instructions, 3);
+        assert_eq!(outcome.duration, Duration::from_millis(10));
+    }
+
+
+    #[test]
+    fn cmp_sets_the_flags_of_sub_and_leaves_its_operands_unchanged() {
+        let mut random = XorShift(0x5DEECE66D);
+        for _ in 0..500 {
+            let a = random.next() as u16;
+            // Every fourth pair compares equal values
+            let b = if random.below(4) == 0 { a } else { random.next() as u16 };
+            let program = |opcode| vec![
+                Instruction::new(IS::Mov, vec![reg(Register::AX), word(a)]),
+                Instruction::new(IS::Mov, vec![reg(Register::BX), word(b)]),
+                Instruction::new(opcode, vec![reg(Register::AX), reg(Register::BX)]),
+            ];
+            let mut compared = cpu(&[], program(IS::Cmp));
+            compared.run().unwrap();
+            let mut subtracted = cpu(&[], program(IS::Sub));
+            subtracted.run().unwrap();
+            assert_eq!(compared.flags_word(), subtracted.flags_word(), "cmp {:#X}, {:#X}", a, b);
+            assert_eq!((compared.register(Register::AX), compared.register(Register::BX)), (a as u32, b as u32));
+        }
+
+        let mut cpu = cpu(&[("limit", Data::Word(7))], vec![Instruction::new(IS::Cmp, vec![mem("limit"), word(7)])]);
+        cpu.run().unwrap();
+        assert_eq!(cpu.read_label("limit").unwrap(), Data::Word(7));
+        assert!(flag(&cpu, "ZF"));
+    }
+
+    #[test]
+    fn conditional_jumps_follow_the_zero_and_carry_flags() {
+        // Whether the jump is taken after comparing the first value with the second
+        let cases = [
+            (IS::Jz, 5, 5, true), (IS::Jz, 5, 6, false),
+            (IS::Jnz, 5, 6, true), (IS::Jnz, 5, 5, false),
+            (IS::Jc, 5, 6, true), (IS::Jc, 6, 5, false), (IS::Jc, 5, 5, false),
+            (IS::Jnc, 6, 5, true), (IS::Jnc, 5, 5, true), (IS::Jnc, 5, 6, false),
+        ];
+        for (opcode, a, b, taken) in cases {
+            let mut cpu = cpu(&[], vec![
+                Instruction::new(IS::Mov, vec![reg(Register::AX), word(a)]),
+                Instruction::new(IS::Cmp, vec![reg(Register::AX), word(b)]),
+                Instruction::new(opcode.clone(), vec![Operand::JmpRel(1)]),
+                Instruction::new(IS::Mov, vec![reg(Register::CX), word(1)]),
+            ]);
+            assert!(!cpu.validate().iter().any(|warning| matches!(warning, Warning::UnreachableCode(_))), "{:?}", opcode);
+            cpu.run().unwrap();
+            assert_eq!(cpu.register(Register::CX) == 0, taken, "{:?} after cmp {}, {}", opcode, a, b);
+        }
+    }
+
+    #[test]
+    fn indirect_operands_address_memory_through_a_register() {
+        let mut cpu = cpu(&[("a0", Data::Word(0x1111)), ("a1", Data::Word(0x2222))], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::BX), Operand::LabelOffset("a0".to_string())]),
+            Instruction::new(IS::Mov, vec![reg(Register::AX), Operand::Memory(MemOp::Indirect(DataWidth::Word, Register::BX, 2))]),
+            Instruction::new(IS::Mov, vec![Operand::Memory(MemOp::Indirect(DataWidth::Byte, Register::BX, 0)), word(0x00FF)]),
+            Instruction::new(IS::Mov, vec![reg(Register::BX), word(3)]),
+            Instruction::new(IS::Mov, vec![reg(Register::CX), Operand::Memory(MemOp::Indirect(DataWidth::Word, Register::BX, 0))]),
+        ]);
+        assert_eq!(cpu.code().nth(1).unwrap().1.to_string(), "mov ax, word ptr [bx+2]");
+        let fault = cpu.run().unwrap_err();
+        assert_eq!(cpu.register(Register::AX), 0x2222);
+        assert_eq!(cpu.read_label("a0").unwrap(), Data::Word(0x11FF));
+        // Reading a word at offset 3 would run past the end of the 4 byte data section
+        assert!(matches!(fault.error, CpuError::MemoryOutOfBounds(3, 2)), "{:?}", fault.error);
+        assert!(cpu.code().nth(1).unwrap().1.register_reads().contains(&Register::BX));
+    }
+
+
+    #[test]
+    fn calls_return_to_the_instruction_after_them() {
+        let subroutine = vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(1)]),
+            Instruction::new(IS::Call, vec![word(4)]),
+            Instruction::new(IS::Add, vec![reg(Register::AX), word(10)]),
+            Instruction::new(IS::Jmp, vec![word(7)]),
+            Instruction::new(IS::Add, vec![reg(Register::AX), word(100)]),
+            Instruction::new(IS::AssertReg, vec![reg(Register::AX), word(101)]),
+            Instruction::new(IS::Ret, vec![]),
+        ];
+        let mut calling = cpu(&[], subroutine.clone());
+        assert!(calling.validate().is_empty(), "{:?}", calling.validate());
+        calling.run().unwrap();
+        assert_eq!(calling.register(Register::AX), 111);
+
+        // A fault inside the subroutine reports where it would have returned to
+        let mut code = subroutine;
+        code[5] = Instruction::new(IS::AssertReg, vec![reg(Register::AX), word(0)]);
+        let fault = cpu(&[], code).run().unwrap_err();
+        assert!(matches!(fault.error, CpuError::GuestAssertionFailed(5, 0, 101)), "{:?}", fault.error);
+        assert_eq!(fault.context.call_stack, vec![2]);
+        assert!(fault.to_string().contains("Call stack: [2]"), "{}", fault);
+    }
+
+    #[test]
+    fn unbalanced_calls_and_returns_fault() {
+        let fault = cpu(&[], vec![Instruction::new(IS::Ret, vec![])]).run().unwrap_err();
+        assert!(matches!(fault.error, CpuError::ReturnWithoutCall), "{:?}", fault.error);
+
+        // Recursing without ever returning
+        let fault = cpu(&[], vec![Instruction::new(IS::Call, vec![word(0)])]).run().unwrap_err();
+        assert!(matches!(fault.error, CpuError::CallStackOverflow), "{:?}", fault.error);
+        assert_eq!(fault.context.call_stack.len(), CALL_STACK_LIMIT);
+    }
+
+    #[test]
+    fn runs_stop_at_the_instruction_limit() {
+        let mut cpu = CpuBuilder::new(HashMap::new(), vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(1)]),
+            Instruction::new(IS::Jmp, vec![word(0)]),
+        ]).verbosity(Verbosity::Quiet).instruction_limit(9).build().unwrap();
+        let fault = cpu.run().unwrap_err();
+        assert!(matches!(fault.error, CpuError::InstructionLimit(9)), "{:?}", fault.error);
+        // The ninth instruction run is the fifth pass over the mov
+        assert_eq!(fault.context.ip, 0);
+
+        // A program finishing on its last allowed instruction isn't stopped
+        let mut cpu = CpuBuilder::new(HashMap::new(), vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(1)]),
+            Instruction::new(IS::Mov, vec![reg(Register::BX), word(2)]),
+        ]).verbosity(Verbosity::Quiet).instruction_limit(2).build().unwrap();
+        cpu.run().unwrap();
+        assert_eq!(cpu.register(Register::BX), 2);
+    }
+
+    #[test]
+    fn div_splits_the_dividend_into_quotient_and_remainder() {
+        let dword = |value| Operand::Immediate(Data::Dword(value));
+        // dx:ax = 0x0001_0005 divided by a word
+        let mut dividing = cpu(&[("divisor", Data::Byte(7))], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(5)]),
+            Instruction::new(IS::Mov, vec![reg(Register::DX), word(1)]),
+            Instruction::new(IS::Mov, vec![reg(Register::CX), word(10)]),
+            Instruction::new(IS::Div, vec![reg(Register::CX)]),
+            Instruction::new(IS::AssertReg, vec![reg(Register::AX), word(6554)]),
+            Instruction::new(IS::AssertReg, vec![reg(Register::DX), word(1)]),
+            // ax = 100 divided by a byte leaves the quotient in al and the remainder in ah
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(100)]),
+            Instruction::new(IS::Div, vec![mem("divisor")]),
+            Instruction::new(IS::AssertReg, vec![reg(Register::AX), word(0x020E)]),
+            // edx:eax = 2^32 + 1 divided by a dword
+            Instruction::new(IS::Mov, vec![reg(Register::EAX), dword(1)]),
+            Instruction::new(IS::Mov, vec![reg(Register::EDX), dword(1)]),
+            Instruction::new(IS::Mov, vec![reg(Register::ECX), dword(2)]),
+            Instruction::new(IS::Div, vec![reg(Register::ECX)]),
+        ]);
+        assert!(dividing.validate().is_empty(), "{:?}", dividing.validate());
+        dividing.run().unwrap();
+        assert_eq!(dividing.register(Register::EAX), 0x8000_0000);
+        assert_eq!(dividing.register(Register::EDX), 1);
+
+        // Dividing by zero, and a quotient that doesn't fit the quotient register
+        for (dx, divisor) in [(0, 0), (2, 2)] {
+            let mut cpu = cpu(&[], vec![
+                Instruction::new(IS::Mov, vec![reg(Register::AX), word(0)]),
+                Instruction::new(IS::Mov, vec![reg(Register::DX), word(dx)]),
+                Instruction::new(IS::Mov, vec![reg(Register::CX), word(divisor)]),
+                Instruction::new(IS::Div, vec![reg(Register::CX)]),
+            ]);
+            let fault = cpu.run().unwrap_err();
+            assert!(matches!(fault.error, CpuError::DivideError), "{:?}", fault.error);
+            assert_eq!(cpu.register(Register::DX), dx as u32);
+        }
+    }
+
+    #[test]
+    fn relative_jumps_loop_backward_and_skip_forward() {
+        let mut looping = cpu(&[], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::CX), word(0)]),
+            Instruction::new(IS::Add, vec![reg(Register::CX), word(1)]),
+            Instruction::new(IS::Cmp, vec![reg(Register::CX), word(3)]),
+            // Leaves the loop by skipping over the backward jump
+            Instruction::new(IS::Jz, vec![Operand::JmpRel(1)]),
+            Instruction::new(IS::Jmp, vec![Operand::JmpRel(-4)]),
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(7)]),
+        ]);
+        let outcome = looping.run().unwrap();
+        assert_eq!(looping.register(Register::CX), 3);
+        assert_eq!(looping.register(Register::AX), 7);
+        // The mov, three passes of add, cmp and jz, two backward jumps and the final mov
+        assert_eq!(outcome.instructions, 13);
+
+        // A forward skip never runs the instruction it jumps over
+        let mut skipping = cpu(&[], vec![
+            Instruction::new(IS::Jmp, vec![Operand::JmpRel(1)]),
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(1)]),
+            Instruction::new(IS::Mov, vec![reg(Register::BX), word(2)]),
+        ]);
+        let outcome = skipping.run().unwrap();
+        assert_eq!(skipping.register(Register::AX), 0);
+        assert_eq!(skipping.register(Register::BX), 2);
+        assert_eq!(outcome.instructions, 2);
+    }
+
+    #[test]
+    fn byte_stores_fill_a_dword_from_the_low_byte_of_a_register() {
+        let byte_ptr = |displacement| Operand::Memory(MemOp::Ptr(DataWidth::Byte, "buffer".to_string(), displacement));
+        let mut code = Vec::new();
+        for (displacement, byte) in [(0, 0x11), (1, 0x22), (2, 0x33), (3, 0x44)] {
+            // The high byte of dx must never reach memory
+            code.push(Instruction::new(IS::Mov, vec![reg(Register::DX), word(0xAB00 | byte)]));
+            code.push(Instruction::new(IS::Mov, vec![byte_ptr(displacement), reg(Register::DX)]));
+        }
+        let mut filling = cpu(&[("buffer", Data::Dword(0)), ("guard", Data::Byte(0xEE))], code);
+        filling.run().unwrap();
+        assert_eq!(filling.read_label("buffer").unwrap(), Data::Dword(0x4433_2211));
+        assert_eq!(filling.read_label("guard").unwrap(), Data::Byte(0xEE));
+    }
+
+    #[test]
+    fn declarations_stay_immutable_while_memory_changes() {
+        let mut declared = cpu(&[("first", Data::Byte(1)), ("num", Data::Word(10))], vec![
+            Instruction::new(IS::Add, vec![mem("num"), word(5)]),
+        ]);
+        let offset = declared.symbol("num").unwrap().offset;
+        assert_eq!(offset, 1);
+        assert_eq!(declared.memory_unit.read_bytes(offset as usize, 2).unwrap(), 10u16.to_le_bytes());
+
+        declared.run().unwrap();
+        assert_eq!(declared.read_label("num").unwrap(), Data::Word(15));
+        // Only RAM changed, the declaration and the label's location are as built
+        assert_eq!(declared.declaration("num"), Some(&Data::Word(10)));
+        assert_eq!(declared.symbol("num").unwrap().offset, offset);
+        assert_eq!(declared.declaration("missing"), None);
+        assert!(declared.symbol("missing").is_none());
+    }
+
+    #[test]
+    fn relocating_the_data_section_only_shifts_label_offsets() {
+        let run_at = |base| {
+            let data = [("num", Data::Word(10)), ("num2", Data::Word(20)), ("result", Data::Word(0))];
+            let data = data.iter().map(|(label, data)| (label.to_string(), data.clone())).collect();
+            let mut relocated = CpuBuilder::new(data, vec![
+                Instruction::new(IS::Mov, vec![reg(Register::AX), mem("num")]),
+                Instruction::new(IS::Add, vec![reg(Register::AX), mem("num2")]),
+                Instruction::new(IS::Mov, vec![mem("result"), reg(Register::AX)]),
+                Instruction::new(IS::Mov, vec![reg(Register::BX), Operand::LabelOffset("result".to_string())]),
+            ]).verbosity(Verbosity::Quiet).data_base(base).build().unwrap();
+            relocated.run().unwrap();
+            relocated
+        };
+        let (low, high) = (run_at(0), run_at(0x200));
+        assert_eq!(low.read_label("result").unwrap(), Data::Word(30));
+        assert_eq!(high.read_label("result").unwrap(), Data::Word(30));
+        for register in Register::all() {
+            match register {
+                Register::BX => assert_eq!(high.register(Register::BX), low.register(Register::BX) + 0x200),
+                _ => assert_eq!(high.register(register.clone()), low.register(register.clone()), "{:?}", register),
+            }
+        }
+        for label in ["num", "num2", "result"] {
+            assert_eq!(high.symbol(label).unwrap().offset, low.symbol(label).unwrap().offset + 0x200);
+        }
+        assert_eq!(high.memory_unit.get_data_len(), low.memory_unit.get_data_len() + 0x200);
+    }
+
+    #[test]
+    fn step_executes_one_instruction_and_reports_the_exit() {
+        let mut stepping = cpu(&[], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(1)]),
+            Instruction::new(IS::Mov, vec![reg(Register::BX), word(2)]),
+        ]);
+        assert_eq!(stepping.step().unwrap(), StopReason::Stepped);
+        assert_eq!((stepping.register(Register::AX), stepping.register(Register::BX)), (1, 0));
+        assert_eq!(stepping.step().unwrap(), StopReason::Exited(0));
+        // Nothing is left to execute
+        assert_eq!(stepping.step().unwrap(), StopReason::Exited(0));
+        assert_eq!(stepping.instruction_count, 2);
+    }
+
+    /// Builds a core of a machine over the data section shared by the machine tests
+    fn core(data: &[(&str, Data)], code: Vec<Instruction>) -> CPU {
+        let data = data.iter().map(|(label, data)| (label.to_string(), data.clone())).collect();
+        CpuBuilder::new(data, code).verbosity(Verbosity::Quiet).instruction_limit(100).build().unwrap()
+    }
+
+    #[test]
+    fn cores_of_a_machine_see_each_others_writes() {
+        let data = [("ready", Data::Byte(0)), ("value", Data::Word(0))];
+        let waiting = core(&data, vec![
+            Instruction::new(IS::Cmp, vec![mem("ready"), Operand::Immediate(Data::Byte(0))]),
+            Instruction::new(IS::Jz, vec![word(0)]),
+            Instruction::new(IS::Mov, vec![reg(Register::AX), mem("value")]),
+        ]);
+        let producing = core(&data, vec![
+            Instruction::new(IS::Mov, vec![reg(Register::BX), word(1)]),
+            Instruction::new(IS::Add, vec![reg(Register::BX), word(41)]),
+            Instruction::new(IS::Mov, vec![mem("value"), reg(Register::BX)]),
+            Instruction::new(IS::Mov, vec![mem("ready"), Operand::Immediate(Data::Byte(1))]),
+        ]);
+        let mut machine = Machine::new(vec![waiting, producing]).unwrap();
+        assert_eq!(machine.run().unwrap(), vec![StopReason::Exited(0), StopReason::Exited(0)]);
+        let waiting = machine.core(0).unwrap();
+        assert_eq!(waiting.register(Register::AX), 42);
+        // The flag is set in the fourth round, after the waiting core's fourth instruction:
+        // it spins twice more through cmp and jz before seeing it, then reads the value
+        assert_eq!(waiting.instruction_count, 7);
+        assert_eq!(machine.read_label("value").unwrap(), Data::Word(42));
+    }
+
+    #[test]
+    fn unsynchronized_increments_of_a_machine_lose_updates() {
+        let increments = || {
+            let mut code = Vec::new();
+            for _ in 0..3 {
+                code.push(Instruction::new(IS::Mov, vec![reg(Register::AX), mem("counter")]));
+                code.push(Instruction::new(IS::Add, vec![reg(Register::AX), word(1)]));
+                code.push(Instruction::new(IS::Mov, vec![mem("counter"), reg(Register::AX)]));
+            }
+            core(&[("counter", Data::Word(0))], code)
+        };
+        let mut machine = Machine::new(vec![increments(), increments()]).unwrap();
+        machine.run().unwrap();
+        // Both cores load the counter before either stores it, so each round adds 1 rather than 2
+        assert_eq!(machine.read_label("counter").unwrap(), Data::Word(3));
+
+        let mismatched = core(&[("counter", Data::Dword(0))], vec![]);
+        assert!(matches!(Machine::new(vec![increments(), mismatched]), Err(BuildError::CoreLayoutMismatch(1))));
+    }
+
+    #[test]
+    fn ihex_images_round_trip_around_gaps_and_past_64_kib() {
+        let data = |a, b, c| [("a", Data::Word(a)), ("b", Data::Dword(b)), ("c", Data::Word(c))];
+        let source = cpu(&data(0x1234, 0x5555_5555, 0xABCD), vec![]);
+        // Leave out the bytes of `b` in the middle
+        let low = source.export_ihex(0..2).unwrap();
+        let high = source.export_ihex(6..8).unwrap();
+        assert_eq!(low, ":020000003412B8\n:00000001FF\n");
+        let image = low.replace(":00000001FF\n", "") + &high;
+        let mut target = cpu(&data(0, 0, 0), vec![]);
+        target.import_ihex(&image).unwrap();
+        assert_eq!(target.read_label("a").unwrap(), Data::Word(0x1234));
+        assert_eq!(target.read_label("b").unwrap(), Data::Dword(0));
+        assert_eq!(target.read_label("c").unwrap(), Data::Word(0xABCD));
+
+        // Addresses above 16 bits need an extended linear address record
+        let large = |bytes: Vec<u8>| {
+            let mut memory_unit = MemoryUnit::new(HashMap::new(), vec![]);
+            memory_unit.data_bus = RAM { data: bytes, capacity: 0 };
+            memory_unit
+        };
+        let bytes: Vec<u8> = (0..0x10010).map(|offset| offset as u8).collect();
+        let image = large(bytes.clone()).export_ihex(0xFFF8..0x10008).unwrap();
+        let records: Vec<&str> = image.lines().collect();
+        assert_eq!(records.len(), 4, "{}", image);
+        assert!(records[0].starts_with(":08FFF800"));
+        assert_eq!(records[1], ":020000040001F9");
+        assert!(records[2].starts_with(":0800000000"));
+        let mut imported = large(vec![0; 0x10010]);
+        imported.import_ihex(&image).unwrap();
+        assert_eq!(imported.read_bytes(0xFFF8, 16).unwrap(), bytes[0xFFF8..0x10008]);
+        assert_eq!(imported.read_bytes(0xFFF0, 8).unwrap(), vec![0; 8]);
+    }
+
+    #[test]
+    fn ihex_import_reports_the_line_of_a_bad_record() {
+        let path = format!("{}/tests/fixtures/corrupted_checksum.hex", env!("CARGO_MANIFEST_DIR"));
+        let image = std::fs::read_to_string(path).unwrap();
+        let mut importing = cpu(&[("head", Data::Dword(0))], vec![]);
+        assert!(matches!(importing.import_ihex(&image), Err(IhexError::Checksum(3, 0xD7, 0xD8))));
+        // The records before the corrupted one are imported
+        assert_eq!(importing.read_label("head").unwrap(), Data::Dword(0xEFBE_ADDE));
+
+        // Non-ASCII text is rejected rather than sliced mid-character
+        for (image, line) in [(":00é0000FF", 1), (":xy", 1), ("\n:0200000400", 2)] {
+            match importing.import_ihex(image) {
+                Err(IhexError::Malformed(number, _)) => assert_eq!(number, line, "{:?}", image),
+                other => panic!("{:?} imported as {:?}", image, other),
+            }
+        }
+        assert!(matches!(importing.import_ihex(":0100000500FA"), Err(IhexError::UnsupportedRecord(1, 0x05))));
+        assert!(matches!(importing.export_ihex(0..0x1000), Err(IhexError::Memory(None, CpuError::MemoryOutOfBounds(..)))));
+    }
+
+    #[test]
+    fn a_saved_memory_image_resumes_where_the_program_left_off() {
+        let program = |entry| {
+            let data = [("i", Data::Word(0)), ("sum", Data::Word(0))];
+            let data = data.iter().map(|(label, data)| (label.to_string(), data.clone())).collect();
+            CpuBuilder::new(data, vec![
+                Instruction::new(IS::Add, vec![mem("sum"), word(5)]),
+                Instruction::new(IS::Add, vec![mem("sum"), word(7)]),
+                // No register is live from here, the state is entirely in memory
+                Instruction::new(IS::Add, vec![mem("i"), word(1)]),
+                Instruction::new(IS::Mov, vec![reg(Register::AX), mem("sum")]),
+                Instruction::new(IS::Add, vec![reg(Register::AX), mem("i")]),
+                Instruction::new(IS::Mov, vec![mem("sum"), reg(Register::AX)]),
+            ]).verbosity(Verbosity::Quiet).entry(entry).build().unwrap()
+        };
+        let mut uninterrupted = program(0);
+        uninterrupted.run().unwrap();
+
+        let path = std::env::temp_dir().join(format!("cpu-memory-{}.img", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut suspended = program(0);
+        assert_eq!(suspended.step().unwrap(), StopReason::Stepped);
+        assert_eq!(suspended.step().unwrap(), StopReason::Stepped);
+        suspended.save_memory(path).unwrap();
+
+        let mut resumed = program(2);
+        resumed.load_memory(path).unwrap();
+        while resumed.step().unwrap() == StopReason::Stepped {}
+        assert_eq!(resumed.machine_state(), uninterrupted.machine_state());
+        assert_eq!(resumed.read_label("sum").unwrap(), Data::Word(13));
+
+        // An image made for a larger RAM is rejected and leaves memory as it was
+        let mut image = std::fs::read(path).unwrap();
+        image[MEMORY_IMAGE_MAGIC.len()..MEMORY_IMAGE_MAGIC.len() + 4].copy_from_slice(&4096u32.to_le_bytes());
+        std::fs::write(path, image).unwrap();
+        let error = resumed.load_memory(path).unwrap_err();
+        assert!(error.contains("capacity of 4096"), "{}", error);
+        assert_eq!(resumed.read_label("sum").unwrap(), Data::Word(13));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn cycle_totals_follow_the_cost_table() {
+        let program = || cpu(&[("n", Data::Word(2))], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(1)]),
+            Instruction::new(IS::Add, vec![reg(Register::AX), mem("n")]),
+            Instruction::new(IS::Jmp, vec![Operand::JmpRel(0)]),
+        ]);
+        // mov 1, add 1 plus 2 for its memory operand, jmp 2
+        let mut default = program();
+        assert_eq!(default.run().unwrap().cycles, 6);
+
+        let mut overridden = program();
+        overridden.set_cost(IS::Add, 4);
+        overridden.set_cost(IS::Jmp, 0);
+        // Memory operands still cost their own cycles on top of the overridden opcode cost
+        assert_eq!(overridden.run().unwrap().cycles, 1 + 4 + 2);
+        assert_eq!(overridden.instruction_cost(&Instruction::new(IS::Mov, vec![reg(Register::AX), mem("n")])), 3);
+    }
+
+    #[test]
+    fn throttled_runs_pace_the_program_against_the_clock() {
+        let program = |limit| {
+            let code = vec![Instruction::new(IS::Add, vec![reg(Register::AX), word(1)]); 100];
+            let time: std::rc::Rc<std::cell::Cell<Duration>> = Default::default();
+            let throttled = CpuBuilder::new(HashMap::new(), code)
+                .verbosity(Verbosity::Quiet)
+                .clock(MockClock { time: time.clone(), step: Duration::ZERO })
+                .instruction_limit(limit)
+                .build()
+                .unwrap();
+            (throttled, time)
+        };
+        let (mut throttled, time) = program(1000);
+        assert_eq!(throttled.run_throttled(50, &AtomicBool::new(false)).unwrap(), StopReason::Exited(0));
+        assert_eq!(throttled.register(Register::AX), 100);
+        assert_eq!(time.get(), Duration::from_secs(2));
+
+        // A hook stands in for a UI thread pausing the program while the 11th instruction is due
+        let (mut paused, time) = program(1000);
+        let stop = std::sync::Arc::new(AtomicBool::new(false));
+        let pause = stop.clone();
+        paused.hook_before(10, move |_| {
+            pause.store(true, Ordering::Relaxed);
+            HookAction::Continue
+        });
+        assert_eq!(paused.run_throttled(50, &stop).unwrap(), StopReason::Paused);
+        assert_eq!(paused.register(Register::AX), 11);
+        assert_eq!(time.get(), Duration::from_millis(220));
+
+        // Breakpoints and the instruction limit still apply
+        let (mut debugged, _) = program(50);
+        debugged.add_breakpoint(30);
+        let flag = AtomicBool::new(false);
+        assert_eq!(debugged.run_throttled(50, &flag).unwrap(), StopReason::Breakpoint(30));
+        assert_eq!(debugged.register(Register::AX), 30);
+        let fault = debugged.run_throttled(50, &flag).unwrap_err();
+        assert!(matches!(fault.error, CpuError::InstructionLimit(50)), "{:?}", fault.error);
+        assert_eq!(debugged.register(Register::AX), 80);
+    }
+
+    #[test]
+    fn hooks_stub_out_a_subroutine_at_its_entry_or_at_one_call() {
+        let program = || CpuBuilder::new(HashMap::from([("first".to_string(), Data::Word(0)), ("second".to_string(), Data::Word(0))]), vec![
+            Instruction::new(IS::Mov, vec![reg(Register::BX), word(3)]),
+            Instruction::new(IS::Call, vec![word(7)]),
+            Instruction::new(IS::Mov, vec![mem("first"), reg(Register::AX)]),
+            Instruction::new(IS::Mov, vec![reg(Register::BX), word(20)]),
+            Instruction::new(IS::Call, vec![word(7)]),
+            Instruction::new(IS::Mov, vec![mem("second"), reg(Register::AX)]),
+            Instruction::new(IS::Jmp, vec![word(9)]),
+            // double: meant to return BX * 2 in AX, but unfinished
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(0)]),
+            Instruction::new(IS::Ret, vec![]),
+        ]).verbosity(Verbosity::Quiet).code_label("double", 7).build().unwrap();
+        let results = |cpu: &CPU| (cpu.read_label("first").unwrap(), cpu.read_label("second").unwrap());
+
+        let mut unfinished = program();
+        unfinished.run().unwrap();
+        assert_eq!(results(&unfinished), (Data::Word(0), Data::Word(0)));
+
+        // Stubbed at its entry, every call returns what the host computed, truncated to AX
+        let mut stubbed = program();
+        let entry = stubbed.code_label("double").unwrap();
+        stubbed.hook_before(entry, |view| {
+            assert_eq!(view.instruction(), 7);
+            let doubled = view.register(Register::BX) * 2;
+            view.set_register(Register::AX, 0x1_0000 + doubled);
+            HookAction::Return
+        });
+        let outcome = stubbed.run().unwrap();
+        assert_eq!(results(&stubbed), (Data::Word(6), Data::Word(40)));
+        // The stub runs in place of the subroutine's instructions
+        assert_eq!(outcome.instructions, 7);
+        assert!(stubbed.call_stack.is_empty());
+        let returned = stubbed.call_guest("double", &[Arg::Reg(Register::BX, 5)]).unwrap();
+        assert_eq!((returned.register(Register::AX), returned.instructions), (10, 0));
+
+        // Skipping the second call only stubs that one out
+        let mut skipped = program();
+        skipped.hook_before(4, |view| {
+            view.set_register(Register::AX, 99);
+            HookAction::SkipInstruction
+        });
+        skipped.run().unwrap();
+        assert_eq!(results(&skipped), (Data::Word(0), Data::Word(99)));
+
+        // Returning outside of a call faults like a RET would
+        let mut outside = program();
+        outside.hook_before(0, |_| HookAction::Return);
+        assert!(matches!(outside.run().unwrap_err().error(), CpuError::ReturnWithoutCall));
+    }
+
+    #[test]
+    fn run_debug_stops_at_breakpoints_and_resumes_past_them() {
+        let mut debugged = cpu(&[], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::CX), word(0)]),
+            Instruction::new(IS::Add, vec![reg(Register::CX), word(1)]),
+            Instruction::new(IS::Cmp, vec![reg(Register::CX), word(2)]),
+            Instruction::new(IS::Jnz, vec![word(1)]),
+        ]);
+        debugged.add_breakpoint(1);
+        for passes in [0, 1] {
+            assert_eq!(debugged.run_debug().unwrap(), StopReason::Breakpoint(1));
+            assert_eq!(debugged.register(Register::CX), passes);
+        }
+        assert!(debugged.remove_breakpoint(1));
+        assert!(!debugged.remove_breakpoint(1));
+        assert_eq!(debugged.run_debug().unwrap(), StopReason::Exited(0));
+        assert_eq!(debugged.register(Register::CX), 2);
+    }
+
+    #[test]
+    fn print_string_writes_up_to_the_terminator_and_returns_the_length() {
+        let mut code = Vec::new();
+        for (label, length) in [("a", "length_a"), ("b", "length_b"), ("c", "length_c")] {
+            code.push(Instruction::new(IS::Mov, vec![reg(syscall_abi::NUMBER), word(syscall_abi::SYS_PRINT_STRING as u16)]));
+            code.push(Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG2), Operand::LabelOffset(label.to_string())]));
+            code.push(Instruction::new(IS::Syscall, vec![]));
+            code.push(Instruction::new(IS::Mov, vec![mem(length), reg(syscall_abi::RESULT)]));
+        }
+        let data = [
+            ("a", Data::Dword(u32::from_le_bytes(*b"Hi!\0"))),
+            ("b", Data::Dword(u32::from_le_bytes(*b"Yo\0Z"))),
+            // The empty string
+            ("c", Data::Byte(0)),
+            ("length_a", Data::Word(0xFFFF)),
+            ("length_b", Data::Word(0xFFFF)),
+            ("length_c", Data::Word(0xFFFF)),
+        ];
+        let data = data.iter().map(|(label, data)| (label.to_string(), data.clone())).collect();
+        let output = SharedOutput::default();
+        let mut printing = CpuBuilder::new(data, code).verbosity(Verbosity::Quiet).guest_output(output.clone()).build().unwrap();
+        printing.run().unwrap();
+        assert_eq!(output.0.borrow().as_slice(), b"Hi!Yo");
+        assert_eq!(printing.read_label("length_a").unwrap(), Data::Word(3));
+        assert_eq!(printing.read_label("length_b").unwrap(), Data::Word(2));
+        assert_eq!(printing.read_label("length_c").unwrap(), Data::Word(0));
+    }
+
+    /// Guest input failing every read
+    struct BrokenInput;
+
+    impl Read for BrokenInput {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("device unplugged"))
+        }
+    }
+
+    #[test]
+    fn read_syscall_stops_at_the_end_of_input_and_faults_instead_of_panicking() {
+        let reading = |length, input: Box<dyn Read>| {
+            let data = [("buffer", Data::Dword(0)), ("tail", Data::Byte(0x77))];
+            let data = data.iter().map(|(label, data)| (label.to_string(), data.clone())).collect();
+            let mut reading = CpuBuilder::new(data, vec![
+                Instruction::new(IS::Mov, vec![reg(syscall_abi::NUMBER), word(syscall_abi::SYS_READ as u16)]),
+                Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG2), Operand::LabelOffset("buffer".to_string())]),
+                Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG3), word(length)]),
+                Instruction::new(IS::Syscall, vec![]),
+            ]).verbosity(Verbosity::Quiet).guest_input(input).build().unwrap();
+            let result = reading.run();
+            (reading, result)
+        };
+        // Only three of the four requested bytes are available
+        let (short, result) = reading(4, Box::new(&b"abc"[..]));
+        result.unwrap();
+        assert_eq!(short.register(syscall_abi::RESULT), 3);
+        assert_eq!(short.read_label("buffer").unwrap(), Data::Dword(u32::from_le_bytes(*b"abc\0")));
+        assert_eq!(short.read_label("tail").unwrap(), Data::Byte(0x77));
+
+        // A buffer reaching past memory is rejected before reading, and a failing input faults
+        let (_, result) = reading(0x400, Box::new(&b"abc"[..]));
+        assert!(matches!(result.unwrap_err().error, CpuError::Syscall(message) if message.contains("MemoryOutOfBounds")));
+        let (_, result) = reading(4, Box::new(BrokenInput));
+        assert!(matches!(result.unwrap_err().error, CpuError::Syscall(message) if message.contains("device unplugged")));
+    }
+
+    /// Parses a flat JSON object whose values are strings, numbers, booleans or arrays of numbers.
+    ///
+    /// Returns each field's value as text, unquoted and unescaped for strings, or `None` if the object is malformed.
+    fn parse_json_object(line: &str) -> Option<BTreeMap<String, String>> {
+        let mut chars = line.chars().peekable();
+        let string = |chars: &mut std::iter::Peekable<std::str::Chars>| -> Option<String> {
+            (chars.next()? == '"').then_some(())?;
+            let mut text = String::new();
+            loop {
+                match chars.next()? {
+                    '"' => return Some(text),
+                    '\\' => match chars.next()? {
+                        'u' => text.push(char::from_u32(u32::from_str_radix(&(0..4).map(|_| chars.next()).collect::<Option<String>>()?, 16).ok()?)?),
+                        escaped @ ('"' | '\\') => text.push(escaped),
+                        _ => return None,
+                    },
+                    character => text.push(character),
+                }
+            }
+        };
+        let mut fields = BTreeMap::new();
+        (chars.next()? == '{').then_some(())?;
+        loop {
+            let key = string(&mut chars)?;
+            (chars.next()? == ':').then_some(())?;
+            let value = match chars.peek()? {
+                '"' => string(&mut chars)?,
+                '[' => {
+                    let (mut array, mut quoted) = (String::new(), false);
+                    loop {
+                        let character = chars.next()?;
+                        array.push(character);
+                        match character {
+                            '"' => quoted = !quoted,
+                            ']' if !quoted => break,
+                            _ => {},
+                        }
+                    }
+                    // Arrays of numbers or of strings without commas
+                    array[1..array.len() - 1].split(',').filter(|item| !item.is_empty())
+                        .all(|item| item.parse::<u32>().is_ok() || (item.len() > 1 && item.starts_with('"') && item.ends_with('"')))
+                        .then_some(())?;
+                    array
+                },
+                _ => {
+                    let mut scalar = String::new();
+                    while !matches!(chars.peek(), Some(',' | '}') | None) {
+                        scalar.push(chars.next()?);
+                    }
+                    (scalar == "true" || scalar == "false" || scalar.parse::<u64>().is_ok()).then_some(())?;
+                    scalar
+                },
+            };
+            fields.insert(key, value);
+            match chars.next()? {
+                ',' => continue,
+                '}' => return chars.next().is_none().then_some(fields),
+                _ => return None,
+            }
+        }
+    }
+
+    #[test]
+    fn json_lines_observer_writes_one_object_per_event() {
+        let observe = |observer: JsonLinesObserver<SharedOutput>, output: &SharedOutput| {
+            let data = [("result", Data::Word(0))];
+            let data = data.iter().map(|(label, data)| (label.to_string(), data.clone())).collect();
+            let mut observed = CpuBuilder::new(data, vec![
+                Instruction::new(IS::Mov, vec![reg(Register::AX), word(5)]),
+                Instruction::new(IS::Mov, vec![reg(Register::CX), word(7)]),
+                Instruction::new(IS::Add, vec![reg(Register::AX), reg(Register::CX)]),
+                Instruction::new(IS::Mov, vec![mem("result"), reg(Register::AX)]),
+                Instruction::new(IS::Mov, vec![reg(syscall_abi::NUMBER), word(syscall_abi::SYS_EXIT as u16)]),
+                Instruction::new(IS::Syscall, vec![]),
+            ]).verbosity(Verbosity::Quiet).observer(observer).build().unwrap();
+            observed.run().unwrap();
+            let text = String::from_utf8(output.0.borrow().clone()).unwrap();
+            text.lines().map(|line| parse_json_object(line).unwrap_or_else(|| panic!("not a JSON object: {}", line))).collect::<Vec<_>>()
+        };
+        let output = SharedOutput::default();
+        let events = observe(JsonLinesObserver::new(output.clone()), &output);
+        let count = |name: &str| events.iter().filter(|event| event["event"] == name).count();
+        assert_eq!(count("instruction_executed"), 6);
+        // ax three times and cx once
+        assert_eq!(count("register_write"), 4);
+        assert_eq!(count("memory_write"), 1);
+        // 5 + 7 = 0b1100 only sets the parity flag
+        assert_eq!(count("flag_change"), 1);
+        assert_eq!(count("syscall"), 1);
+        assert_eq!(count("alu_operation"), 1);
+        assert_eq!(events.len(), 14);
+
+        let field = |event: &BTreeMap<String, String>, name: &str| event[name].clone();
+        let add: Vec<_> = events.iter().filter(|event| event["index"] == "2").collect();
+        assert_eq!(add.len(), 4);
+        assert_eq!(
+            ["mode", "width", "operands", "result", "flags"].map(|name| field(add[0], name)),
+            ["Add", "Word", "[5,7]", "12", "CF=0 OF=0 ZF=0 SF=0 PF=1 AF=0"],
+        );
+        assert_eq!((field(add[1], "register"), field(add[1], "old"), field(add[1], "new")), ("AX".to_string(), "5".to_string(), "12".to_string()));
+        assert_eq!((field(add[2], "flag"), field(add[2], "value")), ("PF".to_string(), "true".to_string()));
+        assert_eq!(field(add[3], "instruction"), "add ax, cx");
+        let write = events.iter().find(|event| event["event"] == "memory_write").unwrap();
+        assert_eq!((field(write, "index"), field(write, "offset"), field(write, "bytes")), ("3".to_string(), "0".to_string(), "[12,0]".to_string()));
+        let syscall = events.iter().find(|event| event["event"] == "syscall").unwrap();
+        assert_eq!((field(syscall, "index"), field(syscall, "step"), field(syscall, "number")), ("5".to_string(), "6".to_string(), "60".to_string()));
+
+        let output = SharedOutput::default();
+        let filtered = observe(JsonLinesObserver::new(output.clone()).only(&[EventKind::MemoryWrite, EventKind::Syscall]), &output);
+        let names: Vec<&str> = filtered.iter().map(|event| event["event"].as_str()).collect();
+        assert_eq!(names, ["memory_write", "syscall"]);
+        assert_eq!(json_string("say \"hi\"\\\n"), r#""say \"hi\"\\\u000a""#);
+    }
+
+    #[test]
+    fn labels_are_interned_once_and_resolved_by_id() {
+        let mut table = SymbolTable::new();
+        let first = table.intern("result");
+        assert_eq!(table.intern("result"), first);
+        let second = table.intern("count");
+        assert_ne!(second, first);
+        assert_eq!((table.name(first), table.name(second)), (Some("result"), Some("count")));
+        // Interning doesn't define the label
+        assert!(table.get(first).is_none());
+        assert!(table.lookup("result").is_none());
+
+        let loaded = cpu(&[("num", Data::Word(10)), ("result", Data::Word(0))], vec![
+            Instruction::new(IS::Add, vec![mem("result"), mem("num")]),
+        ]);
+        let Operand::Memory(MemOp::Symbol(id, label)) = &loaded.instruction_at(0).unwrap().operands[0] else {
+            panic!("{:?} wasn't interned", loaded.instruction_at(0));
+        };
+        assert_eq!(label, "result");
+        assert_eq!(loaded.symbols().name(*id), Some("result"));
+        assert_eq!(loaded.symbols().get(*id).unwrap().offset, loaded.symbol("result").unwrap().offset);
+        // The disassembly keeps the original names
+        assert_eq!(loaded.instruction_at(0).unwrap().to_string(), "add [result], [num]");
+    }
+
+    #[test]
+    fn each_instruction_updates_exactly_its_flags_like_x86() {
+        let mov = |register: Register, value: u16| Instruction::new(IS::Mov, vec![reg(register), word(value)]);
+        let binary = |opcode: IS, value: u16| Instruction::new(opcode, vec![reg(Register::AX), word(value)]);
+        // Setup, the instruction under test, then the flags it must leave as the x86 manuals define them.
+        // Every flag it doesn't update must keep its value from before it executed.
+        type Case = (Vec<Instruction>, Instruction, &'static [(&'static str, bool)]);
+        let cases: Vec<Case> = vec![
+            (vec![mov(Register::AX, 1)], binary(IS::Add, 0xFFFF),
+                &[("CF", true), ("ZF", true), ("SF", false), ("OF", false), ("PF", true), ("AF", true)]),
+            (vec![mov(Register::AX, 0x7FFF)], binary(IS::Add, 1),
+                &[("CF", false), ("ZF", false), ("SF", true), ("OF", true), ("PF", true), ("AF", true)]),
+            (vec![mov(Register::AX, 0)], binary(IS::Sub, 1),
+                &[("CF", true), ("ZF", false), ("SF", true), ("OF", false), ("PF", true), ("AF", true)]),
+            (vec![mov(Register::AX, 0x8000)], binary(IS::Sub, 1),
+                &[("CF", false), ("ZF", false), ("SF", false), ("OF", true), ("PF", true), ("AF", true)]),
+            (vec![mov(Register::AX, 5)], binary(IS::Cmp, 5),
+                &[("CF", false), ("ZF", true), ("SF", false), ("OF", false), ("PF", true), ("AF", false)]),
+            (vec![mov(Register::AX, 0xF0F0)], binary(IS::And, 0x0F0F),
+                &[("CF", false), ("ZF", true), ("SF", false), ("OF", false), ("PF", true)]),
+            (vec![mov(Register::AX, 0x8000)], binary(IS::Or, 1),
+                &[("CF", false), ("ZF", false), ("SF", true), ("OF", false), ("PF", false)]),
+            (vec![mov(Register::AX, 0)], Instruction::new(IS::Not, vec![reg(Register::AX)]), &[]),
+            (vec![], mov(Register::AX, 0), &[]),
+            (vec![mov(Register::AX, 7), mov(Register::DX, 0), mov(Register::CX, 2)],
+                Instruction::new(IS::Div, vec![reg(Register::CX)]), &[]),
+            // 0x09 + 0x08 = 0x11 with AF set, adjusted to 0x17
+            (vec![mov(Register::AX, 9), binary(IS::Add, 8)], Instruction::new(IS::Daa, vec![]),
+                &[("CF", false), ("ZF", false), ("SF", false), ("PF", true), ("AF", true)]),
+            // 0x10 - 0x01 = 0x0F, adjusted to 0x09
+            (vec![mov(Register::AX, 0x10), binary(IS::Sub, 1)], Instruction::new(IS::Das, vec![]),
+                &[("CF", false), ("ZF", false), ("SF", false), ("PF", true), ("AF", true)]),
+            (vec![mov(Register::AX, 0x3F)], Instruction::new(IS::Aam, vec![]),
+                &[("ZF", false), ("SF", false), ("PF", true)]),
+            (vec![mov(Register::AX, 0x0603)], Instruction::new(IS::Aad, vec![]),
+                &[("ZF", false), ("SF", false), ("PF", true)]),
+        ];
+
+        for (setup, instruction, expected) in cases {
+            let opcode = instruction.opcode.clone();
+            let mask = expected.iter()
+                .map(|(name, _)| 1 << FLAG_NAMES.iter().position(|flag| flag == name).unwrap())
+                .fold(0, |mask, flag| mask | flag);
+            assert_eq!(opcode.flag_mask(), mask, "{:?} updates other flags than x86", opcode);
+
+            // With every flag cleared, then set, before the setup runs
+            for preset in [false, true] {
+                let mut code = setup.clone();
+                code.push(instruction.clone());
+                let mut cpu = cpu(&[], code);
+                for index in [0, 1, 2, 3, 7, 8] {
+                    cpu.flags[index].set_value(preset as u8);
+                }
+                for _ in &setup {
+                    cpu.step().unwrap();
+                }
+                let before = cpu.flags_word();
+                cpu.step().unwrap();
+                for (index, name) in FLAG_NAMES.iter().enumerate() {
+                    let value = match expected.iter().find(|(flag, _)| flag == name) {
+                        Some((_, value)) => *value,
+                        None => before & (1 << index) != 0,
+                    };
+                    assert_eq!(flag(&cpu, name), value, "{} after {} with the flags preset to {}", name, instruction, preset);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn aam_splits_al_into_digits_and_aad_combines_them_back() {
+        let byte = |value: u8| Operand::Immediate(Data::Byte(value));
+        let mut adjusting = cpu(&[], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(0x3F)]),
+            Instruction::new(IS::Aam, vec![]),
+            Instruction::new(IS::Mov, vec![reg(Register::BX), reg(Register::AX)]),
+            Instruction::new(IS::Aad, vec![]),
+            Instruction::new(IS::Mov, vec![reg(Register::CX), reg(Register::AX)]),
+            // 0x3F is 3 * 16 + 15 in base 16
+            Instruction::new(IS::Aam, vec![byte(16)]),
+            Instruction::new(IS::Mov, vec![reg(Register::DX), reg(Register::AX)]),
+            Instruction::new(IS::Aad, vec![byte(16)]),
+        ]);
+        adjusting.run().unwrap();
+        // 63 = AH 6, AL 3
+        assert_eq!(adjusting.register(Register::BX), 0x0603);
+        assert_eq!(adjusting.register(Register::CX), 0x003F);
+        assert_eq!(adjusting.register(Register::DX), 0x030F);
+        assert_eq!(adjusting.register(Register::AX), 0x003F);
+
+        let mut dividing = cpu(&[], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(0x3F)]),
+            Instruction::new(IS::Aam, vec![byte(0)]),
+        ]);
+        let fault = dividing.run().unwrap_err();
+        assert!(matches!(fault.error, CpuError::DivideError), "{:?}", fault.error);
+        assert_eq!(dividing.register(Register::AX), 0x3F);
+    }
+
+    #[test]
+    fn run_outcome_reports_the_exit_code_and_counters_of_the_run() {
+        let code = vec![
+            Instruction::new(IS::Mov, vec![reg(Register::CX), word(0)]),
+            Instruction::new(IS::Add, vec![reg(Register::CX), word(1)]),
+            Instruction::new(IS::Cmp, vec![reg(Register::CX), word(3)]),
+            Instruction::new(IS::Jnz, vec![word(1)]),
+            Instruction::new(IS::Mov, vec![reg(syscall_abi::NUMBER), word(syscall_abi::SYS_EXIT as u16)]),
+            Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG1), word(3)]),
+            Instruction::new(IS::Syscall, vec![]),
+        ];
+        let build = |data_section: HashMap<String, Data>| {
+            let clock = MockClock { time: Default::default(), step: Duration::from_millis(250) };
+            CpuBuilder::new(data_section, code.clone()).verbosity(Verbosity::Quiet).clock(clock).build().unwrap()
+        };
+
+        let outcome = build(HashMap::new()).run().unwrap();
+        // 1 + 3 iterations of 3 instructions + 3, costing 1 + 3 * (1 + 1 + 2) + (1 + 1 + 10) cycles
+        assert_eq!(outcome, RunOutcome {
+            exit_code: 3,
+            instructions: 13,
+            cycles: 25,
+            // The clock is read once when the run starts and once when it ends
+            duration: Duration::from_millis(250),
+            warnings: false,
+        });
+        assert_eq!(outcome.to_string(), "Exited with code 3 after 13 instructions (25 cycles) in 250ms");
+
+        // An unused label is a validation warning
+        let outcome = build(HashMap::from([("unused".to_string(), Data::Byte(0))])).run().unwrap();
+        assert!(outcome.warnings);
+        assert_eq!(outcome.to_string(), "Exited with code 3 after 13 instructions (25 cycles) in 250ms with warnings");
+    }
+
+    #[test]
+    fn uninitialized_reads_are_observed_as_warnings_or_fail_the_instruction() {
+        let events = std::rc::Rc::new(RefCell::new(Vec::new()));
+        struct Recorder(std::rc::Rc<RefCell<Vec<(usize, Event)>>>);
+        impl Observer for Recorder {
+            fn event(&mut self, context: &ExecContext, event: &Event) {
+                if event.kind() == EventKind::Warning {
+                    self.0.borrow_mut().push((context.ip, event.clone()));
+                }
+            }
+        }
+        let code = vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(5)]),
+            Instruction::new(IS::Add, vec![reg(Register::CX), reg(Register::AX)]),
+        ];
+        let mut warning = CpuBuilder::new(HashMap::new(), code.clone())
+            .verbosity(Verbosity::Quiet)
+            .observer(Recorder(events.clone()))
+            .build()
+            .unwrap();
+        warning.track_uninitialized(UninitializedPolicy::Warn);
+        warning.run().unwrap();
+        assert_eq!(warning.warnings(), [Warning::UninitializedRegister(1, Register::CX)]);
+        assert_eq!(*events.borrow(), [(1, Event::Warning(Warning::UninitializedRegister(1, Register::CX)))]);
+
+        let mut error = cpu(&[], code);
+        error.track_uninitialized(UninitializedPolicy::Error);
+        let fault = error.run().unwrap_err();
+        assert!(matches!(&fault.error, CpuError::UninitializedRead(Warning::UninitializedRegister(1, Register::CX))), "{:?}", fault.error);
+        assert_eq!(error.register(Register::CX), 0);
+        assert!(error.warnings().is_empty());
+    }
+
+    #[test]
+    fn scheduler_runs_programs_round_robin_in_slices_of_the_quantum() {
+        // Counts CX up to the number, then stores it: 2 + 3 * number instructions
+        let program = |number: u16| cpu(&[("result", Data::Word(0))], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::CX), word(0)]),
+            Instruction::new(IS::Add, vec![reg(Register::CX), word(1)]),
+            Instruction::new(IS::Cmp, vec![reg(Register::CX), word(number)]),
+            Instruction::new(IS::Jnz, vec![word(1)]),
+            Instruction::new(IS::Mov, vec![mem("result"), reg(Register::CX)]),
+        ]);
+        let mut scheduler = Scheduler::new(vec![program(1), program(2), program(3)], 2);
+        let outcomes = scheduler.run();
+
+        let instructions: Vec<u64> = outcomes.iter().map(|outcome| outcome.as_ref().unwrap().instructions).collect();
+        assert_eq!(instructions, [5, 8, 11]);
+        for (index, result) in [1, 2, 3].into_iter().enumerate() {
+            assert_eq!(scheduler.cpu(index).unwrap().read_label("result").unwrap(), Data::Word(result));
+        }
+        let slice = |cpu, start, instructions| Slice { cpu, start, instructions };
+        assert_eq!(scheduler.slices(), [
+            slice(0, 0, 2), slice(1, 2, 2), slice(2, 4, 2),
+            slice(0, 6, 2), slice(1, 8, 2), slice(2, 10, 2),
+            // The first program stops after its fifth instruction, leaving the rotation
+            slice(0, 12, 1), slice(1, 13, 2), slice(2, 15, 2),
+            slice(1, 17, 2), slice(2, 19, 2),
+            slice(2, 21, 2), slice(2, 23, 1),
+        ]);
+
+        // A faulting program stops with its fault while the others finish
+        let mut limited = program(3);
+        limited.instruction_limit = Some(4);
+        let outcomes = Scheduler::new(vec![limited, program(1)], 3).run();
+        assert!(matches!(outcomes[0].as_ref().unwrap_err().error, CpuError::InstructionLimit(4)));
+        assert_eq!(outcomes[1].as_ref().unwrap().instructions, 5);
+    }
+
+    #[test]
+    fn yielding_ends_a_scheduler_slice_early_and_does_nothing_under_run() {
+        // Counts CX up to 3, yielding every iteration: 17 instructions, a yield every 5
+        let program = || cpu(&[("result", Data::Word(0))], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::CX), word(0)]),
+            Instruction::new(IS::Add, vec![reg(Register::CX), word(1)]),
+            Instruction::new(IS::Mov, vec![reg(syscall_abi::NUMBER), word(syscall_abi::SYS_YIELD as u16)]),
+            Instruction::new(IS::Syscall, vec![]),
+            Instruction::new(IS::Cmp, vec![reg(Register::CX), word(3)]),
+            Instruction::new(IS::Jnz, vec![word(1)]),
+            Instruction::new(IS::Mov, vec![mem("result"), reg(Register::CX)]),
+        ]);
+        let mut scheduler = Scheduler::new(vec![program(), program()], 10);
+        let outcomes = scheduler.run();
+        assert!(outcomes.iter().all(|outcome| outcome.as_ref().unwrap().instructions == 17));
+        let slice = |cpu, start, instructions| Slice { cpu, start, instructions };
+        // Without yielding, each program would run in a slice of 10 instructions then one of 7
+        assert_eq!(scheduler.slices(), [
+            slice(0, 0, 4), slice(1, 4, 4),
+            slice(0, 8, 5), slice(1, 13, 5),
+            slice(0, 18, 5), slice(1, 23, 5),
+            slice(0, 28, 3), slice(1, 31, 3),
+        ]);
+
+        // Stepping reports the yield, running goes straight past it
+        let mut stepped = program();
+        let reasons: Vec<StopReason> = (0..4).map(|_| stepped.step().unwrap()).collect();
+        assert_eq!(reasons, [StopReason::Stepped, StopReason::Stepped, StopReason::Stepped, StopReason::Yielded]);
+        assert_eq!(stepped.step().unwrap(), StopReason::Stepped);
+        let mut run = program();
+        assert_eq!(run.run().unwrap().instructions, 17);
+        assert_eq!(run.read_label("result").unwrap(), Data::Word(3));
+        assert_eq!(program().run_debug().unwrap(), StopReason::Exited(0));
+    }
+
+    #[test]
+    fn labels_are_read_and_written_at_their_declared_width() {
+        let mut labels = cpu(&[("byte", Data::Byte(0)), ("word", Data::Word(0)), ("dword", Data::Dword(0))], vec![]);
+        for (label, data) in [("byte", Data::Byte(0xAB)), ("word", Data::Word(0xABCD)), ("dword", Data::Dword(0x89AB_CDEF))] {
+            labels.write_label(label, data.clone()).unwrap();
+            assert_eq!(labels.read_label(label).unwrap(), data);
+        }
+        // Each value fills its label's bytes in little endian order, leaving the neighbouring labels alone
+        for (label, bytes) in [("byte", &[0xAB][..]), ("word", &[0xCD, 0xAB]), ("dword", &[0xEF, 0xCD, 0xAB, 0x89])] {
+            let offset = labels.symbol(label).unwrap().offset as usize;
+            assert_eq!(labels.memory_unit.read_bytes(offset, bytes.len()).unwrap(), bytes);
+        }
+
+        assert!(matches!(labels.read_label("wordd"), Err(CpuError::UndeclaredLabel(label, Some(suggestion))) if label == "wordd" && suggestion == "word"));
+        assert!(matches!(labels.write_label("missing", Data::Byte(1)), Err(CpuError::UndeclaredLabel(label, None)) if label == "missing"));
+
+        let mismatch = labels.write_label("word", Data::Dword(1)).unwrap_err();
+        assert!(matches!(&mismatch, CpuError::WidthMismatch(label, DataWidth::Dword) if label == "word"), "{:?}", mismatch);
+        assert_eq!(labels.read_label("word").unwrap(), Data::Word(0xABCD));
+    }
+
+    #[test]
+    fn mov_leaves_the_flags_word_bit_identical() {
+        assert_eq!(IS::Mov.flag_mask(), 0);
+        let mut random = XorShift(0x2545_F491_4F6C_DD1D);
+        let data = [("w1", Data::Word(1)), ("w2", Data::Word(2)), ("d1", Data::Dword(3))];
+        for _ in 0..500 {
+            let value = random.next() as u32;
+            // Destinations and sources of each width, memory operands last
+            let (destinations, sources) = match random.below(2) {
+                0 => (
+                    vec![reg(Register::AX), reg(Register::CX), mem("w1"),
+                        Operand::Memory(MemOp::Ptr(DataWidth::Word, "w2".to_string(), 0)),
+                        Operand::Memory(MemOp::Indirect(DataWidth::Word, Register::EBX, 0))],
+                    vec![reg(Register::DX), Operand::Immediate(Data::Word(value as u16)), Operand::LabelOffset("d1".to_string()), mem("w2")],
+                ),
+                _ => (
+                    vec![reg(Register::EAX), reg(Register::ECX), mem("d1"),
+                        Operand::Memory(MemOp::Indirect(DataWidth::Dword, Register::EBX, 0))],
+                    vec![reg(Register::EDX), Operand::Immediate(Data::Dword(value)), mem("d1")],
+                ),
+            };
+            let destination = destinations[random.below(destinations.len() as u64) as usize].clone();
+            let mut source = sources[random.below(sources.len() as u64) as usize].clone();
+            // x86 has no memory to memory moves
+            if matches!((&destination, &source), (Operand::Memory(_), Operand::Memory(_))) {
+                source = sources[0].clone();
+            }
+
+            let mut moving = cpu(&data, vec![Instruction::new(IS::Mov, vec![destination, source])]);
+            let flags = random.below(1 << FLAG_NAMES.len());
+            for (index, flag) in moving.flags.iter_mut().enumerate() {
+                flag.set_value((flags >> index) as u8 & 1);
+            }
+            moving.registers.get_register(Register::EDX).set_value(Data::Dword(value));
+            let target = moving.symbol(["w1", "d1"][random.below(2) as usize]).unwrap().offset;
+            moving.registers.get_register(Register::EBX).set_value(Data::Dword(target));
+
+            let before = moving.flags_word();
+            moving.step().unwrap();
+            assert_eq!(moving.flags_word(), before, "{}", moving.instruction_at(0).unwrap());
+        }
+    }
+
+    #[test]
+    fn stack_canaries_catch_a_callee_overwriting_its_frame() {
+        // The stack follows the single word label, the callee writes 0xBEEF at `[anchor+displacement]`
+        let program = |displacement: i32| vec![
+            Instruction::new(IS::Call, vec![word(3)]),
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(1)]),
+            Instruction::new(IS::Jmp, vec![word(5)]),
+            Instruction::new(IS::Mov, vec![Operand::Memory(MemOp::Ptr(DataWidth::Word, "anchor".to_string(), displacement)), word(0xBEEF)]),
+            Instruction::new(IS::Ret, vec![]),
+        ];
+        let build = |code, canaries| {
+            let data = HashMap::from([("anchor".to_string(), Data::Word(0))]);
+            CpuBuilder::new(data, code).verbosity(Verbosity::Quiet).stack(16).stack_canaries(canaries).build().unwrap()
+        };
+
+        // A well-behaved callee, writing below its frame, returns normally and leaves SP where it started
+        let mut calling = build(program(0), true);
+        assert_eq!(calling.registers.SP[0].get_value(), 18);
+        calling.step().unwrap();
+        // The return address is pushed first, then the canary under it at the new SP
+        assert_eq!(calling.registers.SP[0].get_value(), 14);
+        assert_eq!(calling.memory_unit.read_bytes(14, 4).unwrap(), [0x5A, 0xA5, 1, 0]);
+        calling.run().unwrap();
+        assert_eq!(calling.register(Register::AX), 1);
+        assert_eq!(calling.registers.SP[0].get_value(), 18);
+
+        // Overwriting [sp] in the callee clobbers the canary
+        let mut corrupting = build(program(14), true);
+        let fault = corrupting.run().unwrap_err();
+        assert!(matches!(fault.error, CpuError::CorruptedReturnAddress(STACK_CANARY, 0xBEEF, 1)), "{:?}", fault.error);
+        assert_eq!(fault.context.ip, 4);
+
+        // Without canaries RET returns wherever the overwritten return address points
+        let mut redirected = build(program(16), false);
+        assert!(matches!(redirected.run().unwrap_err().error, CpuError::InvalidInstructionPointer(0xBEEF, Some(4))));
+
+        let data = HashMap::new();
+        assert!(matches!(CpuBuilder::new(data, vec![]).stack_canaries(true).build(), Err(BuildError::CanariesWithoutStack)));
+    }
+
+    #[test]
+    fn ret_cross_checks_the_popped_return_address_with_the_shadow_stack() {
+        // The callee stores the return address at `[anchor+displacement]`, replacing its own at 16 to skip `mov ax, 1`
+        let program = |displacement: i32| vec![
+            Instruction::new(IS::Call, vec![word(4)]),
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(1)]),
+            Instruction::new(IS::Mov, vec![reg(Register::BX), word(2)]),
+            Instruction::new(IS::Jmp, vec![word(6)]),
+            Instruction::new(IS::Mov, vec![Operand::Memory(MemOp::Ptr(DataWidth::Word, "anchor".to_string(), displacement)), word(2)]),
+            Instruction::new(IS::Ret, vec![]),
+        ];
+        let build = |displacement, policy| {
+            let data = HashMap::from([("anchor".to_string(), Data::Word(0))]);
+            CpuBuilder::new(data, program(displacement))
+                .verbosity(Verbosity::Quiet)
+                .stack(16)
+                .shadow_stack_policy(policy)
+                .build()
+                .unwrap()
+        };
+
+        let mut matching = build(0, ShadowStackPolicy::Error);
+        matching.run().unwrap();
+        assert_eq!((matching.register(Register::AX), matching.register(Register::BX)), (1, 2));
+        assert!(matching.warnings().is_empty());
+
+        // By default the program returns where it asked to, with a warning
+        let mut switching = build(16, ShadowStackPolicy::Warn);
+        switching.run().unwrap();
+        assert_eq!((switching.register(Register::AX), switching.register(Register::BX)), (0, 2));
+        assert_eq!(switching.warnings(), [Warning::ShadowStackMismatch(5, 2, 1)]);
+        assert!(switching.call_stack.is_empty());
+
+        let mut failing = build(16, ShadowStackPolicy::Error);
+        let fault = failing.run().unwrap_err();
+        assert!(matches!(fault.error, CpuError::ShadowStackMismatch(2, 1)), "{:?}", fault.error);
+        assert_eq!(fault.context.ip, 5);
+        assert_eq!(fault.context.call_stack, [1]);
+    }
+
+    #[test]
+    fn register_conditions_stop_run_debug_at_the_exact_iteration() {
+        let countdown = || cpu(&[], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::CX), word(5)]),
+            Instruction::new(IS::Sub, vec![reg(Register::CX), word(1)]),
+            Instruction::new(IS::Cmp, vec![reg(Register::CX), word(0)]),
+            Instruction::new(IS::Jnz, vec![word(1)]),
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(7)]),
+        ]);
+
+        // CX reaches 0 in the fifth iteration, after 1 + 4 * 3 + 1 instructions, and only stops there once
+        let mut equals = countdown();
+        equals.break_when(Register::CX, Condition::Equals(0));
+        assert_eq!(equals.run_debug().unwrap(), StopReason::RegisterCondition(Register::CX, 1, 0));
+        assert_eq!(equals.instruction_count, 14);
+        assert_eq!(equals.registers.SP[2].get_value(), 2);
+        assert_eq!(equals.run_debug().unwrap(), StopReason::Exited(0));
+        assert_eq!(equals.register(Register::AX), 7);
+
+        // Changed stops on every write of a new value: the MOV, then each SUB
+        let mut changed = countdown();
+        changed.break_when(Register::CX, Condition::Changed);
+        let mut stops = Vec::new();
+        while let StopReason::RegisterCondition(register, old, new) = changed.run_debug().unwrap() {
+            assert_eq!(register, Register::CX);
+            stops.push((old, new, changed.instruction_count));
+        }
+        assert_eq!(stops, [(0, 5, 1), (5, 4, 2), (4, 3, 5), (3, 2, 8), (2, 1, 11), (1, 0, 14)]);
+
+        let mut less_than = countdown();
+        less_than.break_when(Register::CX, Condition::LessThan(3));
+        assert_eq!(less_than.run_debug().unwrap(), StopReason::RegisterCondition(Register::CX, 3, 2));
+        less_than.clear_conditions();
+        assert_eq!(less_than.run_debug().unwrap(), StopReason::Exited(0));
+    }
+
+    #[test]
+    fn watch_expressions_evaluate_every_node_and_report_changes_and_stop_at_the_exact_step() {
+        let mut state = cpu(&[("result", Data::Word(290))], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::CX), word(4)]),
+            Instruction::new(IS::Cmp, vec![reg(Register::CX), word(4)]),
+        ]);
+        state.run().unwrap();
+        for (expression, value) in [
+            ("cx", 4), ("CX - 1", 3), ("zf", 1), ("CF", 0), ("[result]", 290), ("0x10", 16), ("-cx", -4),
+            ("[result] == 290", 1), ("cx != 4", 0), ("cx < 5", 1), ("cx <= 3", 0), ("cx > 3", 1), ("cx >= 5", 0),
+            ("zf && cx < 5", 1), ("cf || cx == 0", 0), ("!zf", 0), ("2 + 3 * cx", 14), ("(2 + 3) * cx", 20), ("1 - 2 - 3", -4),
+        ] {
+            assert_eq!(state.evaluate(expression), Ok(value), "{}", expression);
+        }
+        assert_eq!(
+            "zf && cx < 5".parse::<Expr>(),
+            Ok(Expr::Binary(BinaryOp::And, Box::new(Expr::Flag(2)), Box::new(Expr::Binary(BinaryOp::Less, Box::new(Expr::Register(Register::CX)), Box::new(Expr::Literal(5)))))),
+        );
+        for invalid in ["", "cx +", "si", "[result", "cx 1", "(cx"] {
+            assert!(state.evaluate(invalid).is_err(), "{}", invalid);
+        }
+        assert!(state.evaluate("[reslut]").unwrap_err().contains("UndeclaredLabel"));
+
+        // Counts CX down from 5, one SUB per iteration
+        let countdown = || CpuBuilder::new(HashMap::from([("count".to_string(), Data::Word(0))]), vec![
+            Instruction::new(IS::Mov, vec![reg(Register::CX), word(5)]),
+            Instruction::new(IS::Sub, vec![reg(Register::CX), word(1)]),
+            Instruction::new(IS::Add, vec![mem("count"), word(1)]),
+            Instruction::new(IS::Cmp, vec![reg(Register::CX), word(0)]),
+            Instruction::new(IS::Jnz, vec![word(1)]),
+        ]).verbosity(Verbosity::Quiet);
+        struct Recorder(std::rc::Rc<RefCell<Vec<(usize, u64, Event)>>>);
+        impl Observer for Recorder {
+            fn event(&mut self, context: &ExecContext, event: &Event) {
+                if event.kind() == EventKind::WatchChanged {
+                    self.0.borrow_mut().push((context.ip, context.step_number, event.clone()));
+                }
+            }
+        }
+        let events = std::rc::Rc::default();
+        let mut watched = countdown().observer(Recorder(std::rc::Rc::clone(&events))).build().unwrap();
+        watched.add_watch("[count] * 10").unwrap();
+        assert!(watched.add_watch("cx +").is_err());
+        assert_eq!(watched.run_debug().unwrap(), StopReason::Exited(0));
+        let expected: Vec<(usize, u64, Event)> = (1..=5)
+            .map(|count| (2, 4 * count as u64 - 1, Event::WatchChanged("[count] * 10".to_string(), 10 * (count - 1), 10 * count)))
+            .collect();
+        assert_eq!(*events.borrow(), expected);
+
+        // [count] reaches 3 with the ADD of the third iteration, the 11th instruction
+        let mut stopping = countdown().build().unwrap();
+        stopping.break_when_expr("[count] == 3 && cx < 5").unwrap();
+        assert_eq!(stopping.run_debug().unwrap(), StopReason::ExpressionCondition("[count] == 3 && cx < 5".to_string()));
+        assert_eq!((stopping.instruction_count, stopping.ip()), (11, 3));
+        // The count never gets back to 3, so the run goes on to the end
+        assert_eq!(stopping.run_debug().unwrap(), StopReason::Exited(0));
+        assert!(stopping.break_when_expr("[total] == 1").is_err());
+    }
+
+    #[test]
+    fn last_writer_flips_to_the_second_store_of_a_label() {
+        let mut storing = cpu(&[("before", Data::Byte(0)), ("value", Data::Word(0))], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(1)]),
+            Instruction::new(IS::Mov, vec![mem("value"), reg(Register::AX)]),
+            Instruction::new(IS::Add, vec![reg(Register::AX), word(1)]),
+            Instruction::new(IS::Mov, vec![mem("value"), reg(Register::AX)]),
+        ]);
+        storing.track_writers();
+        let offset = storing.symbol("value").unwrap().offset as usize;
+        assert_eq!(storing.last_writer_of_address(offset), None);
+
+        storing.step().unwrap();
+        storing.step().unwrap();
+        assert_eq!(storing.last_writer_of_address(offset), Some(1));
+        assert_eq!(storing.last_writer_of_address(offset + 1), Some(1));
+        storing.step().unwrap();
+        assert_eq!(storing.last_writer_of_register(Register::AX), Some(2));
+        assert_eq!(storing.last_writer_of_address(offset), Some(1));
+        storing.step().unwrap();
+        assert_eq!(storing.last_writer_of_address(offset), Some(3));
+        assert_eq!(storing.last_writer_of_address(offset + 1), Some(3));
+        // Neither the neighbouring label nor BX were ever written
+        assert_eq!(storing.last_writer_of_address(storing.symbol("before").unwrap().offset as usize), None);
+        assert_eq!(storing.last_writer_of_register(Register::BX), None);
+
+        // A write by the host clears the writer
+        storing.write_label("value", Data::Word(9)).unwrap();
+        assert_eq!(storing.last_writer_of_address(offset), None);
+    }
+
+    #[test]
+    fn golden_disassembly() {
+        let mut program = cpu(&[("num", Data::Word(10)), ("flag", Data::Byte(1)), ("total", Data::Dword(0))], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::BX), mem("num")]),
+            Instruction::new(IS::Add, vec![mem("num"), reg(Register::BX)]),
+            Instruction::new(IS::Mov, vec![reg(Register::ECX), Operand::LabelOffset("total".to_string())]),
+            Instruction::new(IS::Mov, vec![reg(Register::EAX), Operand::Memory(MemOp::Indirect(DataWidth::Dword, Register::ECX, 0))]),
+            Instruction::new(IS::Cmp, vec![Operand::Memory(MemOp::Ptr(DataWidth::Byte, "flag".to_string(), 0)), Operand::Immediate(Data::Byte(1))]),
+            Instruction::new(IS::Jz, vec![word(7)]),
+            Instruction::new(IS::Jmp, vec![Operand::JmpRel(-7)]),
+            Instruction::new(IS::Syscall, vec![]),
+        ]);
+        let mut golden = program.disassemble(&DisasmOptions::default());
+        // The current values are read live, after the first two instructions doubled num
+        program.step().unwrap();
+        program.step().unwrap();
+        golden += "\n";
+        golden += &program.disassemble(&DisasmOptions { current_values: true });
+        compare_golden("disassembly", &golden);
+    }
+
+    #[test]
+    fn fault_accessors_describe_an_out_of_bounds_read_inside_a_call() {
+        let mut reading = cpu(&[("table", Data::Word(0))], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::BX), word(0x1234)]),
+            Instruction::new(IS::Call, vec![word(3)]),
+            Instruction::new(IS::Jmp, vec![word(5)]),
+            Instruction::new(IS::Cmp, vec![reg(Register::BX), word(0x1234)]),
+            Instruction::new(IS::Mov, vec![reg(Register::AX), Operand::Memory(MemOp::Ptr(DataWidth::Word, "table".to_string(), 5000))]),
+        ]);
+        let fault = reading.run().unwrap_err();
+        assert!(matches!(fault.error(), CpuError::AddressOutOfBounds(label, 5000) if label == "table"), "{:?}", fault.error());
+        let context = fault.context();
+        assert_eq!(context.ip(), 4);
+        assert_eq!(context.instruction(), "mov ax, word ptr [table+5000]");
+        assert_eq!(context.registers(), [0, 0x1234, 0, 0, 0, 0, 0, 0]);
+        // The CMP left ZF set
+        assert_eq!(context.flags_word(), FLAG_ZF | FLAG_PF);
+        assert_eq!(context.call_stack(), [2]);
+        assert_eq!(context.memory(), "[00 00]");
+        assert!(fault.to_string().contains("IP: 0004  mov ax, word ptr [table+5000]"), "{}", fault);
+    }
+
+    #[test]
+    fn near_miss_labels_and_mnemonics_get_suggestions() {
+        let data = || HashMap::from([("result".to_string(), Data::Word(0)), ("count".to_string(), Data::Word(0))]);
+        let build = |label: &str| {
+            CpuBuilder::new(data(), vec![Instruction::new(IS::Mov, vec![reg(Register::AX), mem(label)])])
+                .verbosity(Verbosity::Quiet)
+                .build()
+        };
+        let error = build("reslut").unwrap_err();
+        assert!(matches!(&error, BuildError::UndeclaredLabel(0, label, Some(suggestion)) if label == "reslut" && suggestion == "result"), "{:?}", error);
+        assert_eq!(error.to_string(), "Instruction 0 references undeclared label \"reslut\", did you mean \"result\"?");
+        // Nothing declared is close to the label
+        let error = build("total").unwrap_err();
+        assert!(matches!(&error, BuildError::UndeclaredLabel(0, label, None) if label == "total"), "{:?}", error);
+        assert_eq!(error.to_string(), "Instruction 0 references undeclared label \"total\"");
+
+        // Labels looked up at runtime get the same suggestions
+        let cpu = build("count").unwrap();
+        assert!(matches!(cpu.read_label("cont"), Err(CpuError::UndeclaredLabel(_, Some(suggestion))) if suggestion == "count"));
+        assert!(matches!(cpu.read_label("x"), Err(CpuError::UndeclaredLabel(_, None))));
+
+        assert_eq!("MOV".parse::<IS>(), Ok(IS::Mov));
+        assert_eq!("sycall".parse::<IS>(), Err("Unknown instruction: sycall, did you mean syscall?".to_string()));
+        assert_eq!("push".parse::<IS>(), Err("Unknown instruction: push".to_string()));
+    }
+
+    #[test]
+    fn mov_immediates_into_memory_take_the_width_of_the_qualifier_or_label() {
+        let ptr = |width: DataWidth, displacement: i32| Operand::Memory(MemOp::Ptr(width, "value".to_string(), displacement));
+        let data = [("value", Data::Dword(0xFFFF_FFFF)), ("next", Data::Byte(0xEE))];
+        let mut storing = cpu(&data, vec![
+            Instruction::new(IS::Mov, vec![ptr(DataWidth::Byte, 0), Operand::Immediate(Data::Byte(0x11))]),
+            Instruction::new(IS::Mov, vec![ptr(DataWidth::Word, 1), Operand::Immediate(Data::Byte(0x22))]),
+        ]);
+        storing.run().unwrap();
+        // The word store zero-extends the byte into its two bytes and leaves the last one alone
+        assert_eq!(storing.read_label("value").unwrap(), Data::Dword(0xFF00_2211));
+
+        let mut storing = cpu(&data, vec![
+            Instruction::new(IS::Mov, vec![ptr(DataWidth::Dword, 0), Operand::Immediate(Data::Word(0x1234))]),
+        ]);
+        storing.run().unwrap();
+        assert_eq!(storing.read_label("value").unwrap(), Data::Dword(0x1234));
+
+        // Without a qualifier the label's declared width is written, a small immediate still fills it
+        let mut storing = cpu(&data, vec![Instruction::new(IS::Mov, vec![mem("value"), Operand::Immediate(Data::Byte(5))])]);
+        storing.run().unwrap();
+        assert_eq!(storing.read_label("value").unwrap(), Data::Dword(5));
+        assert_eq!(storing.read_label("next").unwrap(), Data::Byte(0xEE));
+
+        // 0x1FFFF doesn't fit a word and isn't truncated to one
+        let mut overflowing = cpu(&data, vec![
+            Instruction::new(IS::Mov, vec![ptr(DataWidth::Word, 0), Operand::Immediate(Data::Dword(0x1_FFFF))]),
+        ]);
+        let fault = overflowing.run().unwrap_err();
+        assert!(matches!(fault.error(), CpuError::ImmediateOverflow(0x1_FFFF, DataWidth::Word)), "{:?}", fault.error());
+        assert_eq!(overflowing.read_label("value").unwrap(), Data::Dword(0xFFFF_FFFF));
+    }
+
+    #[test]
+    fn alu_not_complements_at_each_width() {
+        for (width, value, complement) in [
+            (DataWidth::Byte, 0x0F, 0xF0),
+            (DataWidth::Word, 0x00FF, 0xFF00),
+            (DataWidth::Dword, 0x0000_FFFF, 0xFFFF_0000),
+        ] {
+            let mut alu = ALU::new();
+            alu.set_mode(ALUMode::Not);
+            // Bits above the width are ignored
+            alu.operand_fetch_single(value | !width.mask());
+            let (result, flags) = alu.execute(width);
+            assert_eq!(result, complement, "{:?}", width);
+            assert_eq!(alu.trace.unwrap().operands, (value, 0));
+            assert!(flags.sign);
+            assert_eq!(self::alu(ALUMode::Not, complement, 0, width).0, value);
+        }
+    }
+
+    #[test]
+    fn not_twice_restores_registers_and_memory() {
+        let not = |operand: Operand| Instruction::new(IS::Not, vec![operand]);
+        let mut inverting = cpu(&[("value", Data::Byte(0x5A))], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(0x1234)]),
+            not(reg(Register::AX)),
+            Instruction::new(IS::Mov, vec![reg(Register::BX), reg(Register::AX)]),
+            not(reg(Register::AX)),
+            not(mem("value")),
+            Instruction::new(IS::Mov, vec![reg(Register::CX), mem("value")]),
+            not(mem("value")),
+        ]);
+        for index in [0, 1, 2, 3, 7, 8] {
+            inverting.flags[index].set_value(1);
+        }
+        inverting.run().unwrap();
+        assert_eq!(inverting.register(Register::BX), 0xEDCB);
+        assert_eq!(inverting.register(Register::AX), 0x1234);
+        assert_eq!(inverting.register(Register::CX), 0xA5);
+        assert_eq!(inverting.read_label("value").unwrap(), Data::Byte(0x5A));
+        // NOT leaves every flag untouched
+        assert_eq!(inverting.flags_word(), STATUS_FLAGS);
+    }
+
+    #[test]
+    fn code_labels_must_name_an_instruction_of_the_program() {
+        let code = vec![Instruction::new(IS::Mov, vec![reg(Register::AX), word(1)])];
+        let built = CpuBuilder::new(HashMap::new(), code.clone()).code_label("end", 1).build();
+        assert!(matches!(built, Err(BuildError::CodeLabelOutOfRange(name, 1)) if name == "end"));
+        let built = CpuBuilder::new(HashMap::new(), code).code_label("start", 0).build().unwrap();
+        assert_eq!(built.code_label("start"), Some(0));
+        assert_eq!(built.code_label("end"), None);
+    }
+
+    #[test]
+    fn observed_events_carry_the_instruction_and_step_that_caused_them() {
+        let writes = std::rc::Rc::new(RefCell::new(Vec::new()));
+        /// The ip, instruction and step number of each memory write
+        type Writes = Vec<(usize, String, u64, Event)>;
+        struct Recorder(std::rc::Rc<RefCell<Writes>>);
+        impl Observer for Recorder {
+            fn event(&mut self, context: &ExecContext, event: &Event) {
+                if event.kind() == EventKind::MemoryWrite {
+                    self.0.borrow_mut().push((context.ip, context.instruction.to_string(), context.step_number, event.clone()));
+                }
+            }
+        }
+        let data = HashMap::from([("result".to_string(), Data::Word(0))]);
+        let mut observed = CpuBuilder::new(data, vec![
+            Instruction::new(IS::Mov, vec![reg(Register::CX), word(0)]),
+            // loop:
+            Instruction::new(IS::Add, vec![reg(Register::CX), word(1)]),
+            Instruction::new(IS::Mov, vec![mem("result"), reg(Register::CX)]),
+            Instruction::new(IS::Cmp, vec![reg(Register::CX), word(2)]),
+            Instruction::new(IS::Jnz, vec![word(1)]),
+            // memset(result, 0xAA, 2), writing from within the syscall handler
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(syscall_abi::SYS_MEMSET as u16)]),
+            Instruction::new(IS::Mov, vec![reg(Register::BX), word(0xAA)]),
+            Instruction::new(IS::Mov, vec![reg(Register::CX), Operand::LabelOffset("result".to_string())]),
+            Instruction::new(IS::Mov, vec![reg(Register::DX), word(2)]),
+            Instruction::new(IS::Syscall, vec![]),
+        ]).verbosity(Verbosity::Quiet).observer(Recorder(writes.clone())).build().unwrap();
+        observed.run().unwrap();
+        let mov = "mov [result], cx".to_string();
+        assert_eq!(*writes.borrow(), [
+            (2, mov.clone(), 3, Event::MemoryWrite(0, vec![1, 0])),
+            (2, mov, 7, Event::MemoryWrite(0, vec![2, 0])),
+            (9, "syscall".to_string(), 14, Event::MemoryWrite(0, vec![0xAA, 0xAA])),
+        ]);
+    }
+
+    #[test]
+    fn format_value_renders_boundary_values_in_each_width_and_base() {
+        let cases = [
+            (0x00, DataWidth::Byte, ["0x00", "0", "0", "0b00000000"]),
+            (0x7F, DataWidth::Byte, ["0x7F", "127", "127", "0b01111111"]),
+            (0x80, DataWidth::Byte, ["0x80", "128", "-128", "0b10000000"]),
+            (0xFF, DataWidth::Byte, ["0xFF", "255", "-1", "0b11111111"]),
+            (0x7FFF, DataWidth::Word, ["0x7FFF", "32767", "32767", "0b0111111111111111"]),
+            (0x8000, DataWidth::Word, ["0x8000", "32768", "-32768", "0b1000000000000000"]),
+            (0xFFFF, DataWidth::Word, ["0xFFFF", "65535", "-1", "0b1111111111111111"]),
+            // Bits above the width are dropped
+            (0x1_0001, DataWidth::Word, ["0x0001", "1", "1", "0b0000000000000001"]),
+            (0x0000_FFFF, DataWidth::Dword, ["0x0000FFFF", "65535", "65535", "0b00000000000000001111111111111111"]),
+            (0x8000_0000, DataWidth::Dword, ["0x80000000", "2147483648", "-2147483648", "0b10000000000000000000000000000000"]),
+            (0xFFFF_FFFF, DataWidth::Dword, ["0xFFFFFFFF", "4294967295", "-1", "0b11111111111111111111111111111111"]),
+        ];
+        for (value, width, expected) in cases {
+            let rendered = [Base::Hex, Base::Unsigned, Base::Signed, Base::Binary].map(|base| format_value(value, width, base));
+            assert_eq!(rendered, expected, "{:#X} at {:?}", value, width);
+        }
+
+        let mut registers = cpu(&[], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(0xFFFF)]),
+            Instruction::new(IS::Mov, vec![reg(Register::EAX), Operand::Immediate(Data::Dword(0x0000_FFFF))]),
+        ]);
+        registers.run().unwrap();
+        let shown = registers.format_registers(&RegisterFormat { bases: vec![Base::Signed, Base::Hex] });
+        let lines: Vec<&str> = shown.lines().collect();
+        assert_eq!(lines[0], "AX  = -1  0xFFFF");
+        assert_eq!(lines[4], "EAX = 65535  0x0000FFFF");
+        assert_eq!(registers.format_registers(&RegisterFormat::default()).lines().nth(1), Some("BX  = 0x0000"));
+    }
+
+    #[test]
+    fn snapshot_diff_lists_changed_registers_flags_and_coalesced_memory_ranges() {
+        let mut diffed = cpu(&[("a", Data::Word(0x1234)), ("b", Data::Word(0)), ("c", Data::Dword(0))], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(5)]),
+            Instruction::new(IS::Mov, vec![mem("b"), word(0x0505)]),
+            Instruction::new(IS::Mov, vec![reg(Register::EBX), Operand::Immediate(Data::Dword(0x0101))]),
+            Instruction::new(IS::Mov, vec![mem("c"), reg(Register::EBX)]),
+            // Only the low byte of a changes
+            Instruction::new(IS::Mov, vec![mem("a"), word(0x1299)]),
+            Instruction::new(IS::Cmp, vec![reg(Register::AX), word(5)]),
+        ]);
+        let before = diffed.snapshot();
+        assert!(before.diff(&diffed.snapshot()).is_empty());
+        assert_eq!(before.diff(&before).to_string(), "No changes");
+
+        diffed.run().unwrap();
+        let after = diffed.snapshot();
+        let diff = before.diff(&after);
+        assert_eq!(diff, SnapshotDiff {
+            registers: vec![(Register::AX, 0, 5), (Register::EBX, 0, 0x0101)],
+            // PF and ZF, by index in the flags array
+            flags: vec![(0, true), (2, true)],
+            // b and the low word of c are adjacent, the high byte of a is unchanged
+            memory: vec![0..1, 2..6],
+        });
+        assert_eq!(diff.to_string(), [
+            "AX: 0x0000 -> 0x0005",
+            "EBX: 0x00000000 -> 0x00000101",
+            "PF: 0 -> 1",
+            "ZF: 0 -> 1",
+            "Memory 0x0000..0x0001: 1 bytes",
+            "Memory 0x0002..0x0006: 4 bytes",
+        ].join("\n"));
+        assert!(after.diff(&diffed.snapshot()).is_empty());
+    }
+
+    #[test]
+    fn memory_report_breaks_down_each_symbol_and_comes_with_the_over_capacity_error() {
+        let data = |labels: &[(&str, Data)]| labels.iter().map(|(label, data)| (label.to_string(), data.clone())).collect();
+        let laid_out = CpuBuilder::new(data(&[
+            ("flag", Data::Byte(1)),
+            ("count", Data::Word(2)),
+            ("total", Data::Dword(3)),
+            ("result", Data::Dword(4)),
+        ]), vec![]).data_base(16).stack(8).build().unwrap();
+        let report = laid_out.memory_report();
+        assert_eq!(report, MemoryReport {
+            capacity: 1024,
+            reserved: 16,
+            symbols: vec![("result".to_string(), 4), ("total".to_string(), 4), ("count".to_string(), 2), ("flag".to_string(), 1)],
+            environment: 0,
+            stack: 8,
+        });
+        assert_eq!(report.used(), 35);
+        assert_eq!(report.to_string(), [
+            "Label        Bytes",
+            "result           4",
+            "total            4",
+            "count            2",
+            "flag             1",
+            "Reserved        16",
+            "Stack            8",
+            "Used 35 of 1024 bytes, 989 free",
+        ].join("\n"));
+
+        let over = CpuBuilder::new(data(&[("big", Data::Dword(0)), ("small", Data::Word(0))]), vec![]).data_base(1020).build();
+        let Err(BuildError::OverCapacity(report)) = over else {
+            panic!("expected an over-capacity error, got {:?}", over.map(|_| ()));
+        };
+        assert_eq!(report.symbols, [("big".to_string(), 4), ("small".to_string(), 2)]);
+        assert_eq!((report.reserved, report.used(), report.capacity), (1020, 1026, 1024));
+        assert!(BuildError::OverCapacity(report).to_string().ends_with("Used 1026 of 1024 bytes, over capacity by 2"));
+    }
+
+    #[test]
+    fn execution_begins_at_an_entry_point_placed_after_a_subroutine() {
+        let program = |entry| {
+            let data = HashMap::from([("result".to_string(), Data::Word(0))]);
+            CpuBuilder::new(data, vec![
+                // double: AX += AX
+                Instruction::new(IS::Add, vec![reg(Register::AX), reg(Register::AX)]),
+                Instruction::new(IS::Ret, vec![]),
+                // _start:
+                Instruction::new(IS::Mov, vec![reg(Register::AX), word(21)]),
+                Instruction::new(IS::Call, vec![word(0)]),
+                Instruction::new(IS::Mov, vec![mem("result"), reg(Register::AX)]),
+            ]).verbosity(Verbosity::Quiet).entry(entry).build().unwrap()
+        };
+        let mut started = program(2);
+        assert_eq!(started.ip(), 2);
+        assert_eq!(started.step().unwrap(), StopReason::Stepped);
+        assert_eq!((started.ip(), started.register(Register::AX)), (3, 21));
+        assert_eq!(started.run().unwrap().exit_code, 0);
+        assert_eq!(started.read_label("result").unwrap(), Data::Word(42));
+        // The subroutine is reached through the call, so none of the program is unreachable from the entry
+        assert!(!started.validate().iter().any(|warning| matches!(warning, Warning::UnreachableCode(_))));
+
+        // Starting at index 0 runs straight into the subroutine's RET
+        let fault = program(0).run().unwrap_err();
+        assert!(matches!(fault.error(), CpuError::ReturnWithoutCall), "{:?}", fault.error());
+        assert!(program(0).validate().contains(&Warning::UnreachableCode(2)));
+    }
+
+    #[test]
+    fn jumps_and_returns_outside_the_code_section_fault_with_the_instruction_that_went_there() {
+        let mut jumping = cpu(&[], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(1)]),
+            Instruction::new(IS::Jmp, vec![word(9999)]),
+        ]);
+        let fault = jumping.run().unwrap_err();
+        assert!(matches!(fault.error(), CpuError::InvalidInstructionPointer(9999, Some(1))), "{:?}", fault.error());
+        assert_eq!((fault.context().ip(), fault.context().instruction()), (1, "jmp 9999"));
+        assert_eq!(jumping.ip(), 9999);
+
+        // The callee replaces its return address, at the top of the stack, with garbage
+        let returning = |stack| {
+            let data = HashMap::from([("anchor".to_string(), Data::Word(0))]);
+            CpuBuilder::new(data, vec![
+                Instruction::new(IS::Call, vec![word(2)]),
+                Instruction::new(IS::Jmp, vec![word(4)]),
+                Instruction::new(IS::Mov, vec![Operand::Memory(MemOp::Ptr(DataWidth::Word, "anchor".to_string(), 16)), word(0xBEEF)]),
+                Instruction::new(IS::Ret, vec![]),
+            ]).verbosity(Verbosity::Quiet).stack(stack).build().unwrap()
+        };
+        let fault = returning(16).run().unwrap_err();
+        assert!(matches!(fault.error(), CpuError::InvalidInstructionPointer(0xBEEF, Some(3))), "{:?}", fault.error());
+        assert_eq!((fault.context().ip(), fault.context().instruction()), (3, "ret"));
+
+        // Without a stack return addresses only live in the CPU, a RET can only pop one of them or none
+        let mut unbalanced = cpu(&[], vec![Instruction::new(IS::Ret, vec![])]);
+        let fault = unbalanced.run().unwrap_err();
+        assert!(matches!(fault.error(), CpuError::ReturnWithoutCall), "{:?}", fault.error());
+        assert_eq!(fault.context().ip(), 0);
+    }
+
+    #[test]
+    fn alu_trace_shows_a_word_add_carrying_out_and_a_sub_borrowing() {
+        let events = std::rc::Rc::new(RefCell::new(Vec::new()));
+        struct Recorder(std::rc::Rc<RefCell<Vec<(usize, AluTrace)>>>);
+        impl Observer for Recorder {
+            fn event(&mut self, context: &ExecContext, event: &Event) {
+                if let Event::AluOperation(trace) = event {
+                    self.0.borrow_mut().push((context.ip, *trace));
+                }
+            }
+        }
+        let mut traced = CpuBuilder::new(HashMap::new(), vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(0xFFFF)]),
+            Instruction::new(IS::Add, vec![reg(Register::AX), word(1)]),
+            Instruction::new(IS::Mov, vec![reg(Register::BX), word(3)]),
+            Instruction::new(IS::Sub, vec![reg(Register::BX), word(5)]),
+        ]).verbosity(Verbosity::Quiet).observer(Recorder(events.clone())).build().unwrap();
+        assert!(traced.alu_trace().is_none());
+        traced.run().unwrap();
+
+        let flags = |carry, zero, sign, parity, aux| FlagsDelta { carry, overflow: false, zero, sign, parity, aux };
+        let add = AluTrace { mode: ALUMode::Add, width: DataWidth::Word, operands: (0xFFFF, 1), result: 0, flags: flags(true, true, false, true, true) };
+        let sub = AluTrace { mode: ALUMode::Sub, width: DataWidth::Word, operands: (3, 5), result: 0xFFFE, flags: flags(true, false, true, false, true) };
+        assert_eq!(*events.borrow(), [(1, add), (3, sub)]);
+        assert_eq!(traced.alu_trace(), Some(&sub));
+        assert_eq!(add.to_string(), [
+            "Add (Word)",
+            "    0b1111111111111111",
+            "  + 0b0000000000000001",
+            "  = 0b0000000000000000",
+            "CF=1 OF=0 ZF=1 SF=0 PF=1 AF=1",
+        ].join("\n"));
+        assert_eq!(sub.to_string().lines().last(), Some("CF=1 OF=0 ZF=0 SF=1 PF=0 AF=1"));
+    }
+
+    #[test]
+    fn pipeline_view_reports_the_fetch_decode_and_execute_stages_in_order() {
+        let events = std::rc::Rc::new(RefCell::new(Vec::new()));
+        struct Recorder(std::rc::Rc<RefCell<Vec<(usize, Event)>>>);
+        impl Observer for Recorder {
+            fn event(&mut self, context: &ExecContext, event: &Event) {
+                if matches!(event.kind(), EventKind::Fetched | EventKind::Decoded | EventKind::Executed) {
+                    self.0.borrow_mut().push((context.ip, event.clone()));
+                }
+            }
+        }
+        let program = |pipeline_view| {
+            let data = HashMap::from([("result".to_string(), Data::Word(0))]);
+            CpuBuilder::new(data, vec![
+                Instruction::new(IS::Mov, vec![reg(Register::AX), word(5)]),
+                Instruction::new(IS::Mov, vec![reg(Register::CX), word(7)]),
+                Instruction::new(IS::Add, vec![reg(Register::AX), reg(Register::CX)]),
+                Instruction::new(IS::Mov, vec![mem("result"), reg(Register::AX)]),
+                Instruction::new(IS::Sub, vec![mem("result"), word(2)]),
+            ]).verbosity(Verbosity::Quiet).observer(Recorder(events.clone())).pipeline_view(pipeline_view).build().unwrap()
+        };
+        program(true).run().unwrap();
+        let stages = |operands: &[(&str, u32)]| operands.iter().map(|(operand, value)| (operand.to_string(), *value)).collect();
+        let events = events.take();
+        assert_eq!(events.len(), 15);
+        assert_eq!(events[6..], [
+            (2, Event::Fetched("add ax, cx".to_string())),
+            (2, Event::Decoded(stages(&[("ax", 5), ("cx", 7)]))),
+            (2, Event::Executed(stages(&[("ax", 12)]))),
+            (3, Event::Fetched("mov [result], ax".to_string())),
+            (3, Event::Decoded(stages(&[("ax", 12)]))),
+            (3, Event::Executed(stages(&[("[result]", 12)]))),
+            (4, Event::Fetched("sub [result], 2".to_string())),
+            (4, Event::Decoded(stages(&[("[result]", 12), ("2", 2)]))),
+            (4, Event::Executed(stages(&[("[result]", 10)]))),
+        ]);
+
+        let output = SharedOutput::default();
+        let data = HashMap::from([("result".to_string(), Data::Word(12))]);
+        CpuBuilder::new(data, vec![Instruction::new(IS::Sub, vec![mem("result"), word(2)])])
+            .verbosity(Verbosity::Quiet)
+            .observer(JsonLinesObserver::new(output.clone()).only(&[EventKind::Decoded]))
+            .pipeline_view(true)
+            .build().unwrap().run().unwrap();
+        let text = String::from_utf8(output.0.borrow().clone()).unwrap();
+        let decoded = parse_json_object(text.trim_end()).unwrap();
+        assert_eq!((decoded["operands"].as_str(), decoded["values"].as_str()), ("[\"[result]\",\"2\"]", "[12,2]"));
+
+        // Stage events are only reported in pipeline view
+        let unobserved = std::rc::Rc::new(RefCell::new(Vec::new()));
+        let data = HashMap::new();
+        CpuBuilder::new(data, vec![Instruction::new(IS::Mov, vec![reg(Register::AX), word(5)])])
+            .verbosity(Verbosity::Quiet)
+            .observer(Recorder(unobserved.clone()))
+            .build().unwrap().run().unwrap();
+        assert!(unobserved.borrow().is_empty());
+    }
+
+    #[test]
+    fn simple_semantics_divide_without_faulting_where_strict_semantics_fault() {
+        let dword = |value| Operand::Immediate(Data::Dword(value));
+        // Each case sets up the dividend and the divisor, divides, then checks the quotient and remainder registers
+        let cases = [
+            // DX:AX = 0x0001_0005 divided by zero
+            (vec![(Register::AX, word(5)), (Register::DX, word(1)), (Register::CX, word(0))], reg(Register::CX), [(Register::AX, 0xFFFF), (Register::DX, 5)]),
+            // DX:AX = 0x0002_0003 divided by 2 is 0x1_0001, too wide for AX
+            (vec![(Register::AX, word(3)), (Register::DX, word(2)), (Register::CX, word(2))], reg(Register::CX), [(Register::AX, 1), (Register::DX, 1)]),
+            // AX = 0x0123 divided by a zero byte leaves all ones in AL and the low byte of the dividend in AH
+            (vec![(Register::AX, word(0x0123))], mem("zero"), [(Register::AX, 0x23FF), (Register::DX, 0)]),
+            // EDX:EAX = 2^32 divided by zero
+            (vec![(Register::EAX, dword(0)), (Register::EDX, dword(1)), (Register::ECX, dword(0))], reg(Register::ECX), [(Register::EAX, 0xFFFF_FFFF), (Register::EDX, 0)]),
+        ];
+        for (setup, divisor, expected) in cases {
+            let mut code: Vec<Instruction> = setup.into_iter().map(|(register, value)| Instruction::new(IS::Mov, vec![reg(register), value])).collect();
+            code.push(Instruction::new(IS::Div, vec![divisor]));
+            let build = |semantics| {
+                let data = HashMap::from([("zero".to_string(), Data::Byte(0))]);
+                CpuBuilder::new(data, code.clone()).verbosity(Verbosity::Quiet).semantics(semantics).build().unwrap()
+            };
+            let fault = build(Semantics::Strict).run().unwrap_err();
+            assert!(matches!(fault.error(), CpuError::DivideError), "{:?}", fault.error());
+
+            let mut simple = build(Semantics::Simple);
+            simple.run().unwrap();
+            for (register, value) in expected {
+                assert_eq!(simple.register(register.clone()), value, "{:?} after {}", register, code.last().unwrap());
+            }
+        }
+
+        // AAM divides AL by its base the same way
+        let code = vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(0x3F)]),
+            Instruction::new(IS::Aam, vec![Operand::Immediate(Data::Byte(0))]),
+        ];
+        let build = |semantics| CpuBuilder::new(HashMap::new(), code.clone()).verbosity(Verbosity::Quiet).semantics(semantics).build().unwrap();
+        assert!(matches!(build(Semantics::Strict).run().unwrap_err().error(), CpuError::DivideError));
+        let mut simple = build(Semantics::Simple);
+        simple.run().unwrap();
+        assert_eq!(simple.register(Register::AX), 0xFF3F);
+    }
+
+    #[test]
+    fn a_data_section_fits_ram_to_the_byte_or_fails_to_build_with_the_layout() {
+        // Reserving all but six bytes below the data base leaves a tiny RAM for the data section
+        let build = |data_base| {
+            let data = HashMap::from([("low".to_string(), Data::Word(0x1234)), ("high".to_string(), Data::Dword(0xDEAD_BEEF))]);
+            CpuBuilder::new(data, vec![]).verbosity(Verbosity::Quiet).data_base(data_base).build()
+        };
+        let fitting = build(1018).unwrap();
+        assert_eq!(fitting.memory_report().used(), 1024);
+        assert_eq!(fitting.read_label("high").unwrap(), Data::Dword(0xDEAD_BEEF));
+        assert_eq!(fitting.read_label("low").unwrap(), Data::Word(0x1234));
+
+        let Err(BuildError::OverCapacity(report)) = build(1019) else {
+            panic!("a data section one byte too large was built");
+        };
+        assert_eq!((report.used(), report.capacity, report.reserved), (1025, 1024, 1019));
+        assert_eq!(report.symbols, [("high".to_string(), 4), ("low".to_string(), 2)]);
+        let error = BuildError::OverCapacity(report).to_string();
+        assert!(error.starts_with("Not enough capacity in data bus!\n"), "{}", error);
+        assert!(error.ends_with("Used 1025 of 1024 bytes, over capacity by 1"), "{}", error);
+    }
+
+    #[test]
+    fn folded_stacks_attribute_instructions_to_their_call_chain() {
+        let code = vec![
+            // outer: calls inner twice
+            Instruction::new(IS::Call, vec![word(3)]),
+            Instruction::new(IS::Call, vec![word(3)]),
+            Instruction::new(IS::Ret, vec![]),
+            // inner, without a code label
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(1)]),
+            Instruction::new(IS::Ret, vec![]),
+            // _start: calls outer twice
+            Instruction::new(IS::Mov, vec![reg(Register::CX), word(2)]),
+            Instruction::new(IS::Call, vec![word(0)]),
+            Instruction::new(IS::Sub, vec![reg(Register::CX), word(1)]),
+            Instruction::new(IS::Jnz, vec![word(6)]),
+        ];
+        let build = || CpuBuilder::new(HashMap::new(), code.clone())
+            .verbosity(Verbosity::Quiet)
+            .entry(5)
+            .code_label("_start", 5)
+            .code_label("outer", 0)
+            .build()
+            .unwrap();
+        let mut profiled = build();
+        assert_eq!(profiled.export_folded_stacks(), "");
+        profiled.enable_profiling();
+        assert_eq!(profiled.run().unwrap().instructions, 21);
+        assert_eq!(profiled.export_folded_stacks(), "_start 7\n_start;outer 6\n_start;outer;sub_3 8\n");
+
+        // Without a code label on the entry point the outermost frame is main
+        let mut unlabelled = CpuBuilder::new(HashMap::new(), code).verbosity(Verbosity::Quiet).entry(5).build().unwrap();
+        unlabelled.enable_profiling();
+        unlabelled.run().unwrap();
+        assert_eq!(unlabelled.export_folded_stacks(), "main 7\nmain;sub_0 6\nmain;sub_0;sub_3 8\n");
+    }
+
+    #[test]
+    fn malformed_instructions_fail_to_decode_instead_of_panicking() {
+        let add = Instruction::new(IS::Add, vec![reg(Register::AX)]);
+        assert!(matches!(add.dest(), Ok(Operand::Register(Register::AX))));
+        assert!(matches!(add.src(), Err(CpuError::InvalidOperands(IS::Add, _))));
+        assert!(matches!(Instruction::new(IS::Not, vec![]).only(), Err(CpuError::InvalidOperands(IS::Not, _))));
+        assert!(matches!(Instruction::new(IS::Not, vec![reg(Register::AX), reg(Register::BX)]).only(), Err(CpuError::InvalidOperands(IS::Not, _))));
+
+        // Claiming more operands than it has gets a malformed instruction past the operand check, for every
+        // supported instruction taking operands, with none of them and with all but its last one
+        let mut checked = 0;
+        for opcode in IS::all().iter().filter(|opcode| opcode.info().supported) {
+            let info = opcode.info();
+            let claimed = *info.operand_count.end();
+            for present in [0, claimed.saturating_sub(1)] {
+                if claimed == 0 || present >= *info.operand_count.start() {
+                    continue;
+                }
+                let operands: Vec<Operand> = info.operand_kinds.iter().take(present as usize).map(|kinds| match kinds[0] {
+                    OperandKind::Register => reg(Register::AX),
+                    OperandKind::Memory => mem("value"),
+                    OperandKind::Immediate => word(1),
+                    OperandKind::Relative => Operand::JmpRel(0),
+                }).collect();
+                let malformed = Instruction { opcode: opcode.clone(), operands, operand_count: claimed };
+                let mut decoding = cpu(&[("value", Data::Word(0))], vec![malformed.clone()]);
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| decoding.run()));
+                match result {
+                    Ok(Err(fault)) => assert!(matches!(fault.error(), CpuError::InvalidOperands(failed, _) if failed == opcode), "{:?}: {:?}", malformed, fault.error()),
+                    Ok(Ok(_)) => panic!("{:?} ran", malformed),
+                    Err(_) => panic!("{:?} panicked", malformed),
+                }
+                checked += 1;
+            }
+        }
+        assert!(checked >= IS::all().len() / 2, "only {} malformed instructions checked", checked);
+    }
+
+    #[test]
+    fn a_guest_scans_its_environment_for_a_key_and_branches_on_the_value() {
+        let byte_at = |displacement| Operand::Memory(MemOp::Indirect(DataWidth::Byte, Register::CX, displacement));
+        let byte = |value: u8| Operand::Immediate(Data::Byte(value));
+        let jump = |opcode, target| Instruction::new(opcode, vec![word(target)]);
+        let mut code = vec![
+            Instruction::new(IS::Mov, vec![reg(syscall_abi::NUMBER), word(syscall_abi::SYS_ENVIRONMENT as u16)]),
+            Instruction::new(IS::Syscall, vec![]),
+            // CX walks the block up to its end in DX
+            Instruction::new(IS::Add, vec![reg(Register::DX), reg(Register::CX)]),
+            // 3, each variable: not found once the end of the block is reached
+            Instruction::new(IS::Cmp, vec![reg(Register::CX), reg(Register::DX)]),
+            jump(IS::Jz, 28),
+        ];
+        // 5, skip the variable unless it starts with `MODE=`
+        for (displacement, character) in "MODE=".bytes().enumerate() {
+            code.push(Instruction::new(IS::Cmp, vec![byte_at(displacement as i32), byte(character)]));
+            code.push(jump(IS::Jnz, 18));
+        }
+        code.extend([
+            // 15, the value is fast if it starts with f
+            Instruction::new(IS::Cmp, vec![byte_at(5), byte(b'f')]),
+            jump(IS::Jz, 24),
+            jump(IS::Jmp, 26),
+            // 18, skip past the NUL ending the variable
+            Instruction::new(IS::Cmp, vec![byte_at(0), byte(0)]),
+            jump(IS::Jz, 22),
+            Instruction::new(IS::Add, vec![reg(Register::CX), word(1)]),
+            jump(IS::Jmp, 18),
+            Instruction::new(IS::Add, vec![reg(Register::CX), word(1)]),
+            jump(IS::Jmp, 3),
+            // 24, exit 1 when fast, 2 for any other mode and 3 without one
+            Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG1), word(1)]),
+            jump(IS::Jmp, 29),
+            Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG1), word(2)]),
+            jump(IS::Jmp, 29),
+            Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG1), word(3)]),
+            Instruction::new(IS::Mov, vec![reg(syscall_abi::NUMBER), word(syscall_abi::SYS_EXIT as u16)]),
+            Instruction::new(IS::Syscall, vec![]),
+        ]);
+        let run = |variables: &[(&str, &str)]| {
+            let data = HashMap::from([("padding".to_string(), Data::Word(0))]);
+            let mut guest = CpuBuilder::new(data, code.clone()).verbosity(Verbosity::Quiet).env(variables).build().unwrap();
+            guest.run().unwrap().exit_code
+        };
+        // MODE follows another variable, which is skipped
+        assert_eq!(run(&[("N", "100"), ("MODE", "fast")]), 1);
+        assert_eq!(run(&[("N", "100"), ("MODE", "safe")]), 2);
+        assert_eq!(run(&[("MODEL", "fast"), ("N", "100")]), 3);
+        assert_eq!(run(&[]), 3);
+    }
+
+    #[test]
+    fn guest_routines_are_called_like_functions_keeping_their_global_state() {
+        let program = |stack: u16| CpuBuilder::new(HashMap::from([("calls".to_string(), Data::Word(0)), ("total".to_string(), Data::Word(0))]), vec![
+            Instruction::new(IS::Mov, vec![reg(syscall_abi::NUMBER), word(syscall_abi::SYS_EXIT as u16)]),
+            Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG1), word(7)]),
+            Instruction::new(IS::Syscall, vec![]),
+            // add: AX += BX, counting the calls and keeping a running total
+            Instruction::new(IS::Add, vec![reg(Register::AX), reg(Register::BX)]),
+            Instruction::new(IS::Add, vec![mem("calls"), word(1)]),
+            Instruction::new(IS::Add, vec![mem("total"), reg(Register::AX)]),
+            Instruction::new(IS::Ret, vec![]),
+        ])
+            .verbosity(Verbosity::Quiet)
+            .stack(stack)
+            .stack_canaries(stack > 0)
+            .code_label("main", 0)
+            .code_label("add", 3)
+            .build()
+            .unwrap();
+
+        // With the return addresses kept by the CPU, and on a stack in memory with canaries
+        for stack in [0, 16] {
+            let mut adding = program(stack);
+            for (left, right, calls, total) in [(2, 3, 1, 5), (40, 2, 2, 47), (0x1_0005, 1, 3, 53)] {
+                let returned = adding.call_guest("add", &[Arg::Reg(Register::AX, left), Arg::Reg(Register::BX, right)]).unwrap();
+                // The arguments are truncated to their register
+                assert_eq!(returned.register(Register::AX), (left + right) & 0xFFFF);
+                assert_eq!(returned.instructions, 4);
+                assert_eq!(adding.read_label("calls").unwrap(), Data::Word(calls));
+                assert_eq!(adding.read_label("total").unwrap(), Data::Word(total));
+                assert_eq!(adding.ip(), 0);
+            }
+            assert_eq!(adding.run().unwrap().exit_code, 7);
+        }
+
+        // A routine that exits instead of returning leaves the program as it was
+        let mut exiting = program(0);
+        let fault = exiting.call_guest("main", &[]).unwrap_err();
+        assert!(matches!(fault.error(), CpuError::GuestCallEnded(StopReason::Exited(7))), "{:?}", fault.error());
+        assert_eq!((exiting.ip(), exiting.finished()), (0, None));
+        assert_eq!(exiting.run().unwrap().exit_code, 7);
+        let fault = exiting.call_guest("ad", &[]).unwrap_err();
+        assert!(matches!(fault.error(), CpuError::UndeclaredLabel(label, Some(suggestion)) if label == "ad" && suggestion == "add"));
+    }
+
+    #[test]
+    fn machine_state_hashes_memory_alone_and_round_trips_through_text() {
+        let mut running = cpu(&[("value", Data::Word(1))], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), word(5)]),
+            Instruction::new(IS::Add, vec![reg(Register::EBX), Operand::Immediate(Data::Dword(0x1234_5678))]),
+            Instruction::new(IS::Mov, vec![mem("value"), reg(Register::AX)]),
+        ]);
+        let initial = running.machine_state();
+        assert_eq!(initial.memory_hash, memory_hash(&running.memory_unit.data_bus.data));
+        // Registers, flags and IP change, memory doesn't
+        running.step().unwrap();
+        running.step().unwrap();
+        let registers = running.machine_state();
+        assert_ne!(registers, initial);
+        assert_eq!((registers.ip, registers.registers[&Register::EBX]), (2, 0x1234_5678));
+        assert_eq!(registers.memory_hash, initial.memory_hash);
+        running.step().unwrap();
+        let written = running.machine_state();
+        assert_ne!(written.memory_hash, initial.memory_hash);
+        // The digest depends on the contents alone, writing the old value back restores it
+        running.write_label("value", Data::Word(1)).unwrap();
+        assert_eq!(running.machine_state().memory_hash, initial.memory_hash);
+        assert_eq!(memory_hash(&[]), 0xCBF2_9CE4_8422_2325);
+
+        for state in [initial, registers, written] {
+            let text = state.to_string();
+            assert_eq!(text.parse::<MachineState>(), Ok(state), "{}", text);
+        }
+        assert!("registers: AX=0x0000\nflags: 0x0000\nsp: 0 bp: 0\nmemory_hash: 0x0".parse::<MachineState>().is_err());
+        assert!("registers: SI=0x0000\nflags: 0x0000\nsp: 0 bp: 0 ip: 0\nmemory_hash: 0x0".parse::<MachineState>().is_err());
+    }
+
+    /// Counts to 20 in CX and in `count`, then aborts with code 3 at instruction 7
+    fn aborting() -> CPU {
+        cpu(&[("count", Data::Word(0))], vec![
+            Instruction::new(IS::Mov, vec![reg(Register::CX), word(0)]),
+            Instruction::new(IS::Add, vec![reg(Register::CX), word(1)]),
+            Instruction::new(IS::Add, vec![mem("count"), word(1)]),
+            Instruction::new(IS::Cmp, vec![reg(Register::CX), word(20)]),
+            Instruction::new(IS::Jnz, vec![word(1)]),
+            Instruction::new(IS::Mov, vec![reg(syscall_abi::NUMBER), word(syscall_abi::SYS_ABORT as u16)]),
+            Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG1), word(3)]),
+            Instruction::new(IS::Syscall, vec![]),
+        ])
+    }
+
+    #[test]
+    fn a_core_dump_round_trips_through_its_text_and_describes_the_fault() {
+        let mut faulting = aborting();
+        let fault = faulting.run().unwrap_err();
+        assert!(matches!(fault.error(), CpuError::Aborted(3)), "{:?}", fault.error());
+        let core = faulting.core_dump(&fault);
+        assert_eq!((core.error.as_str(), core.ip), ("Aborted(3)", 7));
+        assert_eq!(core.state, faulting.machine_state());
+        // Only the tail of the 84 instructions executed is kept
+        assert_eq!(core.trace.len(), TRACE_TAIL);
+        assert_eq!(core.trace[TRACE_TAIL - 3..], ["0005  mov ax, 10", "0006  mov bx, 3", "0007  syscall"]);
+        assert_eq!(core.disassembly, faulting.disassemble(&DisasmOptions { current_values: true }).lines().collect::<Vec<&str>>());
+        assert_eq!(core.memory, faulting.memory_unit.data_bus.data);
+
+        let text = core.to_string();
+        let loaded = CoreDump::load(&text).unwrap();
+        assert_eq!(loaded, core, "{}", text);
+        assert_eq!(loaded.register(Register::CX), 20);
+        assert_eq!(loaded.read_label("count"), Some(Data::Word(20)));
+        assert_eq!(loaded.read_label("total"), None);
+        assert_eq!(loaded.disassemble_around(1), "   0006  mov bx, 3\n=> 0007  syscall\n");
+        assert!(loaded.disassemble_around(3).lines().next().unwrap().starts_with("   0004  jnz 1"));
+
+        for broken in [text.replace("fault ip: 7", "fault ip: seven"), text.replace("[trace]\n", ""), text.replace(":00000001FF", ":00000001FE")] {
+            assert!(CoreDump::load(&broken).is_err());
+        }
+    }
+
+    #[test]
+    fn golden_asm_header() {
+        let header = syscall_abi::asm_header();
+        compare_golden("asm_header", &header);
+
+        // Every name the guest includes carries the value the dispatcher uses
+        let constants: HashMap<&str, u32> = header.lines().skip(1)
+            .map(|line| {
+                let (name, value) = line.split_once(" equ ").unwrap();
+                (name.trim_end(), value.parse().unwrap())
+            })
+            .collect();
+        assert_eq!(constants.len(), 16);
+        assert_eq!(constants["SYS_READ"], syscall_abi::SYS_READ as u32);
+        assert_eq!(constants["SYS_WRITE"], syscall_abi::SYS_WRITE as u32);
+        assert_eq!(constants["SYS_ABORT"], syscall_abi::SYS_ABORT as u32);
+        assert_eq!(constants["SYS_EXIT"], syscall_abi::SYS_EXIT as u32);
+        assert_eq!(constants["SYS_ERROR"], syscall_abi::ERROR as u32);
+        assert_eq!(constants["LOG_ERROR"], syscall_abi::LOG_ERROR);
+    }
+}
@@ -0,0 +1,50 @@
+//! Writes the core of a faulting program and inspects it with `cpu debug --core` in a process of its own.
+
+use std::collections::HashMap;
+use std::process::Command;
+
+use cpu::{syscall_abi, CpuBuilder, CpuError, Data, Instruction, MemOp, Operand, Register, Verbosity, IS};
+
+fn reg(register: Register) -> Operand {
+    Operand::Register(register)
+}
+
+fn word(value: u16) -> Operand {
+    Operand::Immediate(Data::Word(value))
+}
+
+#[test]
+fn the_debugger_reads_the_registers_and_code_around_the_fault_from_a_core_alone() {
+    // Counts to 5, then aborts with code 3
+    let mut cpu = CpuBuilder::new(HashMap::from([("count".to_string(), Data::Word(0))]), vec![
+        Instruction::new(IS::Mov, vec![reg(Register::CX), word(0)]),
+        Instruction::new(IS::Add, vec![reg(Register::CX), word(1)]),
+        Instruction::new(IS::Add, vec![Operand::Memory(MemOp::Address("count".to_string())), word(1)]),
+        Instruction::new(IS::Cmp, vec![reg(Register::CX), word(5)]),
+        Instruction::new(IS::Jnz, vec![word(1)]),
+        Instruction::new(IS::Mov, vec![reg(syscall_abi::NUMBER), word(syscall_abi::SYS_ABORT as u16)]),
+        Instruction::new(IS::Mov, vec![reg(syscall_abi::ARG1), word(3)]),
+        Instruction::new(IS::Syscall, vec![]),
+    ]).verbosity(Verbosity::Quiet).build().unwrap();
+    let fault = cpu.run().unwrap_err();
+    assert!(matches!(fault.error(), CpuError::Aborted(3)), "{:?}", fault.error());
+    let path = std::env::temp_dir().join(format!("cpu-core-{}.txt", std::process::id()));
+    std::fs::write(&path, cpu.core_dump(&fault).to_string()).unwrap();
+    drop(cpu);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_cpu")).arg("debug").arg("--core").arg(&path).output().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let report = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines[0], "Fault: Aborted(3) at instruction 7");
+    assert!(lines[1].starts_with("registers: AX=0x000A BX=0x0003 CX=0x0005 "), "{}", report);
+    let around = &lines[lines.len() - 4..];
+    assert!(around[0].starts_with("   0004  jnz 1"), "{}", report);
+    assert!(around[1].starts_with("   0005  mov ax, 10"), "{}", report);
+    assert_eq!(around[2..], ["   0006  mov bx, 3", "=> 0007  syscall"]);
+
+    // A missing core is reported rather than inspected
+    let output = Command::new(env!("CARGO_BIN_EXE_cpu")).arg("debug").arg("--core").arg(&path).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+}
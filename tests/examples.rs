@@ -0,0 +1,193 @@
+//! Runs the programs of `examples/` through the library, checking what they print and leave in memory.
+
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+use cpu::{CpuError, CpuTestBed, Data, Register, StopReason, CPU, IS};
+
+// Each example is compiled in as a module, leaving its `main` unused
+#[path = "../examples/hello_world.rs"]
+#[allow(dead_code)]
+mod hello_world;
+#[path = "../examples/arithmetic.rs"]
+#[allow(dead_code)]
+mod arithmetic;
+#[path = "../examples/loop.rs"]
+#[allow(dead_code)]
+mod r#loop;
+#[path = "../examples/subroutine.rs"]
+#[allow(dead_code)]
+mod subroutine;
+#[path = "../examples/int_to_str.rs"]
+#[allow(dead_code)]
+mod int_to_str;
+#[path = "../examples/label_lookup.rs"]
+#[allow(dead_code)]
+mod label_lookup;
+
+/// Guest output kept for the test to inspect after the CPU has taken its writer
+#[derive(Clone, Default)]
+struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs a built example, checking it finishes within its instruction limit, and returns what it printed
+fn run(cpu: &mut CPU, output: &SharedOutput, limit: u64) -> String {
+    let outcome = cpu.run().unwrap();
+    assert!(outcome.instructions < limit, "{} instructions", outcome.instructions);
+    String::from_utf8(output.0.borrow().clone()).unwrap()
+}
+
+#[test]
+fn hello_world_writes_its_message() {
+    let output = SharedOutput::default();
+    let mut cpu = hello_world::cpu(output.clone()).unwrap();
+    assert_eq!(run(&mut cpu, &output, hello_world::INSTRUCTION_LIMIT), "Hello, world!\n");
+}
+
+#[test]
+fn arithmetic_totals_the_order_in_memory() {
+    let output = SharedOutput::default();
+    let mut cpu = arithmetic::cpu(output.clone()).unwrap();
+    assert_eq!(run(&mut cpu, &output, arithmetic::INSTRUCTION_LIMIT), "");
+    assert_eq!(cpu.read_label("subtotal").unwrap(), Data::Word(2495));
+    assert_eq!(cpu.read_label("total").unwrap(), Data::Word(2195));
+    assert_eq!(cpu.read_label("orders").unwrap(), Data::Word(42));
+}
+
+#[test]
+fn loop_sums_up_to_the_last_number() {
+    let output = SharedOutput::default();
+    let mut cpu = r#loop::cpu(output.clone()).unwrap();
+    assert_eq!(run(&mut cpu, &output, r#loop::INSTRUCTION_LIMIT), "");
+    let last = r#loop::LAST as u32;
+    assert_eq!(cpu.read_label("sum").unwrap(), Data::Word((last * (last + 1) / 2) as u16));
+    assert_eq!(cpu.register(Register::CX), last + 1);
+}
+
+#[test]
+fn subroutine_returns_the_largest_number() {
+    let output = SharedOutput::default();
+    let mut cpu = subroutine::cpu(output.clone()).unwrap();
+    assert_eq!(run(&mut cpu, &output, subroutine::INSTRUCTION_LIMIT), "");
+    assert_eq!(cpu.read_label("result").unwrap(), Data::Word(42));
+    assert!(cpu.validate().is_empty(), "{:?}", cpu.validate());
+}
+
+#[test]
+fn int_to_str_prints_the_sum_of_the_documented_program() {
+    let output = SharedOutput::default();
+    let mut cpu = int_to_str::cpu(10, 20, output.clone()).unwrap();
+    assert_eq!(run(&mut cpu, &output, int_to_str::INSTRUCTION_LIMIT), "30\n");
+    assert_eq!(cpu.read_label("result").unwrap(), Data::Dword(30));
+
+    // One digit, and as many digits as a word sum can have
+    for (num1, num2, printed) in [(0, 7, "7\n"), (65535, 65535, "131070\n")] {
+        let output = SharedOutput::default();
+        let mut cpu = int_to_str::cpu(num1, num2, output.clone()).unwrap();
+        assert_eq!(run(&mut cpu, &output, int_to_str::INSTRUCTION_LIMIT), printed);
+    }
+}
+
+#[test]
+fn int_to_str_stops_once_at_its_subroutine_and_again_only_when_asked() {
+    let output = SharedOutput::default();
+    let mut cpu = int_to_str::cpu(10, 20, output.clone()).unwrap();
+    let subroutine = cpu.code_label("int_to_str").unwrap();
+    assert_eq!(cpu.run_until_label("int_to_str").unwrap(), StopReason::Breakpoint(subroutine));
+    assert_eq!(cpu.register(Register::EAX), 30);
+
+    // The digit loop runs twice for 30, stopping at its first instruction only while asked to
+    let digit_loop = subroutine + 1;
+    assert_eq!(cpu.run_until_ip(digit_loop).unwrap(), StopReason::Breakpoint(digit_loop));
+    assert_eq!(cpu.run_until_ip(digit_loop).unwrap(), StopReason::Breakpoint(digit_loop));
+    assert_eq!(cpu.register(Register::EAX), 3);
+    assert_eq!(cpu.run_debug().unwrap(), StopReason::Exited(0));
+    assert_eq!(String::from_utf8(output.0.borrow().clone()).unwrap(), "30\n");
+
+    // A one-shot stop that never fires doesn't outlive its run either
+    let mut cpu = int_to_str::cpu(10, 20, SharedOutput::default()).unwrap();
+    assert_eq!(cpu.run_until_ip(0).unwrap(), StopReason::Exited(0));
+    assert!(!cpu.remove_breakpoint(0));
+    let fault = cpu.run_until_label("int_to_sr").unwrap_err();
+    assert!(matches!(fault.error(), CpuError::UndeclaredLabel(label, Some(suggestion)) if label == "int_to_sr" && suggestion == "int_to_str"));
+}
+
+#[test]
+fn int_to_str_stops_once_at_each_of_its_syscalls() {
+    let output = SharedOutput::default();
+    let mut cpu = int_to_str::cpu(10, 20, output.clone()).unwrap();
+    let syscalls = cpu.find_instructions(|instruction| matches!(instruction.opcode(), IS::Syscall));
+    assert_eq!(syscalls, [12, 17, 20]);
+    assert_eq!(cpu.code().filter(|(_, instruction)| *instruction.opcode() == IS::Syscall).count(), 3);
+    assert!(syscalls.iter().all(|index| *index < cpu.code_len()));
+    assert_eq!(cpu.instruction_at(syscalls[0]).unwrap().to_string(), "syscall");
+    assert!(cpu.instruction_at(cpu.code_len()).is_none());
+
+    for &index in &syscalls {
+        cpu.add_breakpoint(index);
+    }
+    let mut stops = Vec::new();
+    let exit = loop {
+        match cpu.run_debug().unwrap() {
+            StopReason::Breakpoint(index) => stops.push(index),
+            reason => break reason,
+        }
+    };
+    assert_eq!(stops, syscalls);
+    assert_eq!(exit, StopReason::Exited(0));
+    assert_eq!(String::from_utf8(output.0.borrow().clone()).unwrap(), "30\n");
+
+    // The debugger's convenience sets the same breakpoints
+    let mut cpu = int_to_str::cpu(10, 20, SharedOutput::default()).unwrap();
+    assert_eq!(cpu.break_on_syscalls(), syscalls);
+    assert_eq!(cpu.run_debug().unwrap(), StopReason::Breakpoint(12));
+}
+
+#[test]
+fn int_to_str_converts_seeded_values_in_isolation() {
+    let buffer = int_to_str::cpu(0, 0, SharedOutput::default()).unwrap().symbol("output0").unwrap().offset;
+    // The buffer starts filled so the bytes left alone can be told apart, and EBX points past its end
+    for (value, digits) in [(0, "0"), (7, "7"), (u32::MAX, "4294967295")] {
+        let mut cpu = CpuTestBed::new(int_to_str::program(0, 0, SharedOutput::default()))
+            .with_register(Register::EAX, value)
+            .with_register(Register::EBX, buffer + 10)
+            .with_memory("output0", &[b'?'; 10])
+            .start_at("int_to_str")
+            .unwrap();
+        assert_eq!(cpu.run_debug().unwrap(), StopReason::ReturnedToHost);
+
+        let first_digit = buffer + 10 - digits.len() as u32;
+        assert_eq!(cpu.register(Register::EBX), first_digit);
+        assert_eq!(cpu.register(Register::EAX), 0);
+        assert_eq!(cpu.register(Register::ECX), 10);
+        let written = cpu.hexdump(buffer as usize, 10).unwrap();
+        let expected: Vec<String> = "??????????"[digits.len()..].bytes().chain(digits.bytes()).map(|byte| format!("{:02X}", byte)).collect();
+        assert_eq!(written, format!("[{}]", expected.join(" ")));
+        // Nothing else of the program ran
+        assert_eq!(cpu.read_label("result").unwrap(), Data::Dword(0));
+    }
+
+    let bed = CpuTestBed::new(int_to_str::program(0, 0, SharedOutput::default()));
+    assert!(bed.start_at("int_to_string").is_err());
+    let bed = CpuTestBed::new(int_to_str::program(0, 0, SharedOutput::default())).with_memory("output2", &[0; 64 * 1024]);
+    assert!(bed.start_at("int_to_str").is_err());
+}
+
+#[test]
+fn label_lookup_loops_add_the_same_through_either_operand() {
+    for operand in [label_lookup::interned(), label_lookup::by_name()] {
+        let mut cpu = label_lookup::cpu(100, operand).unwrap();
+        assert_eq!(cpu.run().unwrap().instructions, 401);
+        assert_eq!(cpu.register(Register::BX), 300);
+    }
+}
@@ -0,0 +1,99 @@
+//! Fuzz harness for the decoder and the memory unit, run on generated and known crashing programs.
+//!
+//! Every program must build and run to an exit or a fault under the instruction limit, never panic.
+
+use std::collections::HashMap;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use cpu::{arbitrary_program, CpuBuilder, Data, DataWidth, Instruction, MemOp, Operand, Register, Verbosity, IS};
+
+/// Instructions a generated program may run, enough for the loops it generates to reach the limit quickly
+const INSTRUCTION_LIMIT: u64 = 1000;
+
+/// Builds and runs a program without any output or input, panicking only if the CPU does
+fn run_program(data_section: HashMap<String, Data>, code_section: Vec<Instruction>) {
+    let Ok(mut cpu) = CpuBuilder::new(data_section, code_section)
+        .verbosity(Verbosity::Quiet)
+        .instruction_limit(INSTRUCTION_LIMIT)
+        .guest_output(std::io::sink())
+        .guest_input(std::io::empty())
+        .build() else {
+        return;
+    };
+    // Faults, including reaching the instruction limit, are expected
+    let _ = cpu.run();
+}
+
+/// Runs the program generated from the input, reporting the input and its program if it panics
+fn run_input(input: &[u8]) {
+    let (data_section, code_section) = arbitrary_program(input);
+    let program = format!("{:?}\n{:#?}", data_section, code_section);
+    if catch_unwind(AssertUnwindSafe(|| run_program(data_section, code_section))).is_err() {
+        panic!("input {:02X?} panicked with the program\n{}", input, program);
+    }
+}
+
+#[test]
+fn generated_programs_never_panic() {
+    // xorshift64, seeded so failures reproduce
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    for _ in 0..5000 {
+        let length = (next() % 256) as usize;
+        let input: Vec<u8> = (0..length).map(|_| next() as u8).collect();
+        run_input(&input);
+    }
+    run_input(&[]);
+}
+
+/// Programs that crashed or could crash the CPU, kept as regression inputs
+fn seed_corpus() -> Vec<(HashMap<String, Data>, Vec<Instruction>)> {
+    let label = |name: &str| name.to_string();
+    let mem = |name: &str| Operand::Memory(MemOp::Address(name.to_string()));
+    let reg = Operand::Register;
+    let data = HashMap::from([(label("a"), Data::Word(1)), (label("b"), Data::Dword(2))]);
+    vec![
+        // Memory to memory moves
+        (data.clone(), vec![Instruction::new(IS::Mov, vec![mem("a"), mem("b")])]),
+        // A dword immediate into a word register, and a register into a label of another width
+        (data.clone(), vec![
+            Instruction::new(IS::Mov, vec![reg(Register::AX), Operand::Immediate(Data::Dword(0x1_0000))]),
+            Instruction::new(IS::Add, vec![mem("a"), reg(Register::EAX)]),
+        ]),
+        // Displacements pointing far outside memory, both ways
+        (data.clone(), vec![
+            Instruction::new(IS::Mov, vec![Operand::Memory(MemOp::Ptr(DataWidth::Dword, label("b"), i32::MAX)), reg(Register::EAX)]),
+            Instruction::new(IS::Mov, vec![reg(Register::EAX), Operand::Memory(MemOp::Indirect(DataWidth::Dword, Register::EBX, i32::MIN))]),
+        ]),
+        // Jumps out of range and onto themselves
+        (data.clone(), vec![Instruction::new(IS::Jmp, vec![Operand::JmpRel(i32::MIN)])]),
+        (data.clone(), vec![Instruction::new(IS::Jmp, vec![Operand::JmpRel(-1)])]),
+        // Missing and extra operands, and unsupported instructions
+        (data.clone(), vec![Instruction::new(IS::Add, vec![])]),
+        (data.clone(), vec![Instruction::new(IS::Ret, vec![reg(Register::AX), reg(Register::BX), reg(Register::CX)])]),
+        (data.clone(), vec![Instruction::new(IS::Mul, vec![reg(Register::AX)])]),
+        // Syscalls on buffers running past memory
+        (data, vec![
+            Instruction::new(IS::Mov, vec![reg(Register::CX), Operand::Immediate(Data::Word(0xFFFF))]),
+            Instruction::new(IS::Mov, vec![reg(Register::DX), Operand::Immediate(Data::Word(0xFFFF))]),
+            Instruction::new(IS::Mov, vec![reg(Register::AX), Operand::Immediate(Data::Word(5))]),
+            Instruction::new(IS::Syscall, vec![]),
+            Instruction::new(IS::Mov, vec![reg(Register::AX), Operand::Immediate(Data::Word(3))]),
+            Instruction::new(IS::Syscall, vec![]),
+        ]),
+    ]
+}
+
+#[test]
+fn seed_corpus_never_panics() {
+    for (index, (data_section, code_section)) in seed_corpus().into_iter().enumerate() {
+        if catch_unwind(AssertUnwindSafe(|| run_program(data_section, code_section))).is_err() {
+            panic!("seed {} panicked", index);
+        }
+    }
+}
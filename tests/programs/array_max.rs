@@ -0,0 +1,31 @@
+use cpu::{Data, DataWidth, Register, IS};
+
+use crate::{element, mem, offset, op, ptr, reg, run, word};
+
+#[test]
+fn array_max_scans_a_word_array_through_a_pointer() {
+    let values: [u16; 6] = [12, 7, 300, 299, 0, 45];
+    let labels: Vec<String> = (0..values.len()).map(|index| element("array", index)).collect();
+    let mut data: Vec<(&str, Data)> = labels.iter().zip(values).map(|(label, value)| (label.as_str(), Data::Word(value))).collect();
+    data.push(("max", Data::Word(0)));
+
+    let cpu = run(&data, vec![
+        op(IS::Mov, vec![reg(Register::BX), offset("array00")]),
+        op(IS::Mov, vec![reg(Register::CX), word(values.len() as u16)]),
+        op(IS::Mov, vec![reg(Register::AX), word(0)]),
+        // loop: keep the element if it is above the current maximum
+        op(IS::Cmp, vec![reg(Register::AX), ptr(DataWidth::Word, Register::BX, 0)]),
+        op(IS::Jnc, vec![word(6)]),
+        op(IS::Mov, vec![reg(Register::AX), ptr(DataWidth::Word, Register::BX, 0)]),
+        // next:
+        op(IS::Add, vec![reg(Register::BX), word(2)]),
+        op(IS::Sub, vec![reg(Register::CX), word(1)]),
+        op(IS::Jnz, vec![word(3)]),
+        op(IS::Mov, vec![mem("max"), reg(Register::AX)]),
+    ]);
+    assert_eq!(cpu.read_label("max").unwrap(), Data::Word(300));
+    // The array is left untouched
+    for (label, value) in labels.iter().zip(values) {
+        assert_eq!(cpu.read_label(label).unwrap(), Data::Word(value));
+    }
+}
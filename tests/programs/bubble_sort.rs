@@ -0,0 +1,51 @@
+use cpu::{Data, DataWidth, Operand, Register, IS};
+
+use crate::{element, offset, op, ptr, reg, run, word};
+
+/// Sorts the words in place in ascending order, returning them along with the number of passes over the array
+fn bubble_sort(values: &[u16]) -> (Vec<u16>, u32) {
+    let labels: Vec<String> = (0..values.len()).map(|index| element("array", index)).collect();
+    let data: Vec<(&str, Data)> = labels.iter().zip(values).map(|(label, value)| (label.as_str(), Data::Word(*value))).collect();
+
+    let cpu = run(&data, vec![
+        // pass: DX is set when the pass swapped a pair, EDX counts the passes
+        op(IS::Add, vec![reg(Register::EDX), Operand::Immediate(Data::Dword(1))]),
+        op(IS::Mov, vec![reg(Register::DX), word(0)]),
+        op(IS::Mov, vec![reg(Register::BX), offset("array00")]),
+        op(IS::Mov, vec![reg(Register::CX), word(values.len() as u16 - 1)]),
+        // pair: swap the element with the next one if it is above it
+        op(IS::Mov, vec![reg(Register::AX), ptr(DataWidth::Word, Register::BX, 0)]),
+        op(IS::Cmp, vec![reg(Register::AX), ptr(DataWidth::Word, Register::BX, 2)]),
+        op(IS::Jc, vec![word(12)]),
+        op(IS::Jz, vec![word(12)]),
+        op(IS::Mov, vec![reg(Register::EAX), ptr(DataWidth::Word, Register::BX, 2)]),
+        op(IS::Mov, vec![ptr(DataWidth::Word, Register::BX, 0), reg(Register::EAX)]),
+        op(IS::Mov, vec![ptr(DataWidth::Word, Register::BX, 2), reg(Register::AX)]),
+        op(IS::Mov, vec![reg(Register::DX), word(1)]),
+        // next:
+        op(IS::Add, vec![reg(Register::BX), word(2)]),
+        op(IS::Sub, vec![reg(Register::CX), word(1)]),
+        op(IS::Jnz, vec![word(4)]),
+        op(IS::Cmp, vec![reg(Register::DX), word(0)]),
+        op(IS::Jnz, vec![word(0)]),
+    ]);
+    let sorted = labels.iter()
+        .map(|label| match cpu.read_label(label).unwrap() {
+            Data::Word(value) => value,
+            data => panic!("{} holds {:?}", label, data),
+        })
+        .collect();
+    (sorted, cpu.register(Register::EDX))
+}
+
+#[test]
+fn bubble_sort_orders_a_word_array_in_place() {
+    let (sorted, passes) = bubble_sort(&[500, 3, 42, 3, 65535, 0, 17]);
+    assert_eq!(sorted, vec![0, 3, 3, 17, 42, 500, 65535]);
+    assert!(passes > 1);
+
+    // A sorted array takes a single pass without swaps
+    assert_eq!(bubble_sort(&[1, 2, 3, 4]), (vec![1, 2, 3, 4], 1));
+    // A reversed one needs a pass per element
+    assert_eq!(bubble_sort(&[4, 3, 2, 1]), (vec![1, 2, 3, 4], 4));
+}
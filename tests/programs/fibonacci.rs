@@ -0,0 +1,27 @@
+use cpu::{Data, Register, IS};
+
+use crate::{mem, op, reg, run, word};
+
+#[test]
+fn fibonacci_computes_the_nth_number_iteratively() {
+    for (n, expected) in [(0, 0), (1, 1), (2, 1), (10, 55), (24, 46368)] {
+        let cpu = run(&[("n", Data::Word(n)), ("result", Data::Word(0))], vec![
+            op(IS::Mov, vec![reg(Register::AX), word(0)]),
+            op(IS::Mov, vec![reg(Register::BX), word(1)]),
+            op(IS::Mov, vec![reg(Register::CX), mem("n")]),
+            // loop: while cx != 0
+            op(IS::Cmp, vec![reg(Register::CX), word(0)]),
+            op(IS::Jz, vec![word(11)]),
+            op(IS::Mov, vec![reg(Register::DX), reg(Register::AX)]),
+            op(IS::Add, vec![reg(Register::DX), reg(Register::BX)]),
+            op(IS::Mov, vec![reg(Register::AX), reg(Register::BX)]),
+            op(IS::Mov, vec![reg(Register::BX), reg(Register::DX)]),
+            op(IS::Sub, vec![reg(Register::CX), word(1)]),
+            op(IS::Jmp, vec![word(3)]),
+            // done:
+            op(IS::Mov, vec![mem("result"), reg(Register::AX)]),
+        ]);
+        assert_eq!(cpu.read_label("result").unwrap(), Data::Word(expected), "fib({})", n);
+        assert_eq!(cpu.register(Register::CX), 0);
+    }
+}
@@ -0,0 +1,26 @@
+use cpu::{Data, Register, IS};
+
+use crate::{mem, op, reg, run, word};
+
+#[test]
+fn gcd_subtracts_the_smaller_number_until_both_are_equal() {
+    for (x, y, expected) in [(48, 18, 6), (18, 48, 6), (17, 5, 1), (21, 21, 21), (1071, 462, 21)] {
+        let cpu = run(&[("x", Data::Word(x)), ("y", Data::Word(y)), ("result", Data::Word(0))], vec![
+            op(IS::Mov, vec![reg(Register::AX), mem("x")]),
+            op(IS::Mov, vec![reg(Register::BX), mem("y")]),
+            // loop:
+            op(IS::Cmp, vec![reg(Register::AX), reg(Register::BX)]),
+            op(IS::Jz, vec![word(9)]),
+            op(IS::Jc, vec![word(7)]),
+            op(IS::Sub, vec![reg(Register::AX), reg(Register::BX)]),
+            op(IS::Jmp, vec![word(2)]),
+            // ax below bx:
+            op(IS::Sub, vec![reg(Register::BX), reg(Register::AX)]),
+            op(IS::Jmp, vec![word(2)]),
+            // done:
+            op(IS::Mov, vec![mem("result"), reg(Register::AX)]),
+        ]);
+        assert_eq!(cpu.read_label("result").unwrap(), Data::Word(expected), "gcd({}, {})", x, y);
+        assert_eq!(cpu.register(Register::BX), expected as u32);
+    }
+}
@@ -0,0 +1,49 @@
+//! Classic small algorithms run on the CPU, each asserting the final state of its registers and memory.
+
+mod array_max;
+mod bubble_sort;
+mod fibonacci;
+mod gcd;
+mod string_reverse;
+
+use std::collections::HashMap;
+
+use cpu::{CpuBuilder, Data, DataWidth, Instruction, MemOp, Operand, Register, Verbosity, CPU, IS};
+
+/// Builds a quiet CPU with the data section and code, runs it to completion and returns it
+pub fn run(data: &[(&str, Data)], code: Vec<Instruction>) -> CPU {
+    let data: HashMap<String, Data> = data.iter().map(|(label, data)| (label.to_string(), data.clone())).collect();
+    let mut cpu = CpuBuilder::new(data, code).verbosity(Verbosity::Quiet).build().unwrap();
+    cpu.run().unwrap();
+    cpu
+}
+
+pub fn op(opcode: IS, operands: Vec<Operand>) -> Instruction {
+    Instruction::new(opcode, operands)
+}
+
+pub fn reg(register: Register) -> Operand {
+    Operand::Register(register)
+}
+
+pub fn word(value: u16) -> Operand {
+    Operand::Immediate(Data::Word(value))
+}
+
+pub fn mem(label: &str) -> Operand {
+    Operand::Memory(MemOp::Address(label.to_string()))
+}
+
+pub fn offset(label: &str) -> Operand {
+    Operand::LabelOffset(label.to_string())
+}
+
+/// `width ptr [register+displacement]`
+pub fn ptr(width: DataWidth, register: Register, displacement: i32) -> Operand {
+    Operand::Memory(MemOp::Indirect(width, register, displacement))
+}
+
+/// Labels of the elements of an array, `name00`, `name01`, ... so they are laid out one after the other
+pub fn element(name: &str, index: usize) -> String {
+    format!("{}{:02}", name, index)
+}
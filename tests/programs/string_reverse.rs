@@ -0,0 +1,38 @@
+use cpu::{Data, DataWidth, Register, IS};
+
+use crate::{element, offset, op, ptr, reg, run, word};
+
+/// Reverses the string in place with a pointer walking forward from its start and one walking back from its end
+fn reverse(text: &str) -> String {
+    let labels: Vec<String> = (0..text.len()).map(|index| element("text", index)).collect();
+    let data: Vec<(&str, Data)> = labels.iter().zip(text.bytes()).map(|(label, byte)| (label.as_str(), Data::Byte(byte))).collect();
+
+    let cpu = run(&data, vec![
+        op(IS::Mov, vec![reg(Register::BX), offset("text00")]),
+        op(IS::Mov, vec![reg(Register::CX), offset(labels.last().unwrap())]),
+        // loop: until the pointers meet
+        op(IS::Cmp, vec![reg(Register::BX), reg(Register::CX)]),
+        op(IS::Jnc, vec![word(11)]),
+        op(IS::Mov, vec![reg(Register::AX), ptr(DataWidth::Byte, Register::BX, 0)]),
+        op(IS::Mov, vec![reg(Register::DX), ptr(DataWidth::Byte, Register::CX, 0)]),
+        op(IS::Mov, vec![ptr(DataWidth::Byte, Register::BX, 0), reg(Register::DX)]),
+        op(IS::Mov, vec![ptr(DataWidth::Byte, Register::CX, 0), reg(Register::AX)]),
+        op(IS::Add, vec![reg(Register::BX), word(1)]),
+        op(IS::Sub, vec![reg(Register::CX), word(1)]),
+        op(IS::Jmp, vec![word(2)]),
+    ]);
+    let bytes: Vec<u8> = labels.iter()
+        .map(|label| match cpu.read_label(label).unwrap() {
+            Data::Byte(byte) => byte,
+            data => panic!("{} holds {:?}", label, data),
+        })
+        .collect();
+    String::from_utf8(bytes).unwrap()
+}
+
+#[test]
+fn string_reverse_swaps_bytes_from_both_ends() {
+    assert_eq!(reverse("hello"), "olleh");
+    assert_eq!(reverse("abcdef"), "fedcba");
+    assert_eq!(reverse("x"), "x");
+}
@@ -0,0 +1,136 @@
+//! Drives the library the way an embedder does, through its public API alone.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::io::Write;
+use std::rc::Rc;
+use std::time::Duration;
+
+use cpu::{Clock, CpuBuilder, CpuError, Data, DataWidth, Instruction, Operand, Register, Verbosity, CPU, FLAGS, FLAG_NAMES, IS};
+
+/// Trace output kept for the test to inspect after the CPU has taken its writer
+#[derive(Clone, Default)]
+struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A clock moving forward by `step` every time it is read
+#[derive(Debug)]
+struct SteppingClock {
+    time: Cell<Duration>,
+    step: Duration,
+}
+
+impl Clock for SteppingClock {
+    fn now(&self) -> Duration {
+        let time = self.time.get();
+        self.time.set(time + self.step);
+        time
+    }
+}
+
+fn reg(register: Register) -> Operand {
+    Operand::Register(register)
+}
+
+fn word(value: u16) -> Operand {
+    Operand::Immediate(Data::Word(value))
+}
+
+#[test]
+fn data_converts_to_and_from_bytes_and_refuses_to_overflow() {
+    assert_eq!(Data::Dword(0x1234_5678).to_bytes(), [0x78, 0x56, 0x34, 0x12]);
+    assert_eq!(Data::Byte(0xAB).to_bytes(), [0xAB]);
+    assert_eq!(Data::from_bytes(DataWidth::Word, &[0x34, 0x12]), Ok(Data::Word(0x1234)));
+    assert!(Data::from_bytes(DataWidth::Word, &[0x34]).is_err());
+    assert!(Data::from_bytes(DataWidth::Byte, &[1, 2]).is_err());
+
+    assert_eq!(Data::Word(0xFFFE).checked_add(1), Ok(Data::Word(0xFFFF)));
+    assert!(Data::Word(0xFFFF).checked_add(1).is_err());
+    assert!(Data::Byte(0x80).checked_add(0x80).is_err());
+    assert!(Data::Dword(u32::MAX).checked_add(1).is_err());
+    assert_eq!(Data::split_dword(0xDEAD_BEEF), (Data::Word(0xBEEF), Data::Word(0xDEAD)));
+}
+
+#[test]
+fn register_and_flag_codes_round_trip() {
+    let registers = [Register::AX, Register::BX, Register::CX, Register::DX, Register::EAX, Register::EBX, Register::ECX, Register::EDX];
+    for (code, register) in registers.into_iter().enumerate() {
+        assert_eq!(register.code(), code as u8);
+        assert_eq!(Register::from_code(code as u8), Some(register));
+    }
+    assert_eq!(Register::from_code(8), None);
+
+    // Flag codes are the indices of their names
+    for (code, name) in FLAG_NAMES.iter().enumerate() {
+        let flag = FLAGS::from_code(code as u8).unwrap();
+        assert_eq!(flag.code(), code as u8);
+        assert!(format!("{:?}", flag).starts_with(name), "{:?} is not {}", flag, name);
+    }
+    assert!(matches!(FLAGS::from_code(2), Some(FLAGS::ZF(0))));
+    assert!(FLAGS::from_code(9).is_none());
+}
+
+#[test]
+fn builder_options_reach_the_cpu() {
+    // Default options, with the label's offset loaded as a value
+    let data = HashMap::from([("first".to_string(), Data::Dword(0)), ("second".to_string(), Data::Word(7))]);
+    let mut cpu = CPU::new(data.clone(), vec![Instruction::new(IS::Mov, vec![reg(Register::AX), Operand::LabelOffset("second".to_string())])]).unwrap();
+    cpu.run().unwrap();
+    assert_eq!(cpu.register(Register::AX), 4);
+
+    // The result is traced to the configured output, with memory dumps cut at the dump limit
+    let output = SharedOutput::default();
+    let mut cpu = CpuBuilder::new(data.clone(), vec![Instruction::new(IS::Mov, vec![reg(Register::BX), word(9)])])
+        .verbosity("result".parse().unwrap())
+        .output(output.clone())
+        .dump_limit(2)
+        .build()
+        .unwrap();
+    cpu.run().unwrap();
+    let trace = String::from_utf8(output.0.borrow().clone()).unwrap();
+    assert_eq!(trace.lines().collect::<Vec<_>>(), [
+        "Registers: AX=0x0000 BX=0x0009 CX=0x0000 DX=0x0000 EAX=0x00000000 EBX=0x00000000 ECX=0x00000000 EDX=0x00000000",
+        "Exit code: 0",
+    ]);
+    assert_eq!(cpu.hexdump(4, 2).unwrap(), "[07 00]");
+    assert_eq!(cpu.hexdump(0, 6).unwrap(), "[00 00 ... (6 bytes)]");
+    assert!("loud".parse::<Verbosity>().is_err());
+
+    // Guest assertions fault only while enabled
+    let asserting = vec![Instruction::new(IS::AssertReg, vec![reg(Register::AX), word(1)])];
+    for enabled in [false, true] {
+        let mut cpu = CpuBuilder::new(HashMap::new(), asserting.clone())
+            .verbosity(Verbosity::Quiet)
+            .guest_assertions(enabled)
+            .build()
+            .unwrap();
+        let result = cpu.run();
+        assert_eq!(result.is_err(), enabled);
+        if let Err(fault) = result {
+            assert!(matches!(fault.error(), CpuError::GuestAssertionFailed(0, 1, 0)), "{:?}", fault.error());
+        }
+    }
+}
+
+#[test]
+fn a_run_with_a_timeout_reads_the_clock_every_interval() {
+    // 10ms pass on every read of the clock, which is read after every 4 instructions of the endless loop
+    let clock = SteppingClock { time: Cell::default(), step: Duration::from_millis(10) };
+    let mut cpu = CpuBuilder::new(HashMap::new(), vec![Instruction::new(IS::Jmp, vec![Operand::JmpRel(-1)])])
+        .verbosity(Verbosity::Quiet)
+        .clock(clock)
+        .timeout_check_interval(4)
+        .build()
+        .unwrap();
+    let fault = cpu.run_with_timeout(Duration::from_millis(25)).unwrap_err();
+    assert!(matches!(fault.error(), CpuError::Timeout(12)), "{:?}", fault.error());
+}